@@ -6,7 +6,7 @@ use std::rc::Rc;
 
 use clap::{App, Arg};
 use jmespath::Rcvar;
-use jmespath::{compile, Variable};
+use jmespath::{compile, Profiler, TraceLog, Variable};
 
 macro_rules! die(
     ($msg:expr) => (
@@ -44,6 +44,19 @@ fn main() {
                 .long("ast")
                 .multiple(false),
         )
+        .arg(
+            Arg::with_name("trace")
+                .help("Print a log of how each node of the expression is evaluated to stderr.")
+                .long("trace")
+                .multiple(false),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .help("Print the number of calls and time spent evaluating each node to stderr.")
+                .long("profile")
+                .conflicts_with("trace")
+                .multiple(false),
+        )
         .arg(
             Arg::with_name("expr-file")
                 .help("Read JMESPath expression from the specified file.")
@@ -81,7 +94,21 @@ fn main() {
 
     let json = Rc::new(get_json(matches.value_of("filename")));
 
-    match expr.search(&json) {
+    let result = if matches.is_present("trace") {
+        let mut log = TraceLog::new();
+        let result = expr.search_with_context(&json, expr.context().with_tracer(&mut log));
+        eprint!("{}", log);
+        result
+    } else if matches.is_present("profile") {
+        let mut profiler = Profiler::new(expr.as_str());
+        let result = expr.search_with_context(&json, expr.context().with_tracer(&mut profiler));
+        eprint!("{}", profiler);
+        result
+    } else {
+        expr.search(&json)
+    };
+
+    match result {
         Err(e) => die!(e.to_string()),
         Ok(result) => show_result(result, matches.is_present("unquoted")),
     }
@@ -93,7 +120,7 @@ fn show_result(result: Rcvar, unquoted: bool) {
     } else {
         let mut out = io::stdout();
         serde_json::to_writer_pretty(&mut out, &result)
-            .map(|_| out.write(&[b'\n']))
+            .map(|_| out.write(b"\n"))
             .map_err(|e| die!(format!("Error converting result to string: {}", e)))
             .ok();
     }
@@ -15,6 +15,15 @@ fn get_output(args: Vec<&str>) -> Result<String, String> {
     }
 }
 
+fn get_stdout_and_stderr(args: Vec<&str>) -> (String, String) {
+    let output = Command::new(JPBIN).args(args).output().unwrap();
+    assert!(output.status.success());
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
 #[test]
 fn prints_ast() {
     let output = get_output(vec!["--ast", "foo"]).unwrap();
@@ -68,6 +77,54 @@ fn unquoted_does_nothing_for_non_strings() {
     assert_eq!("[\n  \"foo\"\n]\n", output);
 }
 
+#[test]
+fn prints_trace_to_stderr() {
+    let (stdout, stderr) = get_stdout_and_stderr(vec![
+        "-e",
+        "tests/fixtures/valid-expression",
+        "-f",
+        "tests/fixtures/valid-json",
+        "--trace",
+    ]);
+    assert_eq!("\"bar\"\n", stdout);
+    assert_eq!(
+        vec![
+            r#"Subexpr foo.baz (offset 3) given {"foo":{"baz":"bar"}}"#,
+            r#"  Field foo (offset 0) given {"foo":{"baz":"bar"}}"#,
+            r#"  Field foo (offset 0) returned {"baz":"bar"}"#,
+            r#"  Field baz (offset 4) given {"baz":"bar"}"#,
+            r#"  Field baz (offset 4) returned "bar""#,
+            r#"Subexpr foo.baz (offset 3) returned "bar""#,
+        ],
+        stderr.lines().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn prints_profile_to_stderr() {
+    let (stdout, stderr) = get_stdout_and_stderr(vec![
+        "-e",
+        "tests/fixtures/valid-expression",
+        "-f",
+        "tests/fixtures/valid-json",
+        "--profile",
+    ]);
+    assert_eq!("\"bar\"\n", stdout);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(lines[0].starts_with("Field foo (offset 0): 1 calls, "));
+    assert_eq!(["foo.baz", "^", ""], lines[1..4]);
+    assert!(lines[4].starts_with("Subexpr foo.baz (offset 3): 1 calls, "));
+    assert_eq!(["foo.baz", "   ^", ""], lines[5..8]);
+    assert!(lines[8].starts_with("Field baz (offset 4): 1 calls, "));
+    assert_eq!(["foo.baz", "    ^", ""], lines[9..12]);
+}
+
+#[test]
+fn trace_and_profile_conflict() {
+    let output = get_output(vec!["--trace", "--profile", "foo"]).unwrap_err();
+    assert!(output.contains("cannot be used with"));
+}
+
 #[test]
 fn validates_json_file_exists() {
     let output = get_output(vec![
@@ -137,7 +194,7 @@ fn can_read_json_from_stdin() {
         .stdin
         .as_mut()
         .unwrap()
-        .write("{\"foo\":\"bar\"}".as_bytes())
+        .write_all("{\"foo\":\"bar\"}".as_bytes())
         .ok();
     let output = child.wait_with_output().unwrap();
     let stdout = String::from_utf8(output.stdout).unwrap();
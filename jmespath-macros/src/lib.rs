@@ -0,0 +1,207 @@
+//! Compile-time validated JMESPath expressions.
+//!
+//! The `jmespath!` macro parses an expression when your crate is compiled,
+//! so a syntax error is reported by the compiler rather than by an
+//! `unwrap()` that panics at runtime. The macro expands to a
+//! `jmespath::Expression<'static>` that is compiled the first time it is
+//! used, so both `jmespath` and `jmespath-macros` must be added to the
+//! dependencies of your project.
+//!
+//! ```
+//! use jmespath_macros::jmespath;
+//!
+//! let data = jmespath::Variable::from_json(r#"{"foo": [{"bar": 2, "baz": "a"}]}"#).unwrap();
+//! let result = jmespath!("foo[?bar > `1`].baz").search(data).unwrap();
+//! assert_eq!("[\"a\"]", result.to_string());
+//! ```
+//!
+//! Invalid expressions fail to compile, and the error points to the
+//! location of the problem in the expression:
+//!
+//! ```compile_fail
+//! use jmespath_macros::jmespath;
+//!
+//! let expr = jmespath!("foo[?bar > `1`.baz");
+//! ```
+//!
+//! Expressions use the builtin functions of `jmespath::compile()`. Calls to
+//! functions are not validated because functions are resolved when the
+//! expression is searched. When JMESPath is compiled with the `sync`
+//! feature, each expression is compiled once, shared between threads, and
+//! returned as a `&'static jmespath::Expression<'static>`. Otherwise, each
+//! thread compiles the expression the first time that it is used by the
+//! thread and the macro returns an `Rc<jmespath::Expression<'static>>`,
+//! which is dropped when the thread exits.
+//!
+//! The `Searchable` derive implements `jmespath::Searchable` for a struct
+//! with named fields, so that the struct can be searched in place using
+//! `Expression::search_searchable()` rather than being serialized first.
+//! Each field must also implement `Searchable`. Fields can be renamed with
+//! `#[jmespath(rename = "name")]` and hidden with `#[jmespath(skip)]`.
+//!
+//! ```
+//! use jmespath_macros::{jmespath, Searchable};
+//!
+//! #[derive(Searchable)]
+//! struct Server {
+//!     name: String,
+//!     #[jmespath(rename = "listenPort")]
+//!     port: u16,
+//! }
+//!
+//! let servers = vec![
+//!     Server { name: "a".to_string(), port: 80 },
+//!     Server { name: "b".to_string(), port: 8080 },
+//! ];
+//! let result = jmespath!("[?listenPort > `1024`].name")
+//!     .search_searchable(&servers)
+//!     .unwrap();
+//! assert_eq!("[\"b\"]", result.to_string());
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericParam, LitStr};
+
+/// Validates a JMESPath expression at compile time and expands to a
+/// lazily compiled `jmespath::Expression<'static>`.
+///
+/// The expression is a `&'static` reference with the `sync` feature of
+/// `jmespath`, and a thread-local `Rc` without it.
+#[proc_macro]
+pub fn jmespath(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    if let Err(e) = jmespath::parse(&literal.value()) {
+        let message = format!("Invalid JMESPath expression: {}", e);
+        return syn::Error::new(literal.span(), message)
+            .to_compile_error()
+            .into();
+    }
+    let expanded = quote! {
+        ::jmespath::__lazy_expression!(#literal)
+    };
+    expanded.into()
+}
+
+/// Derives `jmespath::Searchable` for a struct with named fields.
+#[proc_macro_derive(Searchable, attributes(jmespath))]
+pub fn derive_searchable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match searchable(input) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn searchable(mut input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(unsupported(input.ident.span())),
+        },
+        _ => return Err(unsupported(input.ident.span())),
+    };
+
+    // Each field is exposed using its name, or the name it was renamed to.
+    let mut exposed = vec![];
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        let mut name = ident.to_string();
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("jmespath")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename` or `skip`"))
+                }
+            })?;
+        }
+        if !skip {
+            exposed.push((name, ident));
+        }
+    }
+    let names: Vec<_> = exposed.iter().map(|e| &e.0).collect();
+    let idents: Vec<_> = exposed.iter().map(|e| &e.1).collect();
+    // Object values are ordered like the fields of a serialized struct,
+    // which are sorted by name unless `preserve_order` is enabled.
+    let mut sorted = exposed.clone();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let sorted: Vec<_> = sorted.iter().map(|e| &e.1).collect();
+    let is_truthy = !exposed.is_empty();
+
+    let type_params: Vec<_> = input
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match *param {
+            GenericParam::Type(ref t) => Some(t.ident.clone()),
+            _ => None,
+        })
+        .collect();
+    let where_clause = input.generics.make_where_clause();
+    for ident in type_params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ident: ::jmespath::Searchable));
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::jmespath::Searchable for #ident #ty_generics #where_clause {
+            fn get_type(&self) -> ::jmespath::JmespathType {
+                ::jmespath::JmespathType::Object
+            }
+
+            fn is_truthy(&self) -> bool {
+                #is_truthy
+            }
+
+            fn get_field(&self, name: &str) -> ::std::option::Option<&dyn ::jmespath::Searchable> {
+                match name {
+                    #(#names => ::std::option::Option::Some(&self.#idents),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn object_values(
+                &self,
+            ) -> ::std::option::Option<::std::vec::Vec<&dyn ::jmespath::Searchable>> {
+                ::std::option::Option::Some(if ::jmespath::__PRESERVE_ORDER {
+                    ::std::vec![#(&self.#idents as &dyn ::jmespath::Searchable),*]
+                } else {
+                    ::std::vec![#(&self.#sorted as &dyn ::jmespath::Searchable),*]
+                })
+            }
+
+            fn to_variable(
+                &self,
+            ) -> ::std::result::Result<::jmespath::Variable, ::jmespath::JmespathError> {
+                #[allow(unused_mut)]
+                let mut map = ::jmespath::Map::new();
+                #(
+                    map.insert(
+                        ::std::string::String::from(#names),
+                        ::jmespath::Rcvar::new(::jmespath::Searchable::to_variable(&self.#idents)?),
+                    );
+                )*
+                ::std::result::Result::Ok(::jmespath::Variable::Object(map))
+            }
+        }
+    })
+}
+
+fn unsupported(span: Span) -> syn::Error {
+    syn::Error::new(
+        span,
+        "Searchable can only be derived for structs with named fields",
+    )
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use jmespath::{Expression, Variable};
+use jmespath_macros::{jmespath, Searchable};
+use serde_derive::Serialize;
+
+#[test]
+fn searches_with_validated_expressions() {
+    let data = Variable::from_json(r#"{"foo": [{"bar": 1}, {"bar": 2}]}"#).unwrap();
+    let result = jmespath!("foo[?bar > `1`].bar").search(data).unwrap();
+    assert_eq!(Variable::from_json("[2]").unwrap(), *result);
+}
+
+/// Returns the address of the expression that `jmespath!` expanded to, which
+/// is a `&'static` reference with the `sync` feature and an `Rc` without it.
+fn address<E: Deref<Target = Expression<'static>>>(expression: E) -> *const Expression<'static> {
+    &*expression
+}
+
+#[test]
+fn compiles_each_expression_once() {
+    let expression = || jmespath!("foo.bar");
+    assert_eq!(address(expression()), address(expression()));
+    assert_eq!("foo.bar", expression().as_str());
+}
+
+#[test]
+fn accepts_raw_strings() {
+    let expr = jmespath!(r#"foo."bar baz""#);
+    assert_eq!(r#"foo."bar baz""#, expr.as_str());
+}
+
+#[derive(Searchable, Serialize)]
+struct Service<T> {
+    name: String,
+    #[jmespath(rename = "replicaCount")]
+    #[serde(rename = "replicaCount")]
+    replicas: u32,
+    labels: BTreeMap<String, String>,
+    owner: Option<Box<Service<T>>>,
+    extra: T,
+    #[jmespath(skip)]
+    #[serde(skip)]
+    #[allow(dead_code)]
+    secret: String,
+}
+
+#[derive(Searchable)]
+struct Empty {}
+
+fn services() -> Vec<Service<Vec<bool>>> {
+    let service = |name: &str, replicas, owner| {
+        let mut labels = BTreeMap::new();
+        labels.insert("app".to_string(), name.to_string());
+        Service {
+            name: name.to_string(),
+            replicas,
+            labels,
+            owner,
+            extra: vec![replicas > 1],
+            secret: "hidden".to_string(),
+        }
+    };
+    let owner = service("owner", 1, None);
+    vec![
+        service("a", 3, Some(Box::new(owner))),
+        service("b", 1, None),
+    ]
+}
+
+#[test]
+fn searches_derived_structs_like_serialized_structs() {
+    let data = services();
+    for expression in &[
+        "[?replicaCount > `1`].name",
+        "[*].owner.labels.app",
+        "[0]",
+        "[1].*",
+        "[*].extra[]",
+        "[*].secret",
+        "sort_by(@, &replicaCount)[*].name",
+        "[?owner].owner.owner",
+    ] {
+        let expr = jmespath::compile(expression).unwrap();
+        assert_eq!(
+            expr.search(&data).unwrap(),
+            expr.search_searchable(&data).unwrap(),
+            "{}",
+            expression
+        );
+    }
+}
+
+#[test]
+fn empty_structs_are_falsy() {
+    let result = jmespath!("@ || `true`").search_searchable(&Empty {});
+    assert_eq!(Variable::Bool(true), *result.unwrap());
+}
@@ -17,7 +17,7 @@ pub fn main() {
 
     let mut all_benches_test = vec![];
 
-    for (suite_num, &(ref filename, ref suite)) in suites.iter().enumerate() {
+    for (suite_num, (filename, suite)) in suites.iter().enumerate() {
         let suite_obj = suite.as_object().expect("Suite not object");
         let given = suite_obj.get("given").expect("No given value");
         let cases = suite_obj.get("cases").expect("No cases value");
@@ -69,11 +69,31 @@ benchmark_main!(benches);
 }
 
 /// Load all tests suites found in the tests/compliance directory.
+///
+/// Suites in a subdirectory are only loaded when the cargo feature of the
+/// same name is enabled (e.g., tests/compliance/regex requires `regex`).
 pub fn load_test_suites() -> Vec<(String, Value)> {
     let mut result = vec![];
-    let files = fs::read_dir("tests/compliance").expect("Invalid directory: tests/compliance");
+    load_test_suites_from(Path::new("tests/compliance"), &mut result);
+    result
+}
+
+fn load_test_suites_from(dir: &Path, result: &mut Vec<(String, Value)>) {
+    let files = fs::read_dir(dir).expect("Invalid directory: tests/compliance");
     for filename in files {
         let path = filename.expect("Invalid file").path();
+        if path.is_dir() {
+            let feature = path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .expect("Could not to_str directory")
+                .to_uppercase()
+                .replace("-", "_");
+            if env::var_os(format!("CARGO_FEATURE_{}", feature)).is_some() {
+                load_test_suites_from(&path, result);
+            }
+            continue;
+        }
         let file_path = path.to_str().expect("Could not to_str file").to_string();
         let mut f = File::open(path).expect("Unable to open file");
         let mut file_data = String::new();
@@ -87,7 +107,6 @@ pub fn load_test_suites() -> Vec<(String, Value)> {
             result.push((file_path.clone(), suite));
         }
     }
-    result
 }
 
 /// Gets the expression from a test case with helpful error messages.
@@ -121,7 +140,7 @@ fn generate_fn_name(
     let expr = get_expr(case);
     // Use the comment as the fn description if it is present.
     let description = match case.get("comment") {
-        Some(ref c) => c.as_str().expect("comment is not a string"),
+        Some(c) => c.as_str().expect("comment is not a string"),
         None => expr,
     };
     format!(
@@ -0,0 +1,1112 @@
+//! Infers the types of values produced by expressions.
+//!
+//! Expressions are analyzed against a `Shape` that describes the data they
+//! will search. A shape can be created from a JSON Schema, from a
+//! `JmespathType`, or built directly. Analysis infers the shape of the
+//! result of every node of the AST and reports diagnostics for nodes that
+//! can never produce a useful result, such as fields that are not declared
+//! by the schema, comparisons of strings to numbers, and function calls
+//! and arithmetic that always fail with `RuntimeError::InvalidType`.
+//!
+//! ```
+//! use jmespath::analysis::{DiagnosticKind, Shape};
+//! use serde_json::json;
+//!
+//! let schema = json!({
+//!     "type": "object",
+//!     "properties": {
+//!         "people": {
+//!             "type": "array",
+//!             "items": {
+//!                 "type": "object",
+//!                 "properties": {
+//!                     "name": {"type": "string"},
+//!                     "age": {"type": "integer"}
+//!                 },
+//!                 "required": ["name", "age"],
+//!                 "additionalProperties": false
+//!             }
+//!         }
+//!     },
+//!     "required": ["people"]
+//! });
+//! let shape = Shape::from_json_schema(&schema);
+//!
+//! let expr = jmespath::compile("people[?age > `30`].name").unwrap();
+//! let analysis = expr.analyze(&shape);
+//! assert_eq!("array[string]", analysis.result().to_string());
+//! assert!(analysis.diagnostics().is_empty());
+//!
+//! let expr = jmespath::compile("sum(people[*].nmae)").unwrap();
+//! let analysis = expr.analyze(&shape);
+//! assert_eq!(
+//!     DiagnosticKind::UnknownField("nmae".to_owned()),
+//!     analysis.diagnostics()[0].kind
+//! );
+//! ```
+//!
+//! Diagnostics are only reported when a problem occurs for every value
+//! described by the shape. For example, a field that may be null is
+//! accepted by a function that requires a number because the field may
+//! also be a number. Functions are checked using the `Signature` that they
+//! return from `Function::signature()`, and exprefs passed to a function
+//! are analyzed against the elements of the first array argument of the
+//! call, which is how the builtin functions evaluate them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::discriminant;
+
+use serde_json::Value;
+
+use crate::ast::{Ast, Comparator};
+use crate::functions::ArgumentType;
+use crate::variable::JmespathType;
+use crate::{Runtime, RuntimeError, Variable};
+
+/// Describes the values that may be produced when evaluating a node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Any value.
+    Any,
+    Null,
+    Bool,
+    Number,
+    String,
+    /// An array whose elements match the given shape.
+    Array(Box<Shape>),
+    /// An object with the given properties. Properties that may be absent
+    /// include `Shape::Null`. Undeclared properties match `additional`, or
+    /// are never present when `additional` is `None`.
+    Object {
+        properties: BTreeMap<String, Shape>,
+        additional: Option<Box<Shape>>,
+    },
+    Expref,
+    /// A value that matches one of the given shapes. An empty union has no
+    /// values, which is the shape of nodes that are never evaluated or
+    /// always fail.
+    Union(Vec<Shape>),
+}
+
+impl Shape {
+    /// Creates a shape that matches a value of any of the given shapes.
+    ///
+    /// Nested unions are flattened, duplicate shapes are removed, and the
+    /// element shapes of arrays are combined.
+    pub fn union<I>(shapes: I) -> Shape
+    where
+        I: IntoIterator<Item = Shape>,
+    {
+        let mut members: Vec<Shape> = vec![];
+        // Index of the array member and the shapes of its elements.
+        let mut array: Option<(usize, Vec<Shape>)> = None;
+        for shape in shapes {
+            let flattened = match shape {
+                Shape::Union(shapes) => shapes,
+                shape => vec![shape],
+            };
+            for shape in flattened {
+                match shape {
+                    Shape::Any => return Shape::Any,
+                    Shape::Array(element) => match array {
+                        Some((_, ref mut elements)) => elements.push(*element),
+                        None => {
+                            array = Some((members.len(), vec![*element]));
+                            members.push(Shape::Array(Box::new(Shape::Any)));
+                        }
+                    },
+                    shape if !members.contains(&shape) => members.push(shape),
+                    _ => {}
+                }
+            }
+        }
+        if let Some((index, elements)) = array {
+            members[index] = Shape::Array(Box::new(Shape::union(elements)));
+        }
+        if members.len() == 1 {
+            members.pop().unwrap()
+        } else {
+            Shape::Union(members)
+        }
+    }
+
+    /// Creates a shape from a JSON Schema.
+    ///
+    /// The `type`, `properties`, `required`, `additionalProperties`,
+    /// `items`, `enum`, `const`, `anyOf`, `oneOf`, and `nullable` keywords
+    /// are supported. Schemas that use other keywords to describe values
+    /// (e.g., `$ref` or `allOf`) match any value.
+    pub fn from_json_schema(schema: &Value) -> Shape {
+        let schema = match *schema {
+            Value::Bool(true) => return Shape::Any,
+            Value::Bool(false) => return Shape::Union(vec![]),
+            Value::Object(ref schema) => schema,
+            _ => return Shape::Any,
+        };
+        if let Some(value) = schema.get("const") {
+            return Variable::try_from(value).map_or(Shape::Any, |v| Shape::of(&v));
+        }
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            return Shape::union(
+                values
+                    .iter()
+                    .map(|value| Variable::try_from(value).map_or(Shape::Any, |v| Shape::of(&v))),
+            );
+        }
+        for keyword in &["anyOf", "oneOf"] {
+            if let Some(Value::Array(schemas)) = schema.get(*keyword) {
+                return Shape::union(schemas.iter().map(Shape::from_json_schema));
+            }
+        }
+        let types = match schema.get("type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+            _ if schema.contains_key("properties") => vec!["object"],
+            _ if schema.contains_key("items") => vec!["array"],
+            _ => return Shape::Any,
+        };
+        let mut shapes = vec![];
+        for name in types {
+            shapes.push(match name {
+                "null" => Shape::Null,
+                "boolean" => Shape::Bool,
+                "number" | "integer" => Shape::Number,
+                "string" => Shape::String,
+                "array" => Shape::Array(Box::new(
+                    schema
+                        .get("items")
+                        .map_or(Shape::Any, Shape::from_json_schema),
+                )),
+                "object" => object_from_json_schema(schema),
+                _ => Shape::Any,
+            });
+        }
+        if schema.get("nullable") == Some(&Value::Bool(true)) {
+            shapes.push(Shape::Null);
+        }
+        Shape::union(shapes)
+    }
+
+    /// Returns the most specific shape that matches the given value.
+    pub fn of(value: &Variable) -> Shape {
+        match *value {
+            Variable::Null => Shape::Null,
+            Variable::Bool(_) => Shape::Bool,
+            Variable::Number(_) => Shape::Number,
+            Variable::String(_) => Shape::String,
+            Variable::Array(ref elements) => Shape::Array(Box::new(Shape::union(
+                elements.iter().map(|element| Shape::of(element)),
+            ))),
+            Variable::Object(ref properties) => Shape::Object {
+                properties: properties
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Shape::of(value)))
+                    .collect(),
+                additional: None,
+            },
+            Variable::Expref(_) => Shape::Expref,
+        }
+    }
+
+    /// Returns the shapes that a value of this shape may match.
+    fn members(&self) -> &[Shape] {
+        match *self {
+            Shape::Union(ref shapes) => shapes,
+            _ => std::slice::from_ref(self),
+        }
+    }
+
+    /// Returns true if the shape has no values.
+    fn is_never(&self) -> bool {
+        self.members().is_empty()
+    }
+
+    /// Returns true if some values of the shape are matched by `f`.
+    fn may_be(&self, f: impl Fn(&Shape) -> bool) -> bool {
+        self.members()
+            .iter()
+            .any(|member| *member == Shape::Any || f(member))
+    }
+
+    /// Returns the shape without null values.
+    fn without_null(self) -> Shape {
+        Shape::union(
+            self.members()
+                .iter()
+                .filter(|member| **member != Shape::Null)
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+fn object_from_json_schema(schema: &serde_json::Map<String, Value>) -> Shape {
+    let required: Vec<&str> = match schema.get("required") {
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    let mut properties = BTreeMap::new();
+    if let Some(Value::Object(schemas)) = schema.get("properties") {
+        for (name, schema) in schemas {
+            let shape = Shape::from_json_schema(schema);
+            let shape = if required.contains(&name.as_str()) {
+                shape
+            } else {
+                Shape::union(vec![shape, Shape::Null])
+            };
+            properties.insert(name.clone(), shape);
+        }
+    }
+    let additional = match schema.get("additionalProperties") {
+        Some(Value::Bool(false)) => None,
+        Some(schema) => Some(Box::new(Shape::from_json_schema(schema))),
+        None => Some(Box::new(Shape::Any)),
+    };
+    Shape::Object {
+        properties,
+        additional,
+    }
+}
+
+impl From<JmespathType> for Shape {
+    fn from(kind: JmespathType) -> Shape {
+        match kind {
+            JmespathType::Null => Shape::Null,
+            JmespathType::String => Shape::String,
+            JmespathType::Number => Shape::Number,
+            JmespathType::Boolean => Shape::Bool,
+            JmespathType::Array => Shape::Array(Box::new(Shape::Any)),
+            JmespathType::Object => Shape::Object {
+                properties: BTreeMap::new(),
+                additional: Some(Box::new(Shape::Any)),
+            },
+            JmespathType::Expref => Shape::Expref,
+        }
+    }
+}
+
+impl<'a> From<&'a ArgumentType> for Shape {
+    fn from(kind: &'a ArgumentType) -> Shape {
+        match *kind {
+            ArgumentType::Any => Shape::Any,
+            ArgumentType::Null => Shape::Null,
+            ArgumentType::String => Shape::String,
+            ArgumentType::Number => Shape::Number,
+            ArgumentType::Bool => Shape::Bool,
+            ArgumentType::Object => Shape::from(JmespathType::Object),
+            ArgumentType::Array => Shape::from(JmespathType::Array),
+            ArgumentType::Expref => Shape::Expref,
+            ArgumentType::TypedArray(ref kind) => Shape::Array(Box::new(Shape::from(&**kind))),
+            ArgumentType::Union(ref kinds) => Shape::union(kinds.iter().map(Shape::from)),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Shape::Any => write!(fmt, "any"),
+            Shape::Null => write!(fmt, "null"),
+            Shape::Bool => write!(fmt, "boolean"),
+            Shape::Number => write!(fmt, "number"),
+            Shape::String => write!(fmt, "string"),
+            Shape::Array(ref element) if **element == Shape::Any => write!(fmt, "array"),
+            Shape::Array(ref element) => write!(fmt, "array[{}]", element),
+            Shape::Object { .. } => write!(fmt, "object"),
+            Shape::Expref => write!(fmt, "expref"),
+            Shape::Union(ref shapes) if shapes.is_empty() => write!(fmt, "never"),
+            Shape::Union(ref shapes) => {
+                let names = shapes.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                write!(fmt, "{}", names.join("|"))
+            }
+        }
+    }
+}
+
+/// Problem found when analyzing an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Absolute character position of the node with the problem.
+    pub offset: usize,
+    /// Description of the problem.
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} (offset {})", self.kind, self.offset)
+    }
+}
+
+/// Problems that are reported when analyzing an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// The field is not declared by any object the field is selected from,
+    /// and none of the objects allow additional properties, so the field
+    /// always evaluates to null.
+    UnknownField(String),
+    /// The node is applied to a value that never has the expected type, so
+    /// the node always evaluates to null.
+    UnexpectedType {
+        /// Expected type.
+        expected: String,
+        /// Inferred type.
+        actual: String,
+    },
+    /// The values being compared never have compatible types, so equality
+    /// comparisons always have the same result and ordering comparisons
+    /// always evaluate to null.
+    IncomparableTypes {
+        /// Comparator used to compare the values.
+        comparator: Comparator,
+        /// Inferred type of the left hand side.
+        left: String,
+        /// Inferred type of the right hand side.
+        right: String,
+    },
+    /// The node always fails with the given error when evaluated.
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DiagnosticKind::UnknownField(ref name) => write!(fmt, "Unknown field {}", name),
+            DiagnosticKind::UnexpectedType {
+                ref expected,
+                ref actual,
+            } => write!(fmt, "Expected {}, found {}", expected, actual),
+            DiagnosticKind::IncomparableTypes {
+                ref comparator,
+                ref left,
+                ref right,
+            } => write!(fmt, "Cannot compare {} {} {}", left, comparator, right),
+            DiagnosticKind::Runtime(ref e) => write!(fmt, "{}", e),
+        }
+    }
+}
+
+/// Result of analyzing an expression.
+pub struct Analysis<'a> {
+    result: Shape,
+    types: HashMap<usize, Shape>,
+    diagnostics: Vec<Diagnostic>,
+    ast: PhantomData<&'a Ast>,
+}
+
+impl<'a> Analysis<'a> {
+    /// Returns the inferred shape of the result of the expression.
+    pub fn result(&self) -> &Shape {
+        &self.result
+    }
+
+    /// Returns the inferred shape of the result of a node of the analyzed
+    /// AST, or None if the node is not part of the AST.
+    pub fn type_of(&self, node: &'a Ast) -> Option<&Shape> {
+        self.types.get(&address(node))
+    }
+
+    /// Returns the problems found in the expression, ordered by the
+    /// position in which the nodes are evaluated.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+/// Analyzes an AST that will be evaluated against data of the given shape.
+///
+/// Functions are resolved using the given runtime.
+pub fn analyze<'a>(ast: &'a Ast, input: &Shape, runtime: &Runtime) -> Analysis<'a> {
+    let mut analyzer = Analyzer {
+        runtime,
+        root: input.clone(),
+        scopes: vec![],
+        types: HashMap::new(),
+        diagnostics: vec![],
+    };
+    let result = analyzer.infer(ast, input);
+    Analysis {
+        result,
+        types: analyzer.types,
+        diagnostics: analyzer.diagnostics,
+        ast: PhantomData,
+    }
+}
+
+/// Nodes are identified by their address, which is stable while the AST is
+/// borrowed by the analysis.
+fn address(node: &Ast) -> usize {
+    node as *const Ast as usize
+}
+
+struct Analyzer<'a> {
+    runtime: &'a Runtime,
+    root: Shape,
+    scopes: Vec<BTreeMap<String, Shape>>,
+    types: HashMap<usize, Shape>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer<'_> {
+    fn infer(&mut self, node: &Ast, input: &Shape) -> Shape {
+        let shape = self.infer_node(node, input);
+        self.types.insert(address(node), shape.clone());
+        shape
+    }
+
+    fn infer_node(&mut self, node: &Ast, input: &Shape) -> Shape {
+        match *node {
+            Ast::Identity { .. } => input.clone(),
+            Ast::Root { .. } => self.root.clone(),
+            Ast::Literal { ref value, .. } => Shape::of(value),
+            Ast::Field { ref name, offset } => self.field(input, name, offset),
+            Ast::Index { offset, .. } => {
+                self.expect(offset, input, "array", is_array);
+                map(input, |member| match *member {
+                    Shape::Any => Shape::Any,
+                    Shape::Array(ref element) => Shape::union(vec![*element.clone(), Shape::Null]),
+                    _ => Shape::Null,
+                })
+            }
+            Ast::Slice { step, offset, .. } => {
+                if step == 0 {
+                    self.report(offset, DiagnosticKind::Runtime(RuntimeError::InvalidSlice));
+                    return Shape::Union(vec![]);
+                }
+                self.expect(offset, input, "array", is_array);
+                map(input, |member| match *member {
+                    Shape::Any => Shape::union(vec![Shape::from(JmespathType::Array), Shape::Null]),
+                    Shape::Array(_) => member.clone(),
+                    _ => Shape::Null,
+                })
+            }
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => {
+                let left = self.infer(lhs, input);
+                self.infer(rhs, &left)
+            }
+            Ast::Projection {
+                ref lhs,
+                ref rhs,
+                offset,
+            } => {
+                let left = self.infer(lhs, input);
+                self.expect(offset, &left, "array", is_array);
+                let element = elements(&left);
+                let projected = self.infer(rhs, &element).without_null();
+                map(&left, |member| match *member {
+                    Shape::Any => {
+                        Shape::union(vec![Shape::Array(Box::new(projected.clone())), Shape::Null])
+                    }
+                    Shape::Array(_) => Shape::Array(Box::new(projected.clone())),
+                    _ => Shape::Null,
+                })
+            }
+            Ast::Flatten { ref node, offset } => {
+                let shape = self.infer(node, input);
+                self.expect(offset, &shape, "array", is_array);
+                map(&shape, |member| match *member {
+                    Shape::Any => Shape::union(vec![Shape::from(JmespathType::Array), Shape::Null]),
+                    Shape::Array(ref element) => Shape::Array(Box::new(Shape::union(
+                        element.members().iter().map(|member| match *member {
+                            Shape::Array(ref nested) => *nested.clone(),
+                            _ => member.clone(),
+                        }),
+                    ))),
+                    _ => Shape::Null,
+                })
+            }
+            Ast::ObjectValues { ref node, offset } => {
+                let shape = self.infer(node, input);
+                self.expect(offset, &shape, "object", is_object);
+                map(&shape, |member| match *member {
+                    Shape::Any => Shape::union(vec![Shape::from(JmespathType::Array), Shape::Null]),
+                    Shape::Object {
+                        ref properties,
+                        ref additional,
+                    } => Shape::Array(Box::new(Shape::union(
+                        properties
+                            .values()
+                            .cloned()
+                            .chain(additional.iter().map(|a| *a.clone())),
+                    ))),
+                    _ => Shape::Null,
+                })
+            }
+            Ast::Comparison {
+                ref comparator,
+                ref lhs,
+                ref rhs,
+                offset,
+            } => {
+                let left = self.infer(lhs, input);
+                let right = self.infer(rhs, input);
+                self.compare(comparator, &left, &right, offset)
+            }
+            Ast::Arithmetic {
+                ref lhs,
+                ref rhs,
+                offset,
+                ..
+            } => {
+                let left = self.infer(lhs, input);
+                let right = self.infer(rhs, input);
+                self.expect_operands(offset, &[&left, &right]);
+                if left.may_be(is_number) && right.may_be(is_number) {
+                    Shape::union(vec![Shape::Number, Shape::Null])
+                } else {
+                    Shape::Union(vec![])
+                }
+            }
+            Ast::UnaryArithmetic {
+                ref node, offset, ..
+            } => {
+                let operand = self.infer(node, input);
+                self.expect_operands(offset, &[&operand]);
+                if operand.may_be(is_number) {
+                    Shape::Number
+                } else {
+                    Shape::Union(vec![])
+                }
+            }
+            Ast::Or {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::And {
+                ref lhs, ref rhs, ..
+            } => {
+                let left = self.infer(lhs, input);
+                let right = self.infer(rhs, input);
+                Shape::union(vec![left, right])
+            }
+            Ast::Not { ref node, .. } => {
+                self.infer(node, input);
+                Shape::Bool
+            }
+            Ast::Condition {
+                ref predicate,
+                ref then,
+                ..
+            } => {
+                let predicate = self.infer(predicate, input);
+                let then = self.infer(then, input);
+                if predicate.may_be(|member| *member != Shape::Null) {
+                    Shape::union(vec![then, Shape::Null])
+                } else {
+                    Shape::Null
+                }
+            }
+            Ast::MultiList { ref elements, .. } => {
+                let shapes = elements
+                    .iter()
+                    .map(|element| self.infer(element, input))
+                    .collect::<Vec<_>>();
+                self.multi(input, Shape::Array(Box::new(Shape::union(shapes))))
+            }
+            Ast::MultiHash { ref elements, .. } => {
+                let mut properties = BTreeMap::new();
+                for kvp in elements {
+                    let shape = self.infer(&kvp.value, input);
+                    properties.insert(kvp.key.clone(), shape);
+                }
+                let object = Shape::Object {
+                    properties,
+                    additional: None,
+                };
+                self.multi(input, object)
+            }
+            Ast::Function {
+                ref name,
+                ref args,
+                offset,
+            } => self.call(name, args, offset, input),
+            Ast::Expref { ref ast, .. } => {
+                self.infer(ast, &Shape::Any);
+                Shape::Expref
+            }
+            Ast::Let {
+                ref bindings,
+                ref expr,
+                ..
+            } => {
+                let mut scope = BTreeMap::new();
+                for binding in bindings {
+                    let shape = self.infer(&binding.value, input);
+                    scope.insert(binding.name.clone(), shape);
+                }
+                self.scopes.push(scope);
+                let shape = self.infer(expr, input);
+                self.scopes.pop();
+                shape
+            }
+            // Variables that are not bound by a let expression may be
+            // provided when searching.
+            Ast::VariableRef { ref name, .. } => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(name))
+                .cloned()
+                .unwrap_or(Shape::Any),
+        }
+    }
+
+    fn field(&mut self, input: &Shape, name: &str, offset: usize) -> Shape {
+        self.expect(offset, input, "object", is_object);
+        let mut declared = input.may_be(|member| match *member {
+            Shape::Object { ref properties, .. } => properties.contains_key(name),
+            _ => false,
+        });
+        // Objects that allow additional properties may have any field.
+        declared |= input.may_be(|member| match *member {
+            Shape::Object { ref additional, .. } => additional.is_some(),
+            _ => false,
+        });
+        if !declared && input.may_be(is_object) {
+            self.report(offset, DiagnosticKind::UnknownField(name.to_owned()));
+        }
+        map(input, |member| match *member {
+            Shape::Any => Shape::Any,
+            Shape::Object {
+                ref properties,
+                ref additional,
+            } => match (properties.get(name), additional) {
+                (Some(shape), _) => shape.clone(),
+                (None, Some(additional)) => Shape::union(vec![*additional.clone(), Shape::Null]),
+                (None, None) => Shape::Null,
+            },
+            _ => Shape::Null,
+        })
+    }
+
+    fn compare(
+        &mut self,
+        comparator: &Comparator,
+        left: &Shape,
+        right: &Shape,
+        offset: usize,
+    ) -> Shape {
+        if left.is_never() || right.is_never() {
+            return Shape::Union(vec![]);
+        }
+        let comparable = match *comparator {
+            Comparator::Equal | Comparator::NotEqual => {
+                left.may_be(|l| right.may_be(|r| discriminant(l) == discriminant(r)))
+            }
+            _ => left.may_be(is_number) && right.may_be(is_number),
+        };
+        if !comparable {
+            self.report(
+                offset,
+                DiagnosticKind::IncomparableTypes {
+                    comparator: comparator.clone(),
+                    left: left.to_string(),
+                    right: right.to_string(),
+                },
+            );
+        }
+        match *comparator {
+            Comparator::Equal | Comparator::NotEqual => Shape::Bool,
+            _ if !comparable => Shape::Null,
+            _ if is_number(left) && is_number(right) => Shape::Bool,
+            _ => Shape::union(vec![Shape::Bool, Shape::Null]),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Ast], offset: usize, input: &Shape) -> Shape {
+        let mut shapes = vec![Shape::Expref; args.len()];
+        for (arg, shape) in args.iter().zip(shapes.iter_mut()) {
+            if !matches!(*arg, Ast::Expref { .. }) {
+                *shape = self.infer(arg, input);
+            }
+        }
+        let element = shapes
+            .iter()
+            .find(|shape| shape.may_be(is_array))
+            .map_or(Shape::Any, elements);
+        for arg in args {
+            if let Ast::Expref { ref ast, .. } = *arg {
+                self.infer(ast, &element);
+                self.types.insert(address(arg), Shape::Expref);
+            }
+        }
+        let runtime = self.runtime;
+        let signature = match runtime.get_function(name) {
+            Some(function) => match function.signature() {
+                Some(signature) => signature,
+                None => return Shape::Any,
+            },
+            None => {
+                let reason = RuntimeError::UnknownFunction(name.to_owned());
+                self.report(offset, DiagnosticKind::Runtime(reason));
+                return Shape::Union(vec![]);
+            }
+        };
+        if shapes.iter().any(Shape::is_never) {
+            return Shape::Union(vec![]);
+        }
+        let min_expected = signature.inputs.len();
+        let max_expected = min_expected + signature.optional.len();
+        let reason = if args.len() < min_expected {
+            Some(RuntimeError::NotEnoughArguments {
+                expected: signature.arity(),
+                actual: args.len(),
+            })
+        } else if args.len() > max_expected && signature.variadic.is_none() {
+            Some(RuntimeError::TooManyArguments {
+                expected: signature.arity(),
+                actual: args.len(),
+            })
+        } else {
+            shapes.iter().enumerate().find_map(|(position, shape)| {
+                let kind = signature
+                    .inputs
+                    .iter()
+                    .chain(signature.optional.iter().map(|o| &o.kind))
+                    .nth(position)
+                    .or(signature.variadic.as_ref())
+                    .expect("arity was checked");
+                if may_match(shape, kind) {
+                    None
+                } else {
+                    Some(RuntimeError::InvalidType {
+                        expected: kind.to_string(),
+                        actual: shape.to_string(),
+                        position,
+                    })
+                }
+            })
+        };
+        match reason {
+            Some(reason) => {
+                self.report(offset, DiagnosticKind::Runtime(reason));
+                Shape::Union(vec![])
+            }
+            None => Shape::from(&signature.output),
+        }
+    }
+
+    /// Returns the shape of a multi-select, which is null when evaluated
+    /// against null.
+    fn multi(&self, input: &Shape, shape: Shape) -> Shape {
+        map(input, |member| match *member {
+            Shape::Null => Shape::Null,
+            _ => shape.clone(),
+        })
+    }
+
+    /// Reports a node that is applied to values that never have the
+    /// expected type. Null values are ignored because they usually come
+    /// from optional fields.
+    fn expect(&mut self, offset: usize, shape: &Shape, expected: &str, f: fn(&Shape) -> bool) {
+        let members = shape.members();
+        if !shape.may_be(f) && members.iter().any(|member| *member != Shape::Null) {
+            self.report(
+                offset,
+                DiagnosticKind::UnexpectedType {
+                    expected: expected.to_owned(),
+                    actual: shape.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Reports the first operand of an arithmetic operator that is never a
+    /// number, which always fails with an invalid-type error. Unlike
+    /// `expect`, null operands are reported because arithmetic fails for
+    /// null values too.
+    fn expect_operands(&mut self, offset: usize, operands: &[&Shape]) {
+        let invalid = operands
+            .iter()
+            .position(|shape| !shape.is_never() && !shape.may_be(is_number));
+        if let Some(position) = invalid {
+            self.report(
+                offset,
+                DiagnosticKind::Runtime(RuntimeError::InvalidType {
+                    expected: "number".to_owned(),
+                    actual: operands[position].to_string(),
+                    position,
+                }),
+            );
+        }
+    }
+
+    fn report(&mut self, offset: usize, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic { offset, kind });
+    }
+}
+
+/// Applies `f` to each shape that a value of the given shape may match.
+fn map(shape: &Shape, f: impl Fn(&Shape) -> Shape) -> Shape {
+    Shape::union(shape.members().iter().map(f).collect::<Vec<_>>())
+}
+
+/// Returns the shape of the elements of the arrays that a shape may match.
+fn elements(shape: &Shape) -> Shape {
+    if *shape == Shape::Any {
+        return Shape::Any;
+    }
+    Shape::union(
+        shape
+            .members()
+            .iter()
+            .filter_map(|member| match *member {
+                Shape::Array(ref element) => Some(*element.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Returns true if a value of the given shape may be valid for the type.
+fn may_match(shape: &Shape, kind: &ArgumentType) -> bool {
+    shape.may_be(|member| match (member, kind) {
+        (_, ArgumentType::Any) => true,
+        (_, ArgumentType::Union(ref kinds)) => kinds.iter().any(|k| may_match(member, k)),
+        (Shape::Null, ArgumentType::Null)
+        | (Shape::Bool, ArgumentType::Bool)
+        | (Shape::Number, ArgumentType::Number)
+        | (Shape::String, ArgumentType::String)
+        | (Shape::Array(_), ArgumentType::Array)
+        | (Shape::Object { .. }, ArgumentType::Object)
+        | (Shape::Expref, ArgumentType::Expref) => true,
+        // Empty arrays are valid for any typed array.
+        (Shape::Array(ref element), ArgumentType::TypedArray(ref kind)) => {
+            element.is_never() || may_match(element, kind)
+        }
+        _ => false,
+    })
+}
+
+fn is_array(shape: &Shape) -> bool {
+    matches!(*shape, Shape::Array(_))
+}
+
+fn is_object(shape: &Shape) -> bool {
+    matches!(*shape, Shape::Object { .. })
+}
+
+fn is_number(shape: &Shape) -> bool {
+    *shape == Shape::Number
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+    use serde_json::json;
+
+    fn schema() -> Shape {
+        Shape::from_json_schema(&json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "string"},
+                "deleted": {"type": "null"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "people": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": {"type": "string"},
+                            "age": {"type": ["integer", "null"]},
+                            "email": {"type": "string"}
+                        },
+                        "required": ["name", "age"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["id", "people"]
+        }))
+    }
+
+    fn analyzed(expression: &str) -> (String, Vec<DiagnosticKind>) {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        let ast = parse(expression).unwrap();
+        let analysis = analyze(&ast, &schema(), &runtime);
+        let diagnostics = analysis.diagnostics().iter().map(|d| d.kind.clone());
+        (analysis.result().to_string(), diagnostics.collect())
+    }
+
+    fn inferred(expression: &str) -> String {
+        let (result, diagnostics) = analyzed(expression);
+        assert_eq!(Vec::<DiagnosticKind>::new(), diagnostics, "{}", expression);
+        result
+    }
+
+    #[test]
+    fn creates_shapes_from_json_schemas() {
+        let cases = vec![
+            (json!({"type": "integer"}), "number"),
+            (json!({"type": ["string", "null"]}), "string|null"),
+            (json!({"type": "string", "nullable": true}), "string|null"),
+            (json!({"items": {"type": "boolean"}}), "array[boolean]"),
+            (json!({"enum": ["a", 1, null]}), "string|number|null"),
+            (json!({"const": [1]}), "array[number]"),
+            (
+                json!({"anyOf": [{"type": "array"}, {"type": "object"}]}),
+                "array|object",
+            ),
+            (json!({"$ref": "#/definitions/foo"}), "any"),
+            (json!(false), "never"),
+        ];
+        for (schema, expected) in cases {
+            assert_eq!(expected, Shape::from_json_schema(&schema).to_string());
+        }
+    }
+
+    #[test]
+    fn creates_shapes_from_types() {
+        assert_eq!(Shape::Bool, Shape::from(JmespathType::Boolean));
+        assert_eq!("array", Shape::from(JmespathType::Array).to_string());
+        assert_eq!(
+            Shape::Array(Box::new(Shape::union(vec![Shape::Number, Shape::Null]))),
+            Shape::of(&Variable::from_json("[1, null, 2]").unwrap())
+        );
+    }
+
+    #[test]
+    fn infers_result_shapes() {
+        assert_eq!("string", inferred("id"));
+        assert_eq!("array[string]", inferred("people[*].name"));
+        assert_eq!("array[number]", inferred("people[*].age"));
+        assert_eq!("array[string]", inferred("people[?age > `18`].email"));
+        assert_eq!("object|null", inferred("people[0]"));
+        assert_eq!("array[string|number]", inferred("people[*].[name, age][]"));
+        assert_eq!("object", inferred("{n: length(people)}"));
+        assert_eq!("number", inferred("length(tags)"));
+        assert_eq!("string", inferred("join(',', tags)"));
+        assert_eq!("boolean", inferred("!id"));
+        assert_eq!("array[string]", inferred("let $p = people in $p[*].name"));
+        assert_eq!("any", inferred("$x.foo"));
+        assert_eq!("number|null", inferred("`1` + `2`"));
+        assert_eq!("number", inferred("-`1`"));
+    }
+
+    #[test]
+    fn infers_the_shape_of_every_node() {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        let ast = parse("people[*].name").unwrap();
+        let analysis = analyze(&ast, &schema(), &runtime);
+        match ast {
+            Ast::Projection {
+                ref lhs, ref rhs, ..
+            } => {
+                assert_eq!("array[object]", analysis.type_of(lhs).unwrap().to_string());
+                assert_eq!("string", analysis.type_of(rhs).unwrap().to_string());
+            }
+            ref node => panic!("unexpected node: {:?}", node),
+        }
+        let other = parse("people").unwrap();
+        assert_eq!(None, analysis.type_of(&other));
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let (result, diagnostics) = analyzed("people[*].nmae");
+        assert_eq!("array[never]", result);
+        assert_eq!(
+            vec![DiagnosticKind::UnknownField("nmae".to_owned())],
+            diagnostics
+        );
+        // Objects that allow additional properties may have the field.
+        assert_eq!("any", inferred("idd"));
+        assert_eq!("array[object]", inferred("{a: people}.a"));
+    }
+
+    #[test]
+    fn reports_incomparable_types() {
+        let (result, diagnostics) = analyzed("people[?age > '18']");
+        assert_eq!("array[never]", result);
+        assert_eq!(
+            vec![DiagnosticKind::IncomparableTypes {
+                comparator: Comparator::GreaterThan,
+                left: "number|null".to_owned(),
+                right: "string".to_owned(),
+            }],
+            diagnostics
+        );
+        let (_, diagnostics) = analyzed("id == `1`");
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("boolean", inferred("people[0].age == `null`"));
+    }
+
+    #[test]
+    fn reports_invalid_function_calls() {
+        let (result, diagnostics) = analyzed("sum(people[*].name)");
+        assert_eq!("never", result);
+        assert_eq!(
+            vec![DiagnosticKind::Runtime(RuntimeError::InvalidType {
+                expected: "array[number]".to_owned(),
+                actual: "array[string]".to_owned(),
+                position: 0,
+            })],
+            diagnostics
+        );
+        let invalid_type = |actual: &str, position| {
+            vec![DiagnosticKind::Runtime(RuntimeError::InvalidType {
+                expected: "number".to_owned(),
+                actual: actual.to_owned(),
+                position,
+            })]
+        };
+        let (result, diagnostics) = analyzed("`true` + `2`");
+        assert_eq!("never", result);
+        assert_eq!(invalid_type("boolean", 0), diagnostics);
+        // Arithmetic fails for null operands, unlike functions of fields.
+        let (result, diagnostics) = analyzed("`null` + `1`");
+        assert_eq!("never", result);
+        assert_eq!(invalid_type("null", 0), diagnostics);
+        let (result, diagnostics) = analyzed("`1` * deleted");
+        assert_eq!("never", result);
+        assert_eq!(invalid_type("null", 1), diagnostics);
+        let (result, diagnostics) = analyzed("-id");
+        assert_eq!("never", result);
+        assert_eq!(invalid_type("string", 0), diagnostics);
+        assert_eq!("number|null", inferred("people[0].age + `1`"));
+        let (_, diagnostics) = analyzed("length(id, id)");
+        assert!(matches!(
+            diagnostics[0],
+            DiagnosticKind::Runtime(RuntimeError::TooManyArguments { .. })
+        ));
+        let (_, diagnostics) = analyzed("lenght(id)");
+        assert_eq!(
+            vec![DiagnosticKind::Runtime(RuntimeError::UnknownFunction(
+                "lenght".to_owned()
+            ))],
+            diagnostics
+        );
+        // Exprefs are analyzed against the elements of the array argument.
+        let (_, diagnostics) = analyzed("sort_by(people, &nmae)");
+        assert_eq!(
+            vec![DiagnosticKind::UnknownField("nmae".to_owned())],
+            diagnostics
+        );
+        assert_eq!("number", inferred("sum(people[*].age)"));
+    }
+
+    #[test]
+    fn reports_nodes_that_always_return_null() {
+        let (result, diagnostics) = analyzed("id[0]");
+        assert_eq!("null", result);
+        assert_eq!(
+            vec![DiagnosticKind::UnexpectedType {
+                expected: "array".to_owned(),
+                actual: "string".to_owned(),
+            }],
+            diagnostics
+        );
+        let (_, diagnostics) = analyzed("tags[::0]");
+        assert_eq!(
+            vec![DiagnosticKind::Runtime(RuntimeError::InvalidSlice)],
+            diagnostics
+        );
+    }
+}
@@ -9,19 +9,136 @@
 //! `jmespath::parse()` function which returns an Ast rather than an
 //! `Expression`.
 //!
+//! An Ast is formatted as a canonical JMESPath expression using `Display`,
+//! which can be parsed again to produce an equivalent Ast.
+//!
 //! ```
 //! use jmespath;
 //!
 //! let ast = jmespath::parse("a || b && c").unwrap();
+//! assert_eq!("a || b && c", ast.to_string());
+//! ```
+//!
+//! # Serialization
+//!
+//! When the `serde-ast` feature is enabled, `Ast` and the types it contains
+//! implement `Serialize` and `Deserialize`. ASTs that are stored or sent to
+//! other processes should be serialized as a `VersionedAst`, which wraps the
+//! AST in an envelope containing the `SCHEMA_VERSION` of the format:
+//!
+//! ```json
+//! {"version": 1, "ast": {"type": "field", "offset": 0, "name": "foo"}}
+//! ```
+//!
+//! Deserializing a `VersionedAst` fails if the version is not the
+//! `SCHEMA_VERSION` supported by this crate.
+//!
+//! Each node is an object with a `type` field containing the variant name
+//! in snake case (e.g., `"field"`, `"multi_hash"`, `"object_values"`) and
+//! one field for each field of the variant. Child nodes are nested objects,
+//! literals are JSON values, comparators and arithmetic operators are snake
+//! case strings (e.g., `"greater_than_equal"`, `"integer_divide"`), and
+//! optional slice bounds are `null` when omitted. For example,
+//! ``foo[?a > `1`]`` is serialized as:
+//!
+//! ```json
+//! {
+//!   "type": "projection",
+//!   "offset": 12,
+//!   "lhs": {"type": "field", "offset": 0, "name": "foo"},
+//!   "rhs": {
+//!     "type": "condition",
+//!     "offset": 12,
+//!     "predicate": {
+//!       "type": "comparison",
+//!       "offset": 9,
+//!       "comparator": "greater_than",
+//!       "lhs": {"type": "field", "offset": 5, "name": "a"},
+//!       "rhs": {"type": "literal", "offset": 9, "value": 1}
+//!     },
+//!     "then": {"type": "identity", "offset": 12}
+//!   }
+//! }
 //! ```
+//!
+//! A deserialized Ast can be evaluated by creating an `Expression` with
+//! `Expression::new`.
 
+use std::cmp::min;
+use std::collections::HashMap;
 use std::fmt;
 
+#[cfg(feature = "serde-ast")]
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::lexer::Token;
+use crate::parser::{PROJECTION_STOP, UNARY_BP, WILDCARD_BP};
+use crate::variable::Variable;
 use crate::Rcvar;
 
+/// Version of the serialized AST format.
+///
+/// The version is incremented whenever a change to the AST changes the
+/// format used by the `serde-ast` feature.
+#[cfg(feature = "serde-ast")]
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// An Ast that is serialized together with the version of its format.
+///
+/// ```
+/// use jmespath::ast::VersionedAst;
+///
+/// let ast = jmespath::parse("foo").unwrap();
+/// let json = serde_json::to_string(&VersionedAst(ast.clone())).unwrap();
+/// assert_eq!(
+///     r#"{"version":1,"ast":{"type":"field","offset":0,"name":"foo"}}"#,
+///     json
+/// );
+/// let versioned: VersionedAst = serde_json::from_str(&json).unwrap();
+/// assert_eq!(ast, versioned.0);
+/// ```
+#[cfg(feature = "serde-ast")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct VersionedAst(pub Ast);
+
+#[cfg(feature = "serde-ast")]
+impl Serialize for VersionedAst {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut envelope = serializer.serialize_struct("VersionedAst", 2)?;
+        envelope.serialize_field("version", &SCHEMA_VERSION)?;
+        envelope.serialize_field("ast", &self.0)?;
+        envelope.end()
+    }
+}
+
+#[cfg(feature = "serde-ast")]
+impl<'de> Deserialize<'de> for VersionedAst {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The AST is buffered so that the version is checked first even
+        // when it follows the AST, and ASTs of other versions fail with a
+        // version error rather than an error about one of their nodes.
+        #[derive(Deserialize)]
+        struct Envelope {
+            version: u32,
+            ast: serde_json::Value,
+        }
+        let envelope = Envelope::deserialize(deserializer)?;
+        if envelope.version != SCHEMA_VERSION {
+            return Err(de::Error::custom(format!(
+                "unsupported AST schema version {}, expected {}",
+                envelope.version, SCHEMA_VERSION
+            )));
+        }
+        Ast::deserialize(envelope.ast)
+            .map(VersionedAst)
+            .map_err(de::Error::custom)
+    }
+}
+
 /// A JMESPath expression abstract syntax tree.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-ast", serde(tag = "type", rename_all = "snake_case"))]
 pub enum Ast {
     /// Compares two nodes using a comparator, returning true/false.
     Comparison {
@@ -34,6 +151,26 @@ pub enum Ast {
         /// Right hand side of the comparison
         rhs: Box<Ast>,
     },
+    /// Applies an arithmetic operator to the results of two nodes.
+    Arithmetic {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Operator applied to the two results
+        operator: ArithmeticOperator,
+        /// Left hand side of the operation
+        lhs: Box<Ast>,
+        /// Right hand side of the operation
+        rhs: Box<Ast>,
+    },
+    /// Applies a unary arithmetic operator to the result of a node.
+    UnaryArithmetic {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Operator applied to the result
+        operator: UnaryOperator,
+        /// Node to evaluate
+        node: Box<Ast>,
+    },
     /// If `predicate` evaluates to a truthy value, returns the
     /// result `then`
     Condition {
@@ -49,6 +186,11 @@ pub enum Ast {
         /// Approximate absolute position in the parsed expression.
         offset: usize,
     },
+    /// Returns the root node that the expression is evaluated against.
+    Root {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+    },
     /// Used by functions to dynamically evaluate argument values.
     Expref {
         /// Approximate absolute position in the parsed expression.
@@ -168,16 +310,73 @@ pub enum Ast {
         /// Right hand side of the expression.
         rhs: Box<Ast>,
     },
+    /// Binds variables, then evaluates `expr` with the bindings in scope.
+    Let {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Variables to bind, evaluated against the current node.
+        bindings: Vec<VariableBinding>,
+        /// Expression evaluated with the variables in scope.
+        expr: Box<Ast>,
+    },
+    /// Resolves a variable bound in an enclosing scope.
+    VariableRef {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Variable name, without the leading `$`.
+        name: String,
+    },
+}
+
+impl Ast {
+    /// Returns the approximate absolute position of the node in the parsed
+    /// expression.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Ast::Comparison { offset, .. }
+            | Ast::Arithmetic { offset, .. }
+            | Ast::UnaryArithmetic { offset, .. }
+            | Ast::Condition { offset, .. }
+            | Ast::Identity { offset }
+            | Ast::Root { offset }
+            | Ast::Expref { offset, .. }
+            | Ast::Flatten { offset, .. }
+            | Ast::Function { offset, .. }
+            | Ast::Field { offset, .. }
+            | Ast::Index { offset, .. }
+            | Ast::Literal { offset, .. }
+            | Ast::MultiList { offset, .. }
+            | Ast::MultiHash { offset, .. }
+            | Ast::Not { offset, .. }
+            | Ast::Projection { offset, .. }
+            | Ast::ObjectValues { offset, .. }
+            | Ast::And { offset, .. }
+            | Ast::Or { offset, .. }
+            | Ast::Slice { offset, .. }
+            | Ast::Subexpr { offset, .. }
+            | Ast::Let { offset, .. }
+            | Ast::VariableRef { offset, .. } => offset,
+        }
+    }
 }
 
+/// Formats the AST as a canonical JMESPath expression.
+///
+/// Parsing the formatted expression yields an equivalent AST. Nodes that
+/// are only created by the parser as part of a projection (e.g.,
+/// `ObjectValues` or `Condition`) are formatted as projections when they
+/// appear on their own.
 impl fmt::Display for Ast {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(fmt, "{:#?}", self)
+        let mut unparser = Unparser::default();
+        unparser.unparse(self, 0, 0);
+        fmt.write_str(&unparser.out)
     }
 }
 
 /// Represents a key value pair in a MultiHash.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
 pub struct KeyValuePair {
     /// Key name.
     pub key: String,
@@ -185,8 +384,20 @@ pub struct KeyValuePair {
     pub value: Ast,
 }
 
+/// Represents a variable binding in a Let expression.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
+pub struct VariableBinding {
+    /// Variable name, without the leading `$`.
+    pub name: String,
+    /// Expression used to determine the value.
+    pub value: Ast,
+}
+
 /// Comparators used in Comparison nodes.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-ast", serde(rename_all = "snake_case"))]
 pub enum Comparator {
     Equal,
     NotEqual,
@@ -196,6 +407,28 @@ pub enum Comparator {
     GreaterThanEqual,
 }
 
+/// Operators used in Arithmetic nodes.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-ast", serde(rename_all = "snake_case"))]
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    IntegerDivide,
+}
+
+/// Operators used in UnaryArithmetic nodes.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde-ast", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-ast", serde(rename_all = "snake_case"))]
+pub enum UnaryOperator {
+    Plus,
+    Minus,
+}
+
 /// Creates a Comparator from a Token.
 ///
 /// Note: panics if the Token is invalid.
@@ -213,19 +446,1127 @@ impl From<Token> for Comparator {
     }
 }
 
+/// Traverses an AST without modifying it.
+///
+/// Each method defaults to visiting the children of the node by calling the
+/// corresponding `walk_*` function. Override a method to inspect a node,
+/// and call the `walk_*` function from the override to keep descending.
+///
+/// ```
+/// use jmespath::ast::{walk_ast, Ast, Visitor};
+///
+/// #[derive(Default)]
+/// struct Fields(Vec<String>);
+///
+/// impl Visitor for Fields {
+///     fn visit_ast(&mut self, node: &Ast) {
+///         if let Ast::Field { name, .. } = node {
+///             self.0.push(name.clone());
+///         }
+///         walk_ast(self, node);
+///     }
+/// }
+///
+/// let ast = jmespath::parse("foo[?bar > `1`].baz").unwrap();
+/// let mut fields = Fields::default();
+/// fields.visit_ast(&ast);
+/// assert_eq!(vec!["foo", "bar", "baz"], fields.0);
+/// ```
+pub trait Visitor {
+    /// Visits an AST node.
+    fn visit_ast(&mut self, node: &Ast) {
+        walk_ast(self, node);
+    }
+
+    /// Visits a key value pair of a MultiHash node.
+    fn visit_key_value_pair(&mut self, kvp: &KeyValuePair) {
+        walk_key_value_pair(self, kvp);
+    }
+
+    /// Visits a variable binding of a Let node.
+    fn visit_variable_binding(&mut self, binding: &VariableBinding) {
+        walk_variable_binding(self, binding);
+    }
+}
+
+/// Visits the children of a node in the order they appear in an expression.
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, node: &Ast) {
+    match *node {
+        Ast::Identity { .. }
+        | Ast::Root { .. }
+        | Ast::Field { .. }
+        | Ast::Index { .. }
+        | Ast::Literal { .. }
+        | Ast::Slice { .. }
+        | Ast::VariableRef { .. } => {}
+        Ast::Comparison {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::Arithmetic {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::Projection {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::And {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::Or {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            visitor.visit_ast(lhs);
+            visitor.visit_ast(rhs);
+        }
+        Ast::Condition {
+            ref predicate,
+            ref then,
+            ..
+        } => {
+            visitor.visit_ast(predicate);
+            visitor.visit_ast(then);
+        }
+        Ast::Expref { ref ast, .. } => visitor.visit_ast(ast),
+        Ast::Flatten { ref node, .. }
+        | Ast::Not { ref node, .. }
+        | Ast::UnaryArithmetic { ref node, .. }
+        | Ast::ObjectValues { ref node, .. } => visitor.visit_ast(node),
+        Ast::Function { ref args, .. } => {
+            for arg in args {
+                visitor.visit_ast(arg);
+            }
+        }
+        Ast::MultiList { ref elements, .. } => {
+            for element in elements {
+                visitor.visit_ast(element);
+            }
+        }
+        Ast::MultiHash { ref elements, .. } => {
+            for kvp in elements {
+                visitor.visit_key_value_pair(kvp);
+            }
+        }
+        Ast::Let {
+            ref bindings,
+            ref expr,
+            ..
+        } => {
+            for binding in bindings {
+                visitor.visit_variable_binding(binding);
+            }
+            visitor.visit_ast(expr);
+        }
+    }
+}
+
+/// Visits the value of a key value pair.
+pub fn walk_key_value_pair<V: Visitor + ?Sized>(visitor: &mut V, kvp: &KeyValuePair) {
+    visitor.visit_ast(&kvp.value);
+}
+
+/// Visits the value of a variable binding.
+pub fn walk_variable_binding<V: Visitor + ?Sized>(visitor: &mut V, binding: &VariableBinding) {
+    visitor.visit_ast(&binding.value);
+}
+
+/// Transforms an AST by taking ownership of each node and returning a
+/// replacement.
+///
+/// Each method defaults to rebuilding the node from its folded children by
+/// calling the corresponding `fold_*` function. Override a method to
+/// replace nodes, and call the `fold_*` function from the override to keep
+/// descending.
+///
+/// ```
+/// use jmespath::ast::{fold_ast, Ast, Fold};
+///
+/// struct Rename;
+///
+/// impl Fold for Rename {
+///     fn fold_ast(&mut self, node: Ast) -> Ast {
+///         match node {
+///             Ast::Field { offset, ref name } if name == "old" => Ast::Field {
+///                 offset,
+///                 name: "new".to_owned(),
+///             },
+///             node => fold_ast(self, node),
+///         }
+///     }
+/// }
+///
+/// let ast = jmespath::parse("a.old[?old]").unwrap();
+/// assert_eq!("a.new[?new]", Rename.fold_ast(ast).to_string());
+/// ```
+pub trait Fold {
+    /// Folds an AST node.
+    fn fold_ast(&mut self, node: Ast) -> Ast {
+        fold_ast(self, node)
+    }
+
+    /// Folds a key value pair of a MultiHash node.
+    fn fold_key_value_pair(&mut self, kvp: KeyValuePair) -> KeyValuePair {
+        fold_key_value_pair(self, kvp)
+    }
+
+    /// Folds a variable binding of a Let node.
+    fn fold_variable_binding(&mut self, binding: VariableBinding) -> VariableBinding {
+        fold_variable_binding(self, binding)
+    }
+}
+
+/// Rebuilds a node from its folded children.
+pub fn fold_ast<F: Fold + ?Sized>(folder: &mut F, node: Ast) -> Ast {
+    let mut fold = |node: Box<Ast>| Box::new(folder.fold_ast(*node));
+    match node {
+        Ast::Identity { .. }
+        | Ast::Root { .. }
+        | Ast::Field { .. }
+        | Ast::Index { .. }
+        | Ast::Literal { .. }
+        | Ast::Slice { .. }
+        | Ast::VariableRef { .. } => node,
+        Ast::Comparison {
+            offset,
+            comparator,
+            lhs,
+            rhs,
+        } => Ast::Comparison {
+            offset,
+            comparator,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Arithmetic {
+            offset,
+            operator,
+            lhs,
+            rhs,
+        } => Ast::Arithmetic {
+            offset,
+            operator,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::UnaryArithmetic {
+            offset,
+            operator,
+            node,
+        } => Ast::UnaryArithmetic {
+            offset,
+            operator,
+            node: fold(node),
+        },
+        Ast::Condition {
+            offset,
+            predicate,
+            then,
+        } => Ast::Condition {
+            offset,
+            predicate: fold(predicate),
+            then: fold(then),
+        },
+        Ast::Expref { offset, ast } => Ast::Expref {
+            offset,
+            ast: fold(ast),
+        },
+        Ast::Flatten { offset, node } => Ast::Flatten {
+            offset,
+            node: fold(node),
+        },
+        Ast::Not { offset, node } => Ast::Not {
+            offset,
+            node: fold(node),
+        },
+        Ast::ObjectValues { offset, node } => Ast::ObjectValues {
+            offset,
+            node: fold(node),
+        },
+        Ast::Projection { offset, lhs, rhs } => Ast::Projection {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::And { offset, lhs, rhs } => Ast::And {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Or { offset, lhs, rhs } => Ast::Or {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Subexpr { offset, lhs, rhs } => Ast::Subexpr {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Function { offset, name, args } => Ast::Function {
+            offset,
+            name,
+            args: args.into_iter().map(|arg| folder.fold_ast(arg)).collect(),
+        },
+        Ast::MultiList { offset, elements } => Ast::MultiList {
+            offset,
+            elements: elements
+                .into_iter()
+                .map(|element| folder.fold_ast(element))
+                .collect(),
+        },
+        Ast::MultiHash { offset, elements } => Ast::MultiHash {
+            offset,
+            elements: elements
+                .into_iter()
+                .map(|kvp| folder.fold_key_value_pair(kvp))
+                .collect(),
+        },
+        Ast::Let {
+            offset,
+            bindings,
+            expr,
+        } => Ast::Let {
+            offset,
+            bindings: bindings
+                .into_iter()
+                .map(|binding| folder.fold_variable_binding(binding))
+                .collect(),
+            expr: Box::new(folder.fold_ast(*expr)),
+        },
+    }
+}
+
+/// Rebuilds a key value pair with a folded value.
+pub fn fold_key_value_pair<F: Fold + ?Sized>(folder: &mut F, kvp: KeyValuePair) -> KeyValuePair {
+    KeyValuePair {
+        key: kvp.key,
+        value: folder.fold_ast(kvp.value),
+    }
+}
+
+/// Rebuilds a variable binding with a folded value.
+pub fn fold_variable_binding<F: Fold + ?Sized>(
+    folder: &mut F,
+    binding: VariableBinding,
+) -> VariableBinding {
+    VariableBinding {
+        name: binding.name,
+        value: folder.fold_ast(binding.value),
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(match *self {
+            Comparator::Equal => "==",
+            Comparator::NotEqual => "!=",
+            Comparator::LessThan => "<",
+            Comparator::LessThanEqual => "<=",
+            Comparator::GreaterThan => ">",
+            Comparator::GreaterThanEqual => ">=",
+        })
+    }
+}
+
+impl fmt::Display for ArithmeticOperator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(match *self {
+            ArithmeticOperator::Add => "+",
+            ArithmeticOperator::Subtract => "-",
+            ArithmeticOperator::Multiply => "*",
+            ArithmeticOperator::Divide => "/",
+            ArithmeticOperator::Modulo => "%",
+            ArithmeticOperator::IntegerDivide => "//",
+        })
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(match *self {
+            UnaryOperator::Plus => "+",
+            UnaryOperator::Minus => "-",
+        })
+    }
+}
+
+impl ArithmeticOperator {
+    /// Returns the token used to parse the operator.
+    fn token(&self) -> Token {
+        match *self {
+            ArithmeticOperator::Add => Token::Plus,
+            ArithmeticOperator::Subtract => Token::Minus,
+            ArithmeticOperator::Multiply => Token::Star,
+            ArithmeticOperator::Divide => Token::Slash,
+            ArithmeticOperator::Modulo => Token::Percent,
+            ArithmeticOperator::IntegerDivide => Token::DoubleSlash,
+        }
+    }
+}
+
+// The unparser mirrors the Pratt parser. Each node has a "top" binding
+// power (how tightly its outermost syntax binds when it is the right hand
+// side of an operator) and a "tail" binding power (tokens that follow the
+// node with a higher binding power are absorbed into it). A node is
+// wrapped in parentheses when either would change how it is parsed.
+
+/// Binding power of nodes that start and end with a single nud token.
+const ATOM_BP: usize = usize::MAX;
+
+/// Tail binding power of projections, which absorb any token that can
+/// continue a projection.
+const PROJECTION_BP: usize = PROJECTION_STOP - 1;
+
+/// The first token of an unparsed node, used to determine if the node can
+/// follow a "." or continue a projection.
+#[derive(PartialEq)]
+enum Start {
+    /// Identifier, quoted identifier, "{", or "&".
+    Dot,
+    /// "*" (object wildcard).
+    Star,
+    /// "[" that starts an index, slice, wildcard, filter, or multi-list.
+    Bracket,
+    /// Anything else.
+    Other,
+}
+
+/// Syntax used to unparse a Subexpr node.
+enum SubexprForm<'a> {
+    /// `lhs[0]`
+    Index(i32),
+    /// `lhs[0:1]`, followed by the right hand side of the slice projection.
+    Slice(&'a Ast, Option<&'a Ast>),
+    /// `lhs.rhs`
+    Dot,
+    /// `lhs | rhs`
+    Pipe,
+}
+
+/// Syntax used to unparse a projection.
+enum ProjectionForm<'a> {
+    /// `lhs.*`
+    Values,
+    /// `lhs[]`
+    Flatten,
+    /// `[0:1]`
+    Slice(&'a Ast),
+    /// `lhs[?predicate]`
+    Filter(&'a Ast),
+    /// `lhs[*]`
+    Wildcard,
+}
+
+/// A projection node, or a node that is formatted as a projection.
+struct Projection<'a> {
+    form: ProjectionForm<'a>,
+    /// Node that is projected, or None for the current node.
+    lhs: Option<&'a Ast>,
+    /// Node evaluated for each projected element, or None for identity.
+    rhs: Option<&'a Ast>,
+}
+
+impl Projection<'_> {
+    /// Binding power of the token that follows the left hand side.
+    fn lhs_bp(&self) -> usize {
+        match self.form {
+            ProjectionForm::Values => Token::Dot.lbp(),
+            ProjectionForm::Flatten => Token::Flatten.lbp(),
+            ProjectionForm::Filter(_) => Token::Filter.lbp(),
+            _ => Token::Lbracket.lbp(),
+        }
+    }
+
+    /// Binding power used to parse the right hand side.
+    fn rhs_bp(&self) -> usize {
+        match self.form {
+            ProjectionForm::Flatten => Token::Flatten.lbp(),
+            ProjectionForm::Filter(_) => Token::Filter.lbp(),
+            _ => WILDCARD_BP,
+        }
+    }
+}
+
+fn non_identity(node: &Ast) -> Option<&Ast> {
+    match *node {
+        Ast::Identity { .. } => None,
+        _ => Some(node),
+    }
+}
+
+fn projection(node: &Ast) -> Option<Projection<'_>> {
+    let (form, lhs, rhs) = match *node {
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => match (&**lhs, &**rhs) {
+            (Ast::ObjectValues { node, .. }, _) => (
+                ProjectionForm::Values,
+                non_identity(node),
+                non_identity(rhs),
+            ),
+            (Ast::Flatten { node, .. }, _) => (
+                ProjectionForm::Flatten,
+                non_identity(node),
+                non_identity(rhs),
+            ),
+            (Ast::Slice { .. }, _) => (ProjectionForm::Slice(lhs), None, non_identity(rhs)),
+            (
+                _,
+                Ast::Condition {
+                    predicate, then, ..
+                },
+            ) => (
+                ProjectionForm::Filter(predicate),
+                non_identity(lhs),
+                non_identity(then),
+            ),
+            _ => (
+                ProjectionForm::Wildcard,
+                non_identity(lhs),
+                non_identity(rhs),
+            ),
+        },
+        Ast::ObjectValues { ref node, .. } => (ProjectionForm::Values, non_identity(node), None),
+        Ast::Flatten { ref node, .. } => (ProjectionForm::Flatten, non_identity(node), None),
+        Ast::Slice { .. } => (ProjectionForm::Slice(node), None, None),
+        Ast::Condition {
+            ref predicate,
+            ref then,
+            ..
+        } => (ProjectionForm::Filter(predicate), None, non_identity(then)),
+        _ => return None,
+    };
+    Some(Projection { form, lhs, rhs })
+}
+
+fn subexpr_lhs_bp(form: &SubexprForm<'_>) -> usize {
+    match *form {
+        SubexprForm::Index(_) | SubexprForm::Slice(..) => Token::Lbracket.lbp(),
+        SubexprForm::Dot => Token::Dot.lbp(),
+        SubexprForm::Pipe => Token::Pipe.lbp(),
+    }
+}
+
+fn infix_bp(node: &Ast) -> Option<(usize, String)> {
+    match *node {
+        Ast::Comparison { ref comparator, .. } => Some((Token::Eq.lbp(), comparator.to_string())),
+        Ast::Arithmetic { ref operator, .. } => {
+            Some((operator.token().lbp(), operator.to_string()))
+        }
+        Ast::And { .. } => Some((Token::And.lbp(), "&&".to_owned())),
+        Ast::Or { .. } => Some((Token::Or.lbp(), "||".to_owned())),
+        _ => None,
+    }
+}
+
+/// Formats an AST as a JMESPath expression.
+#[derive(Default)]
+struct Unparser {
+    /// Memoized (top, tail) binding powers of each node.
+    bounds: HashMap<*const Ast, (usize, usize)>,
+    out: String,
+}
+
+impl Unparser {
+    fn subexpr_form<'a>(&mut self, rhs: &'a Ast) -> SubexprForm<'a> {
+        match *rhs {
+            Ast::Index { idx, .. } => SubexprForm::Index(idx),
+            Ast::Projection { ref lhs, .. } if matches!(**lhs, Ast::Slice { .. }) => {
+                SubexprForm::Slice(lhs, projection(rhs).and_then(|p| p.rhs))
+            }
+            Ast::MultiList { .. } => SubexprForm::Dot,
+            _ if self.start(rhs, Token::Dot.lbp(), 0) == Start::Dot => SubexprForm::Dot,
+            _ => SubexprForm::Pipe,
+        }
+    }
+
+    /// Returns the (top, tail) binding powers of a node.
+    fn bounds(&mut self, node: &Ast) -> (usize, usize) {
+        let key = node as *const Ast;
+        if let Some(&bounds) = self.bounds.get(&key) {
+            return bounds;
+        }
+        let bounds = self.compute_bounds(node);
+        self.bounds.insert(key, bounds);
+        bounds
+    }
+
+    fn compute_bounds(&mut self, node: &Ast) -> (usize, usize) {
+        if let Some((bp, _)) = infix_bp(node) {
+            return (bp, bp);
+        }
+        if let Some(projection) = projection(node) {
+            let top = match (&projection.form, projection.lhs) {
+                (ProjectionForm::Slice(_), _) | (_, None) => ATOM_BP,
+                _ => projection.lhs_bp(),
+            };
+            let tail = self.suffix_tail(projection.rhs, projection.rhs_bp());
+            return (top, min(PROJECTION_BP, tail));
+        }
+        match *node {
+            Ast::Subexpr { ref rhs, .. } => match self.subexpr_form(rhs) {
+                SubexprForm::Index(_) => (Token::Lbracket.lbp(), ATOM_BP),
+                SubexprForm::Slice(_, rhs) => {
+                    let tail = self.suffix_tail(rhs, WILDCARD_BP);
+                    (Token::Lbracket.lbp(), min(PROJECTION_BP, tail))
+                }
+                SubexprForm::Dot if matches!(**rhs, Ast::MultiList { .. }) => {
+                    (Token::Dot.lbp(), Token::Dot.lbp())
+                }
+                SubexprForm::Dot => {
+                    let (_, tail) = self.bounds(rhs);
+                    (Token::Dot.lbp(), min(Token::Dot.lbp(), tail))
+                }
+                SubexprForm::Pipe => (Token::Pipe.lbp(), Token::Pipe.lbp()),
+            },
+            Ast::Not { .. } => (ATOM_BP, Token::Not.lbp()),
+            Ast::UnaryArithmetic { .. } => (ATOM_BP, UNARY_BP),
+            Ast::Expref { .. } => (ATOM_BP, Token::Ampersand.lbp()),
+            Ast::Let { .. } => (ATOM_BP, 0),
+            _ => (ATOM_BP, ATOM_BP),
+        }
+    }
+
+    /// Returns the tail binding power of the right hand side of a projection.
+    fn suffix_tail(&mut self, rhs: Option<&Ast>, bp: usize) -> usize {
+        match rhs {
+            // Wrapped in a multi-list when it cannot follow the projection.
+            Some(rhs) if self.start(rhs, bp, 0) != Start::Other => self.bounds(rhs).1,
+            _ => ATOM_BP,
+        }
+    }
+
+    fn needs_parens(&mut self, node: &Ast, rbp: usize, follow: usize) -> bool {
+        let (top, tail) = self.bounds(node);
+        top <= rbp || follow > tail
+    }
+
+    /// Returns the first token of a node parsed with the given binding power
+    /// and followed by a token with the binding power of `follow`.
+    fn start(&mut self, node: &Ast, rbp: usize, follow: usize) -> Start {
+        if self.needs_parens(node, rbp, follow) {
+            return Start::Other;
+        }
+        if let Some((bp, _)) = infix_bp(node) {
+            let lhs = match *node {
+                Ast::Comparison { ref lhs, .. }
+                | Ast::Arithmetic { ref lhs, .. }
+                | Ast::And { ref lhs, .. }
+                | Ast::Or { ref lhs, .. } => lhs,
+                _ => unreachable!(),
+            };
+            return self.start(lhs, rbp, bp);
+        }
+        if let Some(projection) = projection(node) {
+            return match (&projection.form, projection.lhs) {
+                (ProjectionForm::Slice(_), _) => Start::Bracket,
+                (ProjectionForm::Values, None) => Start::Star,
+                (ProjectionForm::Flatten, None) => Start::Other,
+                (_, None) => Start::Bracket,
+                (_, Some(lhs)) => self.start(lhs, rbp, projection.lhs_bp()),
+            };
+        }
+        match *node {
+            Ast::Field { .. }
+            | Ast::Function { .. }
+            | Ast::MultiHash { .. }
+            | Ast::Expref { .. } => Start::Dot,
+            Ast::MultiList { ref elements, .. } if !elements.is_empty() => Start::Bracket,
+            Ast::Index { .. } => Start::Bracket,
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => {
+                let form = self.subexpr_form(rhs);
+                self.start(lhs, rbp, subexpr_lhs_bp(&form))
+            }
+            _ => Start::Other,
+        }
+    }
+
+    /// Writes a node that is parsed with the given binding power and
+    /// followed by a token with the binding power of `follow`.
+    fn unparse(&mut self, node: &Ast, rbp: usize, follow: usize) {
+        if self.needs_parens(node, rbp, follow) {
+            self.out.push('(');
+            self.unparse(node, 0, 0);
+            self.out.push(')');
+            return;
+        }
+        if let Some(projection) = projection(node) {
+            return self.unparse_projection(&projection, rbp, follow);
+        }
+        match *node {
+            Ast::Identity { .. } => self.out.push('@'),
+            Ast::Root { .. } => self.out.push('$'),
+            Ast::VariableRef { ref name, .. } => {
+                self.out.push('$');
+                self.out.push_str(name);
+            }
+            Ast::Field { ref name, .. } => self.unparse_identifier(name),
+            Ast::Literal { ref value, .. } => self.unparse_literal(value),
+            Ast::Index { idx, .. } => self.out.push_str(&format!("[{}]", idx)),
+            Ast::MultiList { ref elements, .. } if elements.is_empty() => self.out.push_str("`[]`"),
+            Ast::MultiList { ref elements, .. } => {
+                self.out.push('[');
+                let start = self.out.len();
+                self.unparse_list(elements);
+                // "[*]" would be parsed as a wildcard projection.
+                if &self.out[start..] == "*" {
+                    self.out.replace_range(start.., "(*)");
+                }
+                self.out.push(']');
+            }
+            Ast::MultiHash { ref elements, .. } if elements.is_empty() => self.out.push_str("`{}`"),
+            Ast::MultiHash { ref elements, .. } => {
+                self.out.push('{');
+                for (i, kvp) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.unparse_identifier(&kvp.key);
+                    self.out.push_str(": ");
+                    self.unparse(&kvp.value, 0, 0);
+                }
+                self.out.push('}');
+            }
+            Ast::Function {
+                ref name, ref args, ..
+            } => {
+                self.out.push_str(name);
+                self.out.push('(');
+                self.unparse_list(args);
+                self.out.push(')');
+            }
+            Ast::Not { node: ref n, .. } => {
+                self.out.push('!');
+                self.unparse(n, Token::Not.lbp(), follow);
+            }
+            Ast::UnaryArithmetic {
+                ref operator,
+                node: ref n,
+                ..
+            } => {
+                self.out.push_str(&operator.to_string());
+                self.unparse(n, UNARY_BP, follow);
+            }
+            Ast::Expref { ref ast, .. } => {
+                self.out.push('&');
+                self.unparse(ast, Token::Ampersand.lbp(), follow);
+            }
+            Ast::Let {
+                ref bindings,
+                ref expr,
+                ..
+            } => {
+                self.out.push_str("let ");
+                for (i, binding) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push('$');
+                    self.out.push_str(&binding.name);
+                    self.out.push_str(" = ");
+                    self.unparse(&binding.value, 0, 0);
+                }
+                self.out.push_str(" in ");
+                self.unparse(expr, 0, follow);
+            }
+            Ast::Comparison {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Arithmetic {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::And {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Or {
+                ref lhs, ref rhs, ..
+            } => {
+                let (bp, op) = infix_bp(node).expect("infix operator");
+                self.unparse_infix(lhs, &op, bp, rhs, rbp, follow);
+            }
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => {
+                let form = self.subexpr_form(rhs);
+                match form {
+                    SubexprForm::Pipe => {
+                        self.unparse_infix(lhs, "|", Token::Pipe.lbp(), rhs, rbp, follow)
+                    }
+                    _ => self.unparse(lhs, rbp, subexpr_lhs_bp(&form)),
+                }
+                match form {
+                    SubexprForm::Index(idx) => self.out.push_str(&format!("[{}]", idx)),
+                    SubexprForm::Slice(slice, rhs) => {
+                        self.unparse_slice(slice);
+                        self.unparse_suffix(rhs, WILDCARD_BP, follow);
+                    }
+                    SubexprForm::Dot => {
+                        self.out.push('.');
+                        self.unparse(rhs, Token::Dot.lbp(), follow);
+                    }
+                    SubexprForm::Pipe => {}
+                }
+            }
+            Ast::Projection { .. }
+            | Ast::ObjectValues { .. }
+            | Ast::Flatten { .. }
+            | Ast::Slice { .. }
+            | Ast::Condition { .. } => unreachable!("formatted as a projection"),
+        }
+    }
+
+    fn unparse_projection(&mut self, projection: &Projection<'_>, rbp: usize, follow: usize) {
+        if let Some(lhs) = projection.lhs {
+            self.unparse(lhs, rbp, projection.lhs_bp());
+        }
+        match projection.form {
+            ProjectionForm::Values if projection.lhs.is_some() => self.out.push_str(".*"),
+            ProjectionForm::Values => self.out.push('*'),
+            ProjectionForm::Flatten => self.out.push_str("[]"),
+            ProjectionForm::Slice(slice) => self.unparse_slice(slice),
+            ProjectionForm::Filter(predicate) => {
+                self.out.push_str("[?");
+                self.unparse(predicate, 0, 0);
+                self.out.push(']');
+            }
+            ProjectionForm::Wildcard => self.out.push_str("[*]"),
+        }
+        self.unparse_suffix(projection.rhs, projection.rhs_bp(), follow);
+    }
+
+    fn unparse_infix(
+        &mut self,
+        lhs: &Ast,
+        op: &str,
+        bp: usize,
+        rhs: &Ast,
+        rbp: usize,
+        follow: usize,
+    ) {
+        self.unparse(lhs, rbp, bp);
+        self.out.push(' ');
+        self.out.push_str(op);
+        self.out.push(' ');
+        self.unparse(rhs, bp, follow);
+    }
+
+    /// Writes the right hand side of a projection.
+    fn unparse_suffix(&mut self, rhs: Option<&Ast>, bp: usize, follow: usize) {
+        let rhs = match rhs {
+            Some(rhs) => rhs,
+            None => return,
+        };
+        match self.start(rhs, bp, 0) {
+            Start::Dot | Start::Star => {
+                self.out.push('.');
+                self.unparse(rhs, bp, follow);
+            }
+            Start::Bracket => self.unparse(rhs, bp, follow),
+            // The node has no syntax that can continue a projection, so it
+            // is evaluated as the only element of a multi-list: `[node][0]`.
+            Start::Other => {
+                self.out.push('[');
+                self.unparse(rhs, 0, 0);
+                self.out.push_str("][0]");
+            }
+        }
+    }
+
+    fn unparse_list(&mut self, nodes: &[Ast]) {
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.unparse(node, 0, 0);
+        }
+    }
+
+    fn unparse_slice(&mut self, node: &Ast) {
+        if let Ast::Slice {
+            start, stop, step, ..
+        } = *node
+        {
+            self.out.push('[');
+            if let Some(start) = start {
+                self.out.push_str(&start.to_string());
+            }
+            self.out.push(':');
+            if let Some(stop) = stop {
+                self.out.push_str(&stop.to_string());
+            }
+            if step != 1 {
+                self.out.push(':');
+                self.out.push_str(&step.to_string());
+            }
+            self.out.push(']');
+        }
+    }
+
+    fn unparse_identifier(&mut self, name: &str) {
+        push_identifier(&mut self.out, name);
+    }
+
+    fn unparse_literal(&mut self, value: &Variable) {
+        match *value {
+            // Raw strings cannot contain a backslash that precedes a quote.
+            Variable::String(ref s) if !s.contains('\\') => {
+                self.out.push('\'');
+                self.out.push_str(&s.replace('\'', "\\'"));
+                self.out.push('\'');
+            }
+            _ => {
+                self.out.push('`');
+                self.out.push_str(&value.to_string().replace('`', "\\`"));
+                self.out.push('`');
+            }
+        }
+    }
+}
+
+/// Appends a field name to an expression, quoting it if it is not a valid
+/// unquoted identifier.
+pub(crate) fn push_identifier(out: &mut String, name: &str) {
+    let mut chars = name.chars();
+    let is_identifier = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+    if is_identifier {
+        out.push_str(name);
+    } else {
+        let quoted = serde_json::to_string(name).expect("strings can be encoded");
+        out.push_str(&quoted);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    fn displays_pretty_printed_ast_node() {
+    fn displays_ast_node_as_expression() {
         let node = Ast::Field {
             name: "abc".to_string(),
             offset: 4,
         };
+        assert_eq!("abc", format!("{}", node));
+    }
+
+    fn canonical(expr: &str) -> String {
+        crate::parse(expr).unwrap().to_string()
+    }
+
+    #[test]
+    fn displays_canonical_expressions() {
+        let cases = [
+            ("foo . bar", "foo.bar"),
+            ("foo[0][1]", "foo[0][1]"),
+            ("foo[*].bar[?a==`1`].baz", "foo[*].bar[?a == `1`].baz"),
+            ("*.foo", "*.foo"),
+            ("foo[].bar[]", "foo[].bar[]"),
+            ("foo[::1]", "foo[:]"),
+            ("[1:2:-1].a", "[1:2:-1].a"),
+            ("a || b && c", "a || b && c"),
+            ("(a || b) && c", "(a || b) && c"),
+            ("!(a == b)", "!(a == b)"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("a.{x: b, \"y z\": c}", "a.{x: b, \"y z\": c}"),
+            ("\"foo-bar\"", "\"foo-bar\""),
+            ("'it\\'s'", "'it\\'s'"),
+            ("`\"a\\\\b\"`", "`\"a\\\\b\"`"),
+            ("sort_by(@, &a)", "sort_by(@, &a)"),
+            ("let $a = b in $a.c", "let $a = b in $a.c"),
+            ("$.a | [@, $]", "$.a.[@, $]"),
+            ("a | b[0]", "a.b[0]"),
+            ("a | [b][0]", "a | [b][0]"),
+            ("- a * + b", "-a * +b"),
+            ("a - -b", "a - -b"),
+            ("-a.b[0]", "-a.b[0]"),
+            ("a \u{d7} b \u{f7} c \u{2212} d", "a * b / c - d"),
+        ];
+        for &(expr, expected) in cases.iter() {
+            assert_eq!(expected, canonical(expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn displays_parentheses_when_required() {
+        let cases = [
+            "(a.b)[*].c",
+            "(a[*].b).c",
+            "(a.b[*]).c",
+            "a | (b.c)[0]",
+            "(a[*])[0]",
+            "(&a).b",
+            "[(*)]",
+            "(let $a = b in $a) || c",
+            "-(a + b)",
+            "(-a).b",
+            "(-a)[*]",
+        ];
+        for expr in cases.iter() {
+            assert_eq!(*expr, canonical(expr));
+        }
+    }
+
+    const EVERY_NODE: &str = "let $a = `1` in [a.b[0], c[*].d, e[?f == $a].g, \
+        h.*, i[].j, k[1:], !l && m || n, -o + `2`, sort_by(@, &p), {q: $.r}]";
+
+    #[test]
+    fn visits_every_node() {
+        struct Counter(Vec<String>, usize);
+        impl Visitor for Counter {
+            fn visit_ast(&mut self, node: &Ast) {
+                self.1 += 1;
+                if let Ast::Field { ref name, .. } = *node {
+                    self.0.push(name.clone());
+                }
+                walk_ast(self, node);
+            }
+        }
+        let ast = crate::parse(EVERY_NODE).unwrap();
+        let mut counter = Counter(vec![], 0);
+        counter.visit_ast(&ast);
+        let fields: String = counter.0.concat();
+        assert_eq!("abcdefghijklmnopr", fields);
+        assert_eq!(49, counter.1);
+    }
+
+    #[test]
+    fn default_fold_rebuilds_the_same_ast() {
+        struct Identity;
+        impl Fold for Identity {}
+        let ast = crate::parse(EVERY_NODE).unwrap();
+        assert_eq!(ast, Identity.fold_ast(ast.clone()));
+    }
+
+    #[test]
+    fn folds_nested_nodes() {
+        struct Negate;
+        impl Fold for Negate {
+            fn fold_ast(&mut self, node: Ast) -> Ast {
+                match fold_ast(self, node) {
+                    Ast::Comparison { lhs, rhs, .. } => Ast::Not {
+                        offset: 0,
+                        node: Box::new(Ast::Comparison {
+                            offset: 0,
+                            comparator: Comparator::Equal,
+                            lhs,
+                            rhs,
+                        }),
+                    },
+                    node => node,
+                }
+            }
+        }
+        let ast = crate::parse("a[?b > c].{d: e < f}").unwrap();
+        assert_eq!(
+            "a[?!(b == c)].{d: !(e == f)}",
+            Negate.fold_ast(ast).to_string()
+        );
+    }
+
+    #[cfg(feature = "serde-ast")]
+    #[test]
+    fn serializes_ast_as_tagged_json() {
+        let ast = crate::parse("foo[?a > `1`]").unwrap();
+        let expected = serde_json::json!({
+            "type": "projection",
+            "offset": 12,
+            "lhs": {"type": "field", "offset": 0, "name": "foo"},
+            "rhs": {
+                "type": "condition",
+                "offset": 12,
+                "predicate": {
+                    "type": "comparison",
+                    "offset": 9,
+                    "comparator": "greater_than",
+                    "lhs": {"type": "field", "offset": 5, "name": "a"},
+                    "rhs": {"type": "literal", "offset": 9, "value": 1}
+                },
+                "then": {"type": "identity", "offset": 12}
+            }
+        });
+        assert_eq!(expected, serde_json::to_value(&ast).unwrap());
+    }
+
+    #[cfg(feature = "serde-ast")]
+    #[test]
+    fn deserializes_serialized_ast() {
+        let expr = format!(
+            "{} | [[:2], [::-1], a // `3`, `{{\"x\": [null]}}`]",
+            EVERY_NODE
+        );
+        let ast = crate::parse(&expr).unwrap();
+        let json = serde_json::to_string(&ast).unwrap();
+        assert_eq!(ast, serde_json::from_str::<Ast>(&json).unwrap());
+    }
+
+    #[cfg(feature = "serde-ast")]
+    #[test]
+    fn rejects_unknown_schema_versions() {
+        let versioned = |version: u32| {
+            let json = serde_json::json!({
+                "version": version,
+                "ast": {"type": "wildcard", "offset": 0}
+            });
+            serde_json::from_value::<VersionedAst>(json)
+        };
+        assert_eq!(
+            "unsupported AST schema version 2, expected 1",
+            versioned(SCHEMA_VERSION + 1).unwrap_err().to_string()
+        );
+        let err = serde_json::from_str::<VersionedAst>(r#"{"ast": {"type": "identity"}}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing field `version`"), "{}", err);
+        assert!(versioned(SCHEMA_VERSION)
+            .unwrap_err()
+            .to_string()
+            .contains("wildcard"));
+    }
+
+    #[test]
+    fn displays_deeply_nested_projections() {
+        let expr = format!("a{}", ".b[?c].d[].e[*]".repeat(40));
+        assert_eq!(expr, canonical(&expr));
+    }
+
+    #[test]
+    fn displays_nodes_without_direct_syntax() {
+        let field = |name: &str| {
+            Box::new(Ast::Field {
+                offset: 0,
+                name: name.to_owned(),
+            })
+        };
+        let pipe_in_projection = Ast::Projection {
+            offset: 0,
+            lhs: field("a"),
+            rhs: Box::new(Ast::Or {
+                offset: 0,
+                lhs: field("b"),
+                rhs: field("c"),
+            }),
+        };
+        assert_eq!("a[*][b || c][0]", pipe_in_projection.to_string());
+        let values = Ast::ObjectValues {
+            offset: 0,
+            node: field("a"),
+        };
+        assert_eq!("a.*", values.to_string());
         assert_eq!(
-            "Field {\n    offset: 4,\n    name: \"abc\",\n}",
-            format!("{}", node)
+            "`[]`",
+            Ast::MultiList {
+                offset: 0,
+                elements: vec![]
+            }
+            .to_string()
         );
     }
 }
@@ -0,0 +1,664 @@
+//! Searches borrowed documents without converting them to `Variable`s.
+//!
+//! Nodes that only select parts of a document (fields, indexes, slices,
+//! projections, filters, etc.) borrow from the document. Parts of the
+//! document are only copied into `Variable`s when they are part of the
+//! result, are passed to a function, or are compared or used in arithmetic.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use serde_json::Value;
+
+use crate::ast::{walk_ast, Ast, Visitor};
+use crate::interpreter::{arithmetic, interpret, produces_value, unary_arithmetic, SearchResult};
+use crate::variable::{slice_indices, JmespathType};
+use crate::{Context, ErrorReason, JmespathError, Map, Rcvar, RuntimeError, Variable};
+
+/// Read-only access to a document that can be searched using
+/// `Expression::search_borrowed()`.
+///
+/// This trait is implemented for `serde_json::Value`, and can be
+/// implemented for other document types to search them in place.
+pub trait JmespathValue {
+    /// Returns the JMESPath type of the value.
+    fn get_type(&self) -> JmespathType;
+
+    /// Returns true if the value is truthy. False, null, and empty strings,
+    /// arrays, and objects are falsy.
+    fn is_truthy(&self) -> bool;
+
+    /// Returns the value of a field if the value is an object with the field.
+    fn get_field(&self, name: &str) -> Option<&Self>;
+
+    /// Returns the number of elements if the value is an array.
+    fn array_len(&self) -> Option<usize>;
+
+    /// Returns an element if the value is an array with the index.
+    fn get_index(&self, index: usize) -> Option<&Self>;
+
+    /// Returns the values of the fields if the value is an object.
+    fn object_values(&self) -> Option<Vec<&Self>>;
+
+    /// Copies the value into a `Variable`.
+    fn to_variable(&self) -> Result<Variable, JmespathError>;
+}
+
+impl JmespathValue for Value {
+    fn get_type(&self) -> JmespathType {
+        match *self {
+            Value::Null => JmespathType::Null,
+            Value::Bool(_) => JmespathType::Boolean,
+            Value::Number(_) => JmespathType::Number,
+            Value::String(_) => JmespathType::String,
+            Value::Array(_) => JmespathType::Array,
+            Value::Object(_) => JmespathType::Object,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match *self {
+            Value::Bool(b) => b,
+            Value::String(ref s) => !s.is_empty(),
+            Value::Array(ref a) => !a.is_empty(),
+            Value::Object(ref o) => !o.is_empty(),
+            Value::Number(_) => true,
+            Value::Null => false,
+        }
+    }
+
+    fn get_field(&self, name: &str) -> Option<&Value> {
+        self.as_object().and_then(|o| o.get(name))
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        self.as_array().map(Vec::len)
+    }
+
+    fn get_index(&self, index: usize) -> Option<&Value> {
+        self.as_array().and_then(|a| a.get(index))
+    }
+
+    fn object_values(&self) -> Option<Vec<&Value>> {
+        self.as_object().map(|o| o.values().collect())
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Variable::try_from(self)
+    }
+}
+
+/// Value produced while searching a borrowed document.
+enum Node<'a, V: ?Sized> {
+    /// Part of the document being searched.
+    Borrowed(&'a V),
+    /// Value computed by the expression.
+    Owned(Rcvar),
+    /// Array created by the expression, whose elements may be borrowed.
+    Array(Rc<Vec<Node<'a, V>>>),
+}
+
+impl<V: ?Sized> Clone for Node<'_, V> {
+    fn clone(&self) -> Self {
+        match *self {
+            Node::Borrowed(v) => Node::Borrowed(v),
+            Node::Owned(ref v) => Node::Owned(v.clone()),
+            Node::Array(ref v) => Node::Array(v.clone()),
+        }
+    }
+}
+
+impl<'a, V: JmespathValue + ?Sized> Node<'a, V> {
+    fn null() -> Self {
+        Node::Owned(Rcvar::new(Variable::Null))
+    }
+
+    fn is_truthy(&self) -> bool {
+        match *self {
+            Node::Borrowed(v) => v.is_truthy(),
+            Node::Owned(ref v) => v.is_truthy(),
+            Node::Array(ref v) => !v.is_empty(),
+        }
+    }
+
+    fn get_type(&self) -> JmespathType {
+        match *self {
+            Node::Borrowed(v) => v.get_type(),
+            Node::Owned(ref v) => v.get_type(),
+            Node::Array(_) => JmespathType::Array,
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        self.get_type() == JmespathType::Null
+    }
+
+    fn get_field(&self, name: &str) -> Self {
+        match *self {
+            Node::Borrowed(v) => v.get_field(name).map_or_else(Node::null, Node::Borrowed),
+            Node::Owned(ref v) => Node::Owned(v.get_field(name)),
+            Node::Array(_) => Node::null(),
+        }
+    }
+
+    fn get_index(&self, idx: i32) -> Self {
+        match *self {
+            Node::Borrowed(v) => {
+                let index = match v.array_len() {
+                    Some(_) if idx >= 0 => Some(idx as usize),
+                    Some(len) => len.checked_sub(((-idx) as usize).max(1)),
+                    None => None,
+                };
+                index
+                    .and_then(|i| v.get_index(i))
+                    .map_or_else(Node::null, Node::Borrowed)
+            }
+            Node::Owned(ref v) if idx >= 0 => Node::Owned(v.get_index(idx as usize)),
+            Node::Owned(ref v) => Node::Owned(v.get_negative_index((-idx) as usize)),
+            Node::Array(ref v) => {
+                let index = if idx >= 0 {
+                    Some(idx as usize)
+                } else {
+                    v.len().checked_sub(((-idx) as usize).max(1))
+                };
+                index
+                    .and_then(|i| v.get(i).cloned())
+                    .unwrap_or_else(Node::null)
+            }
+        }
+    }
+
+    /// Returns the elements of an array.
+    fn elements(&self) -> Option<Vec<Self>> {
+        match *self {
+            Node::Borrowed(v) => v.array_len().map(|len| {
+                (0..len)
+                    .filter_map(|i| v.get_index(i))
+                    .map(Node::Borrowed)
+                    .collect()
+            }),
+            Node::Owned(ref v) => v
+                .as_array()
+                .map(|a| a.iter().cloned().map(Node::Owned).collect()),
+            Node::Array(ref v) => Some(v.to_vec()),
+        }
+    }
+
+    /// Returns the values of an object.
+    fn values(&self) -> Option<Vec<Self>> {
+        match *self {
+            Node::Borrowed(v) => v
+                .object_values()
+                .map(|values| values.into_iter().map(Node::Borrowed).collect()),
+            Node::Owned(ref v) => v
+                .as_object()
+                .map(|o| o.values().cloned().map(Node::Owned).collect()),
+            Node::Array(_) => None,
+        }
+    }
+
+    /// Converts the node to a variable, copying it if it is borrowed.
+    fn into_rcvar(self) -> SearchResult {
+        match self {
+            Node::Borrowed(v) => Ok(Rcvar::new(v.to_variable()?)),
+            Node::Owned(v) => Ok(v),
+            Node::Array(v) => {
+                let elements = Rc::try_unwrap(v).unwrap_or_else(|v| v.to_vec());
+                let values = elements
+                    .into_iter()
+                    .map(Node::into_rcvar)
+                    .collect::<Result<_, _>>()?;
+                Ok(Rcvar::new(Variable::Array(values)))
+            }
+        }
+    }
+
+    fn array(nodes: Vec<Self>) -> Self {
+        Node::Array(Rc::new(nodes))
+    }
+}
+
+/// Searches a borrowed document using an AST node.
+pub fn search<V: JmespathValue + ?Sized>(
+    data: &V,
+    ast: &Ast,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    let mut searcher = Searcher {
+        root: data,
+        scopes: vec![],
+    };
+    searcher
+        .search(Node::Borrowed(data), ast, ctx)?
+        .into_rcvar()
+}
+
+struct Searcher<'a, V: ?Sized> {
+    root: &'a V,
+    /// Variables bound by let expressions, with the innermost scope last.
+    scopes: Vec<BTreeMap<String, Node<'a, V>>>,
+}
+
+impl<'a, V: JmespathValue + ?Sized> Searcher<'a, V> {
+    fn search(
+        &mut self,
+        data: Node<'a, V>,
+        node: &Ast,
+        ctx: &mut Context<'_>,
+    ) -> Result<Node<'a, V>, JmespathError> {
+        ctx.enter(node.offset())?;
+        let result = self.search_node(data, node, ctx);
+        ctx.exit();
+        let result = result?;
+        if produces_value(node) {
+            match result {
+                Node::Borrowed(_) => {}
+                Node::Owned(ref v) => ctx.check_value(v, node.offset())?,
+                Node::Array(ref v) => ctx.check_size(v.len(), node.offset())?,
+            }
+        }
+        Ok(result)
+    }
+
+    fn search_node(
+        &mut self,
+        data: Node<'a, V>,
+        node: &Ast,
+        ctx: &mut Context<'_>,
+    ) -> Result<Node<'a, V>, JmespathError> {
+        match *node {
+            Ast::Field { ref name, .. } => Ok(data.get_field(name)),
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => {
+                let left = self.search(data, lhs, ctx)?;
+                self.search(left, rhs, ctx)
+            }
+            Ast::Identity { .. } => Ok(data),
+            Ast::Root { .. } => Ok(Node::Borrowed(self.root)),
+            Ast::Literal { ref value, .. } => Ok(Node::Owned(value.clone())),
+            Ast::Index { idx, .. } => Ok(data.get_index(idx)),
+            Ast::Or {
+                ref lhs, ref rhs, ..
+            } => {
+                let left = self.search(data.clone(), lhs, ctx)?;
+                if left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.search(data, rhs, ctx)
+                }
+            }
+            Ast::And {
+                ref lhs, ref rhs, ..
+            } => {
+                let left = self.search(data.clone(), lhs, ctx)?;
+                if !left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.search(data, rhs, ctx)
+                }
+            }
+            Ast::Not { ref node, .. } => {
+                let result = self.search(data, node, ctx)?;
+                Ok(Node::Owned(Rcvar::new(Variable::Bool(!result.is_truthy()))))
+            }
+            Ast::Condition {
+                ref predicate,
+                ref then,
+                ..
+            } => {
+                let cond_result = self.search(data.clone(), predicate, ctx)?;
+                if cond_result.is_truthy() {
+                    self.search(data, then, ctx)
+                } else {
+                    Ok(Node::null())
+                }
+            }
+            Ast::Comparison {
+                ref comparator,
+                ref lhs,
+                ref rhs,
+                ..
+            } => {
+                let left = self.search(data.clone(), lhs, ctx)?.into_rcvar()?;
+                let right = self.search(data, rhs, ctx)?.into_rcvar()?;
+                Ok(Node::Owned(
+                    left.compare(comparator, &right)
+                        .map_or(Rcvar::new(Variable::Null), |result| {
+                            Rcvar::new(Variable::Bool(result))
+                        }),
+                ))
+            }
+            Ast::Arithmetic {
+                ref operator,
+                ref lhs,
+                ref rhs,
+                offset,
+            } => {
+                let left = self.search(data.clone(), lhs, ctx)?.into_rcvar()?;
+                let right = self.search(data, rhs, ctx)?.into_rcvar()?;
+                arithmetic(&left, operator, &right, offset, ctx).map(Node::Owned)
+            }
+            Ast::UnaryArithmetic {
+                ref operator,
+                ref node,
+                offset,
+            } => {
+                let operand = self.search(data, node, ctx)?.into_rcvar()?;
+                unary_arithmetic(operator, &operand, offset, ctx).map(Node::Owned)
+            }
+            Ast::ObjectValues { ref node, .. } => {
+                let subject = self.search(data, node, ctx)?;
+                match subject.values() {
+                    Some(values) => Ok(Node::array(values)),
+                    None => Ok(Node::null()),
+                }
+            }
+            Ast::Projection {
+                ref lhs, ref rhs, ..
+            } => match self.search(data, lhs, ctx)?.elements() {
+                None => Ok(Node::null()),
+                Some(elements) => {
+                    let mut collected = vec![];
+                    for element in elements {
+                        let current = self.search(element, rhs, ctx)?;
+                        if !current.is_null() {
+                            collected.push(current);
+                        }
+                    }
+                    Ok(Node::array(collected))
+                }
+            },
+            Ast::Flatten { ref node, .. } => match self.search(data, node, ctx)?.elements() {
+                None => Ok(Node::null()),
+                Some(elements) => {
+                    let mut collected = vec![];
+                    for element in elements {
+                        match element.elements() {
+                            Some(nested) => collected.extend(nested),
+                            None => collected.push(element),
+                        }
+                    }
+                    Ok(Node::array(collected))
+                }
+            },
+            Ast::MultiList { ref elements, .. } => {
+                if data.is_null() {
+                    Ok(Node::null())
+                } else {
+                    let mut collected = vec![];
+                    for node in elements {
+                        collected.push(self.search(data.clone(), node, ctx)?);
+                    }
+                    Ok(Node::array(collected))
+                }
+            }
+            Ast::MultiHash { ref elements, .. } => {
+                if data.is_null() {
+                    Ok(Node::null())
+                } else {
+                    let mut collected = Map::new();
+                    for kvp in elements {
+                        let value = self.search(data.clone(), &kvp.value, ctx)?;
+                        collected.insert(kvp.key.clone(), value.into_rcvar()?);
+                    }
+                    Ok(Node::Owned(Rcvar::new(Variable::Object(collected))))
+                }
+            }
+            Ast::Function {
+                ref name,
+                ref args,
+                offset,
+            } => {
+                let mut fn_args: Vec<Rcvar> = vec![];
+                for arg in args {
+                    fn_args.push(self.search(data.clone(), arg, ctx)?.into_rcvar()?);
+                }
+                // Exprefs are interpreted by the function, so they can only
+                // see the root and variables that are set on the context.
+                let exprefs = args.iter().any(|arg| matches!(*arg, Ast::Expref { .. }));
+                if exprefs && ctx.root.is_none() && references_root(args) {
+                    ctx.root = Some(Rcvar::new(self.root.to_variable()?));
+                }
+                let scopes = if exprefs { self.push_scopes(ctx)? } else { 0 };
+                // Reset the offset so that it points to the function being evaluated.
+                ctx.offset = offset;
+                let result = match ctx.runtime.get_function(name) {
+                    Some(f) => f.evaluate(&fn_args, ctx),
+                    None => {
+                        let reason =
+                            ErrorReason::Runtime(RuntimeError::UnknownFunction(name.to_owned()));
+                        Err(JmespathError::from_ctx(ctx, reason))
+                    }
+                };
+                for _ in 0..scopes {
+                    ctx.pop_scope();
+                }
+                result.map(Node::Owned)
+            }
+            Ast::Expref { .. } => {
+                interpret(&Rcvar::new(Variable::Null), node, ctx).map(Node::Owned)
+            }
+            Ast::Slice {
+                start,
+                stop,
+                step,
+                offset,
+            } => {
+                if step == 0 {
+                    ctx.offset = offset;
+                    let reason = ErrorReason::Runtime(RuntimeError::InvalidSlice);
+                    Err(JmespathError::from_ctx(ctx, reason))
+                } else {
+                    match data.elements() {
+                        Some(elements) => Ok(Node::array(
+                            slice_indices(elements.len(), start, stop, step)
+                                .into_iter()
+                                .map(|i| elements[i].clone())
+                                .collect(),
+                        )),
+                        None => Ok(Node::null()),
+                    }
+                }
+            }
+            Ast::Let {
+                ref bindings,
+                ref expr,
+                ..
+            } => {
+                let mut scope = BTreeMap::new();
+                for binding in bindings {
+                    let value = self.search(data.clone(), &binding.value, ctx)?;
+                    scope.insert(binding.name.clone(), value);
+                }
+                self.scopes.push(scope);
+                let result = self.search(data, expr, ctx);
+                self.scopes.pop();
+                result
+            }
+            Ast::VariableRef { ref name, offset } => {
+                let value = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.get(name).cloned());
+                match value.or_else(|| ctx.get_variable(name).map(Node::Owned)) {
+                    Some(value) => Ok(value),
+                    None => {
+                        ctx.offset = offset;
+                        let reason =
+                            ErrorReason::Runtime(RuntimeError::UndefinedVariable(name.to_owned()));
+                        Err(JmespathError::from_ctx(ctx, reason))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies the variables bound by let expressions onto the context so
+    /// that they can be referenced by exprefs, returning the number of
+    /// scopes that were pushed.
+    fn push_scopes(&self, ctx: &mut Context<'_>) -> Result<usize, JmespathError> {
+        let mut copied = vec![];
+        for scope in &self.scopes {
+            let mut variables = BTreeMap::new();
+            for (name, value) in scope {
+                variables.insert(name.clone(), value.clone().into_rcvar()?);
+            }
+            copied.push(variables);
+        }
+        let len = copied.len();
+        for scope in copied {
+            ctx.push_scope(scope);
+        }
+        Ok(len)
+    }
+}
+
+/// Returns true if any of the nodes reference the root node.
+fn references_root(nodes: &[Ast]) -> bool {
+    struct RootFinder(bool);
+
+    impl Visitor for RootFinder {
+        fn visit_ast(&mut self, node: &Ast) {
+            match *node {
+                Ast::Root { .. } => self.0 = true,
+                _ => walk_ast(self, node),
+            }
+        }
+    }
+
+    let mut finder = RootFinder(false);
+    for node in nodes {
+        finder.visit_ast(node);
+    }
+    finder.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compile;
+    use serde_json::json;
+
+    /// Document that fails if objects or arrays are copied.
+    #[repr(transparent)]
+    struct Scalars(Value);
+
+    impl JmespathValue for Scalars {
+        fn get_type(&self) -> JmespathType {
+            self.0.get_type()
+        }
+
+        fn is_truthy(&self) -> bool {
+            self.0.is_truthy()
+        }
+
+        fn get_field(&self, name: &str) -> Option<&Self> {
+            self.0.get_field(name).map(wrap)
+        }
+
+        fn array_len(&self) -> Option<usize> {
+            self.0.array_len()
+        }
+
+        fn get_index(&self, index: usize) -> Option<&Self> {
+            self.0.get_index(index).map(wrap)
+        }
+
+        fn object_values(&self) -> Option<Vec<&Self>> {
+            self.0
+                .object_values()
+                .map(|values| values.into_iter().map(wrap).collect())
+        }
+
+        fn to_variable(&self) -> Result<Variable, JmespathError> {
+            assert!(
+                !self.0.is_array() && !self.0.is_object(),
+                "copied {}",
+                self.0
+            );
+            self.0.to_variable()
+        }
+    }
+
+    fn wrap(value: &Value) -> &Scalars {
+        unsafe { &*(value as *const Value as *const Scalars) }
+    }
+
+    fn search(expression: &str, data: &Value) -> SearchResult {
+        compile(expression).unwrap().search_borrowed(wrap(data))
+    }
+
+    #[test]
+    fn only_copies_selected_values() {
+        let data = json!({
+            "people": [
+                {"name": "a", "age": 20, "tags": ["x"]},
+                {"name": "b", "age": 40, "tags": ["y"]}
+            ],
+            "other": {"large": [1, 2, 3]}
+        });
+        let cases = vec![
+            ("people[?age > `30`].name", "[\"b\"]"),
+            ("people[*].tags[0]", "[\"x\",\"y\"]"),
+            ("people[-1].name", "\"b\""),
+            ("people[::-1].age", "[40,20]"),
+            ("people[].name | join(',', @)", "\"a,b\""),
+            ("length(people[*].name)", "2"),
+            ("let $n = 'a' in people[?name == $n].age", "[20]"),
+            ("people[?age == $.people[1].age].name", "[\"b\"]"),
+            ("other.large[1] + `1`", "3"),
+        ];
+        for (expression, expected) in cases {
+            let expected = Rcvar::new(Variable::from_json(expected).unwrap());
+            assert_eq!(
+                expected,
+                search(expression, &data).unwrap(),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn copies_variables_and_root_for_exprefs() {
+        let data = json!({"min": 2, "items": [{"v": 3}, {"v": 1}, {"v": 2}]});
+        let expr = compile("let $k = 'v' in sort_by(items[?v >= $.min], &v)[*].v").unwrap();
+        assert_eq!(
+            Variable::from_json("[2,3]").unwrap(),
+            *expr.search_borrowed(&data).unwrap()
+        );
+        let expr = compile("let $min = min in min_by(items, &abs(v - $min)).v").unwrap();
+        assert_eq!(
+            Variable::from_json("2").unwrap(),
+            *expr.search_borrowed(&data).unwrap()
+        );
+        let expr = compile("map(&[v, $.min], items)[0]").unwrap();
+        assert_eq!(
+            Variable::from_json("[3,2]").unwrap(),
+            *expr.search_borrowed(&data).unwrap()
+        );
+    }
+
+    #[test]
+    fn returns_errors() {
+        let err = search("[$x]", &json!({})).unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::UndefinedVariable("x".to_owned())),
+            err.reason
+        );
+        let err = search("foo[::0]", &json!({})).unwrap_err();
+        assert_eq!(ErrorReason::Runtime(RuntimeError::InvalidSlice), err.reason);
+        let err = search("`1` + foo", &json!({"foo": "bar"})).unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::InvalidType {
+                expected: "number".to_owned(),
+                actual: "string".to_owned(),
+                position: 1,
+            }),
+            err.reason
+        );
+    }
+}
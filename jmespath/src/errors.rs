@@ -3,6 +3,7 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::functions::Arity;
 use crate::Context;
 
 /// JMESPath error.
@@ -68,7 +69,7 @@ impl From<serde_json::Error> for JmespathError {
 
 fn inject_carat(column: usize, buff: &mut String) {
     buff.push_str(&(0..column).map(|_| ' ').collect::<String>());
-    buff.push_str(&"^\n");
+    buff.push_str("^\n");
 }
 
 impl fmt::Display for JmespathError {
@@ -125,19 +126,21 @@ pub enum RuntimeError {
     /// Encountered when too many arguments are provided to a function.
     TooManyArguments {
         /// Expeced number of arguments.
-        expected: usize,
+        expected: Arity,
         /// Provided number of arguments.
         actual: usize,
     },
     /// Encountered when too few arguments are provided to a function.
     NotEnoughArguments {
         /// Expeced number of arguments.
-        expected: usize,
+        expected: Arity,
         /// Provided number of arguments.
         actual: usize,
     },
     /// Encountered when an unknown function is called.
     UnknownFunction(String),
+    /// Encountered when a variable is referenced that is not in scope.
+    UndefinedVariable(String),
     /// Encountered when a type of variable given to a function is invalid.
     InvalidType {
         /// Expected type.
@@ -147,6 +150,13 @@ pub enum RuntimeError {
         /// Argument position when calling the function.
         position: usize,
     },
+    /// Encountered when a function argument has a valid type but an invalid value.
+    InvalidValue {
+        /// Argument position when calling the function.
+        position: usize,
+        /// Description of why the value is invalid.
+        reason: String,
+    },
     /// Encountered when an expression reference returns an invalid type.
     InvalidReturnType {
         /// Expected return type.
@@ -158,6 +168,42 @@ pub enum RuntimeError {
         /// Which invocation iteration of the expression reference failed.
         invocation: usize,
     },
+    /// Encountered when evaluating an expression takes more steps than
+    /// allowed by the `Limits` of the expression.
+    StepLimitExceeded {
+        /// Maximum number of steps.
+        limit: usize,
+    },
+    /// Encountered when nodes are nested more deeply than allowed by the
+    /// `Limits` of the expression.
+    DepthLimitExceeded {
+        /// Maximum depth of nested nodes.
+        limit: usize,
+    },
+    /// Encountered when an array or object is produced with more elements
+    /// than allowed by the `Limits` of the expression.
+    SizeLimitExceeded {
+        /// Maximum number of elements.
+        limit: usize,
+        /// Number of elements of the produced value.
+        size: usize,
+    },
+    /// Encountered when a string is produced that is longer than allowed by
+    /// the `Limits` of the expression.
+    StringLengthLimitExceeded {
+        /// Maximum length in bytes.
+        limit: usize,
+        /// Length in bytes of the produced string.
+        length: usize,
+    },
+    /// Encountered when the `CancellationToken` of a search is cancelled.
+    Cancelled,
+    /// Encountered when a search is still being evaluated at its deadline.
+    Timeout,
+    /// Encountered when searching for the paths of values using an
+    /// expression that can produce values that are not in the searched
+    /// document (e.g., a function call or a multi-select).
+    UnsupportedPathExpression,
 }
 
 impl fmt::Display for RuntimeError {
@@ -165,6 +211,7 @@ impl fmt::Display for RuntimeError {
         use self::RuntimeError::*;
         match *self {
             UnknownFunction(ref function) => write!(fmt, "Call to undefined function {}", function),
+            UndefinedVariable(ref name) => write!(fmt, "Reference to undefined variable ${}", name),
             TooManyArguments {
                 ref expected,
                 ref actual,
@@ -191,6 +238,21 @@ impl fmt::Display for RuntimeError {
                 position, expected, actual
             ),
             InvalidSlice => write!(fmt, "Invalid slice"),
+            Cancelled => write!(fmt, "Evaluation was cancelled"),
+            Timeout => write!(fmt, "Evaluation exceeded its deadline"),
+            UnsupportedPathExpression => write!(
+                fmt,
+                "Paths can only be found using fields, indexes, slices, wildcards, \
+                 flattens, and filters"
+            ),
+            InvalidValue {
+                ref position,
+                ref reason,
+            } => write!(
+                fmt,
+                "Argument {} has an invalid value: {}",
+                position, reason
+            ),
             InvalidReturnType {
                 ref expected,
                 ref actual,
@@ -201,6 +263,30 @@ impl fmt::Display for RuntimeError {
                 "Argument {} must return {} but invocation {} returned {}",
                 position, expected, invocation, actual
             ),
+            StepLimitExceeded { ref limit } => {
+                write!(fmt, "Evaluation exceeded the limit of {} steps", limit)
+            }
+            DepthLimitExceeded { ref limit } => write!(
+                fmt,
+                "Evaluation exceeded the limit of {} nested nodes",
+                limit
+            ),
+            SizeLimitExceeded {
+                ref limit,
+                ref size,
+            } => write!(
+                fmt,
+                "Produced a value with {} elements, exceeding the limit of {}",
+                size, limit
+            ),
+            StringLengthLimitExceeded {
+                ref limit,
+                ref length,
+            } => write!(
+                fmt,
+                "Produced a string of {} bytes, exceeding the limit of {}",
+                length, limit
+            ),
         }
     }
 }
@@ -212,7 +298,7 @@ mod test {
     #[test]
     fn coordinates_can_be_created_from_string_with_new_lines() {
         let expr = "foo\n..bar";
-        let err = JmespathError::new(&expr, 5, ErrorReason::Parse("Test".to_owned()));
+        let err = JmespathError::new(expr, 5, ErrorReason::Parse("Test".to_owned()));
         assert_eq!(1, err.line);
         assert_eq!(1, err.column);
         assert_eq!(5, err.offset);
@@ -225,7 +311,7 @@ mod test {
     #[test]
     fn coordinates_can_be_created_from_string_with_new_lines_pointing_to_non_last() {
         let expr = "foo\n..bar\nbaz";
-        let err = JmespathError::new(&expr, 5, ErrorReason::Parse("Test".to_owned()));
+        let err = JmespathError::new(expr, 5, ErrorReason::Parse("Test".to_owned()));
         assert_eq!(1, err.line);
         assert_eq!(1, err.column);
         assert_eq!(5, err.offset);
@@ -238,7 +324,7 @@ mod test {
     #[test]
     fn coordinates_can_be_created_from_string_with_no_new_lines() {
         let expr = "foo..bar";
-        let err = JmespathError::new(&expr, 4, ErrorReason::Parse("Test".to_owned()));
+        let err = JmespathError::new(expr, 4, ErrorReason::Parse("Test".to_owned()));
         assert_eq!(0, err.line);
         assert_eq!(4, err.column);
         assert_eq!(4, err.offset);
@@ -276,25 +362,84 @@ mod test {
         );
     }
 
+    #[test]
+    fn displays_undefined_variable_error() {
+        let error = RuntimeError::UndefinedVariable("foo".to_owned());
+        assert_eq!("Reference to undefined variable $foo", error.to_string());
+    }
+
     #[test]
     fn displays_invalid_slice() {
         let error = RuntimeError::InvalidSlice;
         assert_eq!("Invalid slice", error.to_string());
     }
 
+    #[test]
+    fn displays_interruption_errors() {
+        assert_eq!(
+            "Evaluation was cancelled",
+            RuntimeError::Cancelled.to_string()
+        );
+        assert_eq!(
+            "Evaluation exceeded its deadline",
+            RuntimeError::Timeout.to_string()
+        );
+    }
+
+    #[test]
+    fn displays_unsupported_path_expression_error() {
+        assert_eq!(
+            "Paths can only be found using fields, indexes, slices, wildcards, \
+             flattens, and filters",
+            RuntimeError::UnsupportedPathExpression.to_string()
+        );
+    }
+
+    #[test]
+    fn displays_invalid_value_error() {
+        let error = RuntimeError::InvalidValue {
+            position: 1,
+            reason: "bad pattern".to_owned(),
+        };
+        assert_eq!(
+            "Argument 1 has an invalid value: bad pattern",
+            error.to_string()
+        );
+    }
+
     #[test]
     fn displays_too_many_arguments_error() {
         let error = RuntimeError::TooManyArguments {
-            expected: 1,
+            expected: Arity::Exactly(1),
             actual: 2,
         };
         assert_eq!("Too many arguments: expected 1, found 2", error.to_string());
     }
 
+    #[test]
+    fn displays_arity_ranges() {
+        let error = RuntimeError::TooManyArguments {
+            expected: Arity::Range(1, 3),
+            actual: 4,
+        };
+        assert_eq!(
+            "Too many arguments: expected 1 to 3, found 4",
+            error.to_string()
+        );
+        let error = RuntimeError::NotEnoughArguments {
+            expected: Arity::AtLeast(2),
+            actual: 1,
+        };
+        assert_eq!(
+            "Not enough arguments: expected at least 2, found 1",
+            error.to_string()
+        );
+    }
+
     #[test]
     fn displays_not_enough_arguments_error() {
         let error = RuntimeError::NotEnoughArguments {
-            expected: 2,
+            expected: Arity::Exactly(2),
             actual: 1,
         };
         assert_eq!(
@@ -316,4 +461,31 @@ mod test {
             error.to_string()
         );
     }
+
+    #[test]
+    fn displays_limit_errors() {
+        let error = RuntimeError::StepLimitExceeded { limit: 10 };
+        assert_eq!(
+            "Evaluation exceeded the limit of 10 steps",
+            error.to_string()
+        );
+        let error = RuntimeError::DepthLimitExceeded { limit: 5 };
+        assert_eq!(
+            "Evaluation exceeded the limit of 5 nested nodes",
+            error.to_string()
+        );
+        let error = RuntimeError::SizeLimitExceeded { limit: 2, size: 3 };
+        assert_eq!(
+            "Produced a value with 3 elements, exceeding the limit of 2",
+            error.to_string()
+        );
+        let error = RuntimeError::StringLengthLimitExceeded {
+            limit: 4,
+            length: 6,
+        };
+        assert_eq!(
+            "Produced a string of 6 bytes, exceeding the limit of 4",
+            error.to_string()
+        );
+    }
 }
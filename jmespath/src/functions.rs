@@ -1,18 +1,47 @@
 //! JMESPath functions.
 
+use std::borrow::Cow;
 use std::cmp::{max, min};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fmt;
+use std::marker::PhantomData;
+#[cfg(feature = "regex")]
+use std::sync::Mutex;
 
+use crate::ast::ArithmeticOperator;
 use crate::interpreter::{interpret, SearchResult};
+use crate::number::Numeric;
 use crate::variable::{JmespathType, Variable};
-use crate::{Context, ErrorReason, JmespathError, Rcvar, RuntimeError};
-use serde_json::Number;
+use crate::{Context, ErrorReason, JmespathError, Map, Rcvar, RuntimeError};
+#[cfg(feature = "regex")]
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{Number, Value};
 
 /// Represents a JMESPath function.
 pub trait Function: Sync {
     /// Evaluates the function against an in-memory variable.
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult;
+
+    /// Returns true if the function always returns the same result for the
+    /// same arguments and has no side effects.
+    ///
+    /// Calls to pure functions with literal arguments are evaluated when an
+    /// expression is compiled by a Runtime with optimizations enabled.
+    fn is_pure(&self) -> bool {
+        false
+    }
+
+    /// Returns the signature used to validate the arguments of the function.
+    ///
+    /// Signatures are used to check calls to the function when analyzing
+    /// expressions with `jmespath::analysis`. Functions without a signature
+    /// accept any arguments and may return any value.
+    fn signature(&self) -> Option<&Signature> {
+        None
+    }
 }
 
 /// Function argument types used when validating.
@@ -97,20 +126,20 @@ macro_rules! arg {
     ($($x:ident) | *) => (ArgumentType::Union(vec![$(arg!($x)), *]));
 }
 
+/// Boxed closure invoked by a `CustomFunction`.
+pub type CustomFunctionFn = Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync>;
+
 /// Custom function that allows the creation of runtime functions with signature validation.
 pub struct CustomFunction {
     /// Signature used to validate the function.
     signature: Signature,
     /// Function to invoke after validating the signature.
-    f: Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync>,
+    f: CustomFunctionFn,
 }
 
 impl CustomFunction {
     /// Creates a new custom function.
-    pub fn new(
-        fn_signature: Signature,
-        f: Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync>,
-    ) -> CustomFunction {
+    pub fn new(fn_signature: Signature, f: CustomFunctionFn) -> CustomFunction {
         CustomFunction {
             signature: fn_signature,
             f,
@@ -121,7 +150,197 @@ impl CustomFunction {
 impl Function for CustomFunction {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        (self.f)(args, ctx)
+        let args = self.signature.fill_defaults(args);
+        (self.f)(&args, ctx)
+    }
+
+    fn signature(&self) -> Option<&Signature> {
+        Some(&self.signature)
+    }
+}
+
+/// Rust types that can be used as arguments of a `TypedFunction`.
+///
+/// Arguments are converted from `Variable` using serde, and the
+/// `ArgumentType` is used to build the signature of the function.
+pub trait TypedArgument: DeserializeOwned {
+    /// Returns the type used to validate the argument.
+    fn argument_type() -> ArgumentType;
+
+    /// Returns true if the argument may be omitted. Optional arguments
+    /// must follow all required arguments.
+    fn is_optional() -> bool {
+        false
+    }
+}
+
+macro_rules! typed_argument {
+    ($argument_type:expr, $($t:ty),*) => {
+        $(impl TypedArgument for $t {
+            fn argument_type() -> ArgumentType {
+                $argument_type
+            }
+        })*
+    };
+}
+
+typed_argument!(ArgumentType::String, String);
+typed_argument!(ArgumentType::Bool, bool);
+typed_argument!(
+    ArgumentType::Number,
+    f64,
+    f32,
+    i64,
+    i32,
+    i16,
+    i8,
+    isize,
+    u64,
+    u32,
+    u16,
+    u8,
+    usize
+);
+typed_argument!(ArgumentType::Any, Variable, Rcvar, Value);
+
+impl<T: TypedArgument> TypedArgument for Vec<T> {
+    fn argument_type() -> ArgumentType {
+        match T::argument_type() {
+            ArgumentType::Any => ArgumentType::Array,
+            t => ArgumentType::TypedArray(Box::new(t)),
+        }
+    }
+}
+
+impl<T: TypedArgument> TypedArgument for BTreeMap<String, T> {
+    fn argument_type() -> ArgumentType {
+        ArgumentType::Object
+    }
+}
+
+impl<T: TypedArgument> TypedArgument for HashMap<String, T> {
+    fn argument_type() -> ArgumentType {
+        ArgumentType::Object
+    }
+}
+
+/// Optional arguments receive `None` when omitted or given null.
+impl<T: TypedArgument> TypedArgument for Option<T> {
+    fn argument_type() -> ArgumentType {
+        ArgumentType::Union(vec![T::argument_type(), ArgumentType::Null])
+    }
+
+    fn is_optional() -> bool {
+        true
+    }
+}
+
+/// Rust functions that can be registered using `Runtime::register_typed`.
+///
+/// Implemented for functions and closures of up to six arguments where
+/// each argument implements `TypedArgument` and the return value
+/// implements `serde::Serialize`.
+pub trait TypedFunction<Args>: Sync + 'static {
+    /// Creates a signature from the argument types of the function.
+    fn signature(&self) -> Signature;
+
+    /// Converts the validated arguments and invokes the function.
+    fn invoke(&self, args: &[Rcvar], ctx: &Context<'_>) -> SearchResult;
+}
+
+/// Builds a signature from the argument types of a typed function.
+fn typed_signature(arguments: &[(ArgumentType, bool)]) -> Signature {
+    let mut signature = Signature::new(vec![], None);
+    for (argument_type, is_optional) in arguments {
+        if *is_optional {
+            signature = signature.with_optional(vec![argument_type.clone()]);
+        } else if signature.optional.is_empty() {
+            signature.inputs.push(argument_type.clone());
+        } else {
+            panic!("Required function arguments cannot follow optional arguments");
+        }
+    }
+    signature
+}
+
+/// Converts the argument at the given position, using null when omitted.
+fn typed_argument<T: TypedArgument>(
+    args: &[Rcvar],
+    position: usize,
+    ctx: &Context<'_>,
+) -> Result<T, JmespathError> {
+    let value = args
+        .get(position)
+        .map(|arg| (**arg).clone())
+        .unwrap_or(Variable::Null);
+    T::deserialize(value).map_err(|e| invalid_value(ctx, position, &e.to_string()))
+}
+
+macro_rules! typed_function {
+    ($($arg:ident),*) => {
+        impl<Func, Ret, $($arg),*> TypedFunction<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + Sync + 'static,
+            Ret: Serialize,
+            $($arg: TypedArgument,)*
+        {
+            fn signature(&self) -> Signature {
+                typed_signature(&[$(($arg::argument_type(), $arg::is_optional())),*])
+            }
+
+            #[allow(non_snake_case, unused_assignments, unused_mut, unused_variables)]
+            fn invoke(&self, args: &[Rcvar], ctx: &Context<'_>) -> SearchResult {
+                let mut position = 0;
+                $(
+                    let $arg = typed_argument::<$arg>(args, position, ctx)?;
+                    position += 1;
+                )*
+                Variable::from_serializable((self)($($arg),*)).map(Rcvar::new)
+            }
+        }
+    };
+}
+
+typed_function!();
+typed_function!(A);
+typed_function!(A, B);
+typed_function!(A, B, C);
+typed_function!(A, B, C, D);
+typed_function!(A, B, C, D, E);
+typed_function!(A, B, C, D, E, F);
+
+/// Function that validates and converts arguments for a `TypedFunction`.
+pub struct TypedFn<F, Args> {
+    signature: Signature,
+    f: F,
+    args: PhantomData<fn(Args)>,
+}
+
+impl<F, Args> TypedFn<F, Args>
+where
+    F: TypedFunction<Args>,
+{
+    /// Creates a new typed function.
+    pub fn new(f: F) -> TypedFn<F, Args> {
+        TypedFn {
+            signature: f.signature(),
+            f,
+            args: PhantomData,
+        }
+    }
+}
+
+impl<F, Args> Function for TypedFn<F, Args>
+where
+    F: TypedFunction<Args>,
+{
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        self.f.invoke(args, ctx)
+    }
+
+    fn signature(&self) -> Option<&Signature> {
+        Some(&self.signature)
     }
 }
 
@@ -139,60 +358,221 @@ where
     }
 }
 
+/// Number of arguments accepted by a function.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Arity {
+    /// Accepts exactly this many arguments.
+    Exactly(usize),
+    /// Accepts an inclusive range of arguments.
+    Range(usize, usize),
+    /// Accepts this many arguments or more.
+    AtLeast(usize),
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            Arity::Exactly(n) => write!(fmt, "{}", n),
+            Arity::Range(min, max) => write!(fmt, "{} to {}", min, max),
+            Arity::AtLeast(n) => write!(fmt, "at least {}", n),
+        }
+    }
+}
+
+/// Optional argument that may follow the required inputs of a signature.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OptionalArgument {
+    /// Type the argument must match when it is provided.
+    pub kind: ArgumentType,
+    default: Option<DefaultValue>,
+}
+
+impl OptionalArgument {
+    /// Returns the value provided to the function when the argument is
+    /// omitted, if the argument has a default.
+    pub fn default(&self) -> Option<Rcvar> {
+        self.default.as_ref().map(DefaultValue::get)
+    }
+}
+
+/// Default value of an optional argument, which is converted and validated
+/// when the signature is created.
+///
+/// Signatures are shared by every thread that uses a `Runtime`, and `Rcvar`
+/// can only be shared between threads with the `sync` feature. Without it,
+/// the validated JSON value is kept and wrapped in a new `Rcvar` when used.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct DefaultValue {
+    #[cfg(feature = "sync")]
+    value: Rcvar,
+    #[cfg(not(feature = "sync"))]
+    value: Value,
+}
+
+impl DefaultValue {
+    fn new(kind: &ArgumentType, default: Value) -> DefaultValue {
+        let value = Variable::try_from(&default)
+            .map(Rcvar::new)
+            .expect("Default values must be valid JMESPath values");
+        if !kind.is_valid(&value) {
+            panic!(
+                "Default value {} does not match the argument type {}",
+                default, kind
+            );
+        }
+        #[cfg(feature = "sync")]
+        let default = value;
+        DefaultValue { value: default }
+    }
+
+    #[cfg(feature = "sync")]
+    fn get(&self) -> Rcvar {
+        self.value.clone()
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn get(&self) -> Rcvar {
+        Variable::try_from(&self.value)
+            .map(Rcvar::new)
+            .expect("default value was validated")
+    }
+}
+
 /// Represents a function's signature.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Signature {
     pub inputs: Vec<ArgumentType>,
+    /// Trailing arguments that may be omitted when calling the function.
+    pub optional: Vec<OptionalArgument>,
     pub variadic: Option<ArgumentType>,
+    /// Type of the value returned by the function.
+    pub output: ArgumentType,
 }
 
 impl Signature {
     /// Creates a new Signature struct.
     pub fn new(inputs: Vec<ArgumentType>, variadic: Option<ArgumentType>) -> Signature {
-        Signature { inputs, variadic }
+        Signature {
+            inputs,
+            optional: vec![],
+            variadic,
+            output: ArgumentType::Any,
+        }
+    }
+
+    /// Sets the type of the value returned by the function, which is `Any`
+    /// by default.
+    pub fn with_output(mut self, output: ArgumentType) -> Signature {
+        self.output = output;
+        self
+    }
+
+    /// Adds optional arguments without defaults that may follow the
+    /// required inputs.
+    pub fn with_optional(mut self, optional: Vec<ArgumentType>) -> Signature {
+        self.optional
+            .extend(optional.into_iter().map(|kind| OptionalArgument {
+                kind,
+                default: None,
+            }));
+        self
+    }
+
+    /// Adds an optional argument that uses `default` when it is omitted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default` does not match `kind`.
+    ///
+    /// ```
+    /// use jmespath::functions::{ArgumentType, Signature};
+    ///
+    /// // pad(string, number, string?) with a default padding of " "
+    /// let signature = Signature::new(vec![ArgumentType::String, ArgumentType::Number], None)
+    ///     .with_default(ArgumentType::String, " ".into());
+    /// assert_eq!("2 to 3", signature.arity().to_string());
+    /// ```
+    pub fn with_default(mut self, kind: ArgumentType, default: Value) -> Signature {
+        let default = DefaultValue::new(&kind, default);
+        self.optional.push(OptionalArgument {
+            kind,
+            default: Some(default),
+        });
+        self
+    }
+
+    /// Returns the number of arguments accepted by the signature.
+    pub fn arity(&self) -> Arity {
+        let min = self.inputs.len();
+        if self.variadic.is_some() {
+            Arity::AtLeast(min)
+        } else if self.optional.is_empty() {
+            Arity::Exactly(min)
+        } else {
+            Arity::Range(min, min + self.optional.len())
+        }
     }
 
     /// Validates the arity of a function. If the arity is invalid, a runtime
     /// error is returned with the relative position of the error and the
     /// expression that was being executed.
     pub fn validate_arity(&self, actual: usize, ctx: &Context<'_>) -> Result<(), JmespathError> {
-        let expected = self.inputs.len();
-        if self.variadic.is_some() {
-            if actual >= expected {
-                Ok(())
-            } else {
-                let reason =
-                    ErrorReason::Runtime(RuntimeError::NotEnoughArguments { expected, actual });
-                Err(JmespathError::from_ctx(ctx, reason))
-            }
-        } else if actual == expected {
-            Ok(())
-        } else if actual < expected {
-            let reason =
-                ErrorReason::Runtime(RuntimeError::NotEnoughArguments { expected, actual });
+        let min_expected = self.inputs.len();
+        let max_expected = min_expected + self.optional.len();
+        if actual < min_expected {
+            let reason = ErrorReason::Runtime(RuntimeError::NotEnoughArguments {
+                expected: self.arity(),
+                actual,
+            });
             Err(JmespathError::from_ctx(ctx, reason))
-        } else {
-            let reason = ErrorReason::Runtime(RuntimeError::TooManyArguments { expected, actual });
+        } else if actual > max_expected && self.variadic.is_none() {
+            let reason = ErrorReason::Runtime(RuntimeError::TooManyArguments {
+                expected: self.arity(),
+                actual,
+            });
             Err(JmespathError::from_ctx(ctx, reason))
+        } else {
+            Ok(())
         }
     }
 
     /// Validates the provided function arguments against the signature.
     pub fn validate(&self, args: &[Rcvar], ctx: &Context<'_>) -> Result<(), JmespathError> {
         self.validate_arity(args.len(), ctx)?;
-        if let Some(ref variadic) = self.variadic {
-            for (k, v) in args.iter().enumerate() {
-                let validator = self.inputs.get(k).unwrap_or(variadic);
-                self.validate_arg(ctx, k, v, validator)?;
-            }
-        } else {
-            for (k, v) in args.iter().enumerate() {
-                self.validate_arg(ctx, k, v, &self.inputs[k])?;
-            }
+        for (k, v) in args.iter().enumerate() {
+            let validator = self
+                .inputs
+                .iter()
+                .chain(self.optional.iter().map(|o| &o.kind))
+                .nth(k)
+                .or(self.variadic.as_ref())
+                .expect("arity was validated");
+            self.validate_arg(ctx, k, v, validator)?;
         }
         Ok(())
     }
 
+    /// Appends the defaults of any omitted optional arguments.
+    ///
+    /// Defaults are filled in order and stop at the first omitted argument
+    /// that has no default. The arguments are borrowed as-is when there is
+    /// nothing to fill.
+    pub fn fill_defaults<'a>(&self, args: &'a [Rcvar]) -> Cow<'a, [Rcvar]> {
+        let provided = args.len().saturating_sub(self.inputs.len());
+        let mut defaults = self
+            .optional
+            .iter()
+            .skip(provided)
+            .map_while(OptionalArgument::default)
+            .peekable();
+        if defaults.peek().is_none() {
+            return Cow::Borrowed(args);
+        }
+        let mut filled = args.to_vec();
+        filled.extend(defaults);
+        Cow::Owned(filled)
+    }
+
     fn validate_arg(
         &self,
         ctx: &Context<'_>,
@@ -213,9 +593,54 @@ impl Signature {
     }
 }
 
+/// A builtin function defined using `defn!` or `regex_defn!`.
+///
+/// The macros implement this trait for each builtin, and builtins are
+/// registered wrapped in a `BuiltinFunction`, which reports the signature of
+/// the builtin. Every builtin returns the same result for the same
+/// arguments, so they are all reported as pure.
+pub(crate) trait Builtin: Function {
+    /// Returns the signature used to validate the arguments of the builtin.
+    fn builtin_signature(&self) -> &Signature;
+}
+
+/// Implements `Builtin` for a builtin that has a `signature` field.
+macro_rules! builtin_function {
+    ($name:ident) => {
+        impl Builtin for $name {
+            fn builtin_signature(&self) -> &Signature {
+                &self.signature
+            }
+        }
+    };
+}
+
+/// A `Function` that evaluates a builtin and reports its signature.
+pub(crate) struct BuiltinFunction<F>(pub(crate) F);
+
+impl<F: Builtin> Function for BuiltinFunction<F> {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.0.evaluate(args, ctx)
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn signature(&self) -> Option<&Signature> {
+        Some(self.0.builtin_signature())
+    }
+}
+
 /// Macro to more easily and quickly define a function and signature.
 macro_rules! defn {
-    ($name:ident, $args:expr, $variadic:expr) => {
+    ($name:ident -> $output:expr, $args:expr, $variadic:expr) => {
+        defn!($name -> $output, $args, vec![], $variadic);
+    };
+    ($name:ident -> $output:expr, $args:expr, $optional:expr, $variadic:expr) => {
+        defn!($name -> $output, Signature::new($args, $variadic).with_optional($optional));
+    };
+    ($name:ident -> $output:expr, $signature:expr) => {
         pub struct $name {
             signature: Signature,
         }
@@ -229,10 +654,87 @@ macro_rules! defn {
         impl $name {
             pub fn new() -> $name {
                 $name {
-                    signature: Signature::new($args, $variadic),
+                    signature: $signature.with_output($output),
                 }
             }
         }
+
+        builtin_function!($name);
+    };
+}
+
+/// Cache of compiled regular expressions keyed by their pattern.
+///
+/// Patterns are typically string literals, so caching them means an
+/// expression like `[?matches(name, '^prod-')]` compiles its pattern once
+/// rather than once per element.
+#[cfg(feature = "regex")]
+#[derive(Default)]
+struct RegexCache {
+    patterns: Mutex<HashMap<String, Regex>>,
+}
+
+#[cfg(feature = "regex")]
+impl RegexCache {
+    /// Maximum number of patterns to retain before the cache is cleared.
+    const CAPACITY: usize = 256;
+
+    /// Gets or compiles the pattern found at the given argument position.
+    fn get(
+        &self,
+        args: &[Rcvar],
+        position: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Regex, JmespathError> {
+        let pattern = args[position].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse(format!("Expected args[{}] to be a string", position)),
+            )
+        })?;
+        let mut patterns = self
+            .patterns
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(regex) = patterns.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex =
+            Regex::new(pattern).map_err(|e| invalid_value(ctx, position, &e.to_string()))?;
+        if patterns.len() >= Self::CAPACITY {
+            patterns.clear();
+        }
+        patterns.insert(pattern.to_owned(), regex.clone());
+        Ok(regex)
+    }
+}
+
+/// Macro to define a regex function, signature, and pattern cache.
+#[cfg(feature = "regex")]
+macro_rules! regex_defn {
+    ($name:ident -> $output:expr, $args:expr) => {
+        pub struct $name {
+            signature: Signature,
+            cache: RegexCache,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $name {
+            pub fn new() -> $name {
+                $name {
+                    signature: Signature::new($args, None).with_output($output),
+                    cache: RegexCache::default(),
+                }
+            }
+        }
+
+        builtin_function!($name);
     };
 }
 
@@ -282,7 +784,7 @@ macro_rules! min_and_max_by {
                         expected: format!("expression->{}", entered_type),
                         actual: mapped.get_type().to_string(),
                         position: 1,
-                        invocation: invocation,
+                        invocation,
                     }),
                 ));
             }
@@ -316,38 +818,194 @@ macro_rules! min_and_max {
     }};
 }
 
-defn!(AbsFn, vec![arg!(number)], None);
+/// Gets a number argument that has been validated by the signature.
+fn number_arg(args: &[Rcvar], position: usize) -> Result<&Number, JmespathError> {
+    match *args[position] {
+        Variable::Number(ref n) => Ok(n),
+        _ => Err(JmespathError::new(
+            "",
+            0,
+            ErrorReason::Parse(format!("Expected args[{}] to be a number", position)),
+        )),
+    }
+}
+
+/// Converts the result of a numeric function to a number.
+fn numeric_result(n: Numeric) -> SearchResult {
+    let n = n.into_number().ok_or_else(|| {
+        JmespathError::new(
+            "",
+            0,
+            ErrorReason::Parse("Expected to be a valid number".to_owned()),
+        )
+    })?;
+    Ok(Rcvar::new(Variable::Number(n)))
+}
+
+/// Creates an invalid value error for the argument at the given position.
+fn invalid_value(ctx: &Context<'_>, position: usize, reason: &str) -> JmespathError {
+    JmespathError::from_ctx(
+        ctx,
+        ErrorReason::Runtime(RuntimeError::InvalidValue {
+            position,
+            reason: reason.to_owned(),
+        }),
+    )
+}
+
+/// Gets an optional integer argument, failing if the value is not a whole
+/// number that fits in an `i64`.
+fn optional_integer(
+    args: &[Rcvar],
+    position: usize,
+    ctx: &Context<'_>,
+) -> Result<Option<i64>, JmespathError> {
+    match args.get(position).and_then(|v| v.as_number()) {
+        None => Ok(None),
+        Some(n) if n.fract() == 0.0 && n.abs() <= i64::MAX as f64 => Ok(Some(n as i64)),
+        Some(_) => Err(invalid_value(ctx, position, "expected an integer")),
+    }
+}
+
+/// Gets an optional integer argument that must not be negative.
+fn optional_non_negative_integer(
+    args: &[Rcvar],
+    position: usize,
+    ctx: &Context<'_>,
+) -> Result<Option<usize>, JmespathError> {
+    match optional_integer(args, position, ctx)? {
+        Some(n) if n < 0 => Err(invalid_value(
+            ctx,
+            position,
+            "expected a non-negative integer",
+        )),
+        n => Ok(n.map(|n| n as usize)),
+    }
+}
+
+/// Macro used to implement find_first and find_last functions.
+///
+/// The start and optional end arguments restrict the search to a slice of
+/// the subject using the same rules as slice expressions.
+macro_rules! find {
+    ($ctx:expr, $args:expr, $position:ident) => {{
+        let subject = $args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let search = $args[1].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[1] to be a string".to_owned()),
+            )
+        })?;
+        let start = optional_integer(&$args, 2, $ctx)?.unwrap_or_default();
+        let end = optional_integer(&$args, 3, $ctx)?;
+        if search.is_empty() {
+            return Ok(Rcvar::new(Variable::Null));
+        }
+        let subject: Vec<char> = subject.chars().collect();
+        let search: Vec<char> = search.chars().collect();
+        let len = subject.len() as i64;
+        let adjust = |n: i64| {
+            let n = if n < 0 { n + len } else { n };
+            max(0, min(n, len)) as usize
+        };
+        let start = adjust(start);
+        let end = adjust(end.unwrap_or(len));
+        let found = if start < end {
+            subject[start..end]
+                .windows(search.len())
+                .$position(|w| *w == search[..])
+        } else {
+            None
+        };
+        match found {
+            Some(i) => Ok(Rcvar::new(Variable::Number(Number::from(start + i)))),
+            None => Ok(Rcvar::new(Variable::Null)),
+        }
+    }};
+}
+
+/// Macro used to implement pad_left and pad_right functions.
+macro_rules! pad {
+    ($ctx:expr, $args:expr, $left:expr) => {{
+        let subject = $args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let width = optional_non_negative_integer(&$args, 1, $ctx)?.unwrap_or(0);
+        let pad = $args[2].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[2] to be a string".to_owned()),
+            )
+        })?;
+        let mut chars = pad.chars();
+        let pad = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return Err(invalid_value($ctx, 2, "expected a single character")),
+        };
+        let len = subject.chars().count();
+        if len >= width {
+            return Ok($args[0].clone());
+        }
+        // Check the length before padding so that huge widths fail rather
+        // than exhausting memory.
+        let length = (width - len)
+            .checked_mul(pad.len_utf8())
+            .and_then(|n| n.checked_add(subject.len()))
+            .unwrap_or(usize::MAX);
+        $ctx.check_string_length(length, $ctx.offset)?;
+        let padding: String = std::iter::repeat(pad).take(width - len).collect();
+        let result = if $left {
+            padding + subject
+        } else {
+            subject.to_owned() + &padding
+        };
+        Ok(Rcvar::new(Variable::String(result)))
+    }};
+}
+
+/// Macro used to implement trim, trim_left, and trim_right functions.
+///
+/// Whitespace is trimmed unless a non-empty string of characters to trim
+/// is provided.
+macro_rules! trim {
+    ($args:expr, $trim:ident, $trim_matches:ident) => {{
+        let subject = $args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let result = match $args.get(1).and_then(|v| v.as_string()) {
+            Some(chars) if !chars.is_empty() => subject.$trim_matches(|c| chars.contains(c)),
+            _ => subject.$trim(),
+        };
+        Ok(Rcvar::new(Variable::String(result.to_owned())))
+    }};
+}
+
+defn!(AbsFn -> arg!(number), vec![arg!(number)], None);
 
 impl Function for AbsFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        match args[0].as_ref() {
-            Variable::Number(n) => Ok(Rcvar::new(Variable::Number(
-                Number::from_f64(
-                    n.as_f64()
-                        .ok_or_else(|| {
-                            JmespathError::new(
-                                "",
-                                0,
-                                ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                            )
-                        })?
-                        .abs(),
-                )
-                .ok_or_else(|| {
-                    JmespathError::new(
-                        "",
-                        0,
-                        ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                    )
-                })?,
-            ))),
-            _ => Ok(args[0].clone()),
-        }
+        numeric_result(Numeric::new(number_arg(args, 0)?).abs())
     }
 }
 
-defn!(AvgFn, vec![arg!(array_number)], None);
+defn!(AvgFn -> arg!(number | null), vec![arg!(array_number)], None);
 
 impl Function for AvgFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -359,56 +1017,38 @@ impl Function for AvgFn {
                 ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
             )
         })?;
-
-        let mut sum = 0.0;
-
-        for value in values {
-            sum += value.as_number().ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                )
-            })?;
+        // The average of an empty array is undefined.
+        if values.is_empty() {
+            return Ok(Rcvar::new(Variable::Null));
         }
-
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(sum / (values.len() as f64)).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                )
-            })?,
-        )))
+        let sum = Numeric::sum(values.iter().filter_map(|v| match **v {
+            Variable::Number(ref n) => Some(n),
+            _ => None,
+        }));
+        let count = Numeric::Integer(values.len() as i128);
+        numeric_result(
+            sum.arithmetic(&ArithmeticOperator::Divide, count)
+                .ok_or_else(|| {
+                    JmespathError::new(
+                        "",
+                        0,
+                        ErrorReason::Parse("Expected to be a valid number".to_owned()),
+                    )
+                })?,
+        )
     }
 }
 
-defn!(CeilFn, vec![arg!(number)], None);
+defn!(CeilFn -> arg!(number), vec![arg!(number)], None);
 
 impl Function for CeilFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let n = args[0].as_number().ok_or_else(|| {
-            JmespathError::new(
-                "",
-                0,
-                ErrorReason::Parse("Expected args[0] to be a number".to_owned()),
-            )
-        })?;
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(n.ceil()).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected n.ceil() to be a valid f64".to_owned()),
-                )
-            })?,
-        )))
+        numeric_result(Numeric::new(number_arg(args, 0)?).ceil())
     }
 }
 
-defn!(ContainsFn, vec![arg!(string | array), arg!(any)], None);
+defn!(ContainsFn -> arg!(bool), vec![arg!(string | array), arg!(any)], None);
 
 impl Function for ContainsFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -416,7 +1056,7 @@ impl Function for ContainsFn {
         let haystack = &args[0];
         let needle = &args[1];
         match **haystack {
-            Variable::Array(ref a) => Ok(Rcvar::new(Variable::Bool(a.contains(&needle)))),
+            Variable::Array(ref a) => Ok(Rcvar::new(Variable::Bool(a.contains(needle)))),
             Variable::String(ref subj) => match needle.as_string() {
                 None => Ok(Rcvar::new(Variable::Bool(false))),
                 Some(s) => Ok(Rcvar::new(Variable::Bool(subj.contains(s)))),
@@ -426,54 +1066,173 @@ impl Function for ContainsFn {
     }
 }
 
-defn!(EndsWithFn, vec![arg!(string), arg!(string)], None);
+defn!(EndsWithFn -> arg!(bool), vec![arg!(string), arg!(string)], None);
+
+impl Function for EndsWithFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a valid string".to_owned()),
+            )
+        })?;
+        let search = args[1].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[1] to be a valid string".to_owned()),
+            )
+        })?;
+        Ok(Rcvar::new(Variable::Bool(subject.ends_with(search))))
+    }
+}
+
+defn!(
+    FindFirstFn -> arg!(number | null),
+    Signature::new(vec![arg!(string), arg!(string)], None)
+        .with_default(arg!(number), 0.into())
+        .with_optional(vec![arg!(number)])
+);
+
+impl Function for FindFirstFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let args = self.signature.fill_defaults(args);
+        find!(ctx, args, position)
+    }
+}
+
+defn!(
+    FindLastFn -> arg!(number | null),
+    Signature::new(vec![arg!(string), arg!(string)], None)
+        .with_default(arg!(number), 0.into())
+        .with_optional(vec![arg!(number)])
+);
+
+impl Function for FindLastFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let args = self.signature.fill_defaults(args);
+        find!(ctx, args, rposition)
+    }
+}
+
+defn!(FloorFn -> arg!(number), vec![arg!(number)], None);
+
+impl Function for FloorFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        numeric_result(Numeric::new(number_arg(args, 0)?).floor())
+    }
+}
+
+defn!(FromItemsFn -> arg!(object), vec![arg!(array)], None);
+
+impl Function for FromItemsFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let items = args[0].as_array().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
+            )
+        })?;
+        let mut result = Map::new();
+        for item in items {
+            match item.as_array().map(|pair| pair.as_slice()) {
+                Some([key, value]) if key.is_string() => {
+                    result.insert(key.as_string().unwrap().clone(), value.clone());
+                }
+                _ => {
+                    let reason = ErrorReason::Runtime(RuntimeError::InvalidType {
+                        expected: "array[array[string, any]]".to_owned(),
+                        actual: format!("array[{}]", item.get_type()),
+                        position: 0,
+                    });
+                    return Err(JmespathError::from_ctx(ctx, reason));
+                }
+            }
+        }
+        Ok(Rcvar::new(Variable::Object(result)))
+    }
+}
+
+defn!(GroupByFn -> arg!(object), vec![arg!(array), arg!(expref)], None);
 
-impl Function for EndsWithFn {
+impl Function for GroupByFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let subject = args[0].as_string().ok_or_else(|| {
+        let vals = args[0].as_array().ok_or_else(|| {
             JmespathError::new(
                 "",
                 0,
-                ErrorReason::Parse("Expected args[0] to be a valid string".to_owned()),
+                ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
             )
         })?;
-        let search = args[1].as_string().ok_or_else(|| {
+        let ast = args[1].as_expref().ok_or_else(|| {
             JmespathError::new(
                 "",
                 0,
-                ErrorReason::Parse("Expected args[1] to be a valid string".to_owned()),
+                ErrorReason::Parse("Expected args[1] to be an expref".to_owned()),
             )
         })?;
-        Ok(Rcvar::new(Variable::Bool(subject.ends_with(search))))
+        let mut groups: Map<String, Vec<Rcvar>> = Map::new();
+        for (invocation, v) in vals.iter().enumerate() {
+            let key = interpret(v, ast, ctx)?;
+            match *key {
+                Variable::String(ref key) => groups.entry(key.clone()).or_default().push(v.clone()),
+                // Elements without a group key are omitted from the result.
+                Variable::Null => (),
+                _ => {
+                    return Err(JmespathError::from_ctx(
+                        ctx,
+                        ErrorReason::Runtime(RuntimeError::InvalidReturnType {
+                            expected: "expression->string".to_owned(),
+                            actual: key.get_type().to_string(),
+                            position: 1,
+                            invocation,
+                        }),
+                    ));
+                }
+            }
+        }
+        let result = groups
+            .into_iter()
+            .map(|(k, v)| (k, Rcvar::new(Variable::Array(v))))
+            .collect();
+        Ok(Rcvar::new(Variable::Object(result)))
     }
 }
 
-defn!(FloorFn, vec![arg!(number)], None);
+defn!(ItemsFn -> arg!(array), vec![arg!(object)], None);
 
-impl Function for FloorFn {
+impl Function for ItemsFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let n = args[0].as_number().ok_or_else(|| {
+        let object = args[0].as_object().ok_or_else(|| {
             JmespathError::new(
                 "",
                 0,
-                ErrorReason::Parse("Expected args[0] to be a valid number".to_owned()),
+                ErrorReason::Parse("Expected args[0] to be a valid Object".to_owned()),
             )
         })?;
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(n.floor()).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid number".to_owned()),
-                )
-            })?,
-        )))
+        let items = object
+            .iter()
+            .map(|(k, v)| {
+                Rcvar::new(Variable::Array(vec![
+                    Rcvar::new(Variable::String(k.clone())),
+                    v.clone(),
+                ]))
+            })
+            .collect();
+        Ok(Rcvar::new(Variable::Array(items)))
     }
 }
 
-defn!(JoinFn, vec![arg!(string), arg!(array_string)], None);
+defn!(JoinFn -> arg!(string), vec![arg!(string), arg!(array_string)], None);
 
 impl Function for JoinFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -504,12 +1263,12 @@ impl Function for JoinFn {
                 })
             })
             .collect::<Result<Vec<String>, JmespathError>>()?
-            .join(&glue);
+            .join(glue);
         Ok(Rcvar::new(Variable::String(result)))
     }
 }
 
-defn!(KeysFn, vec![arg!(object)], None);
+defn!(KeysFn -> arg!(array_string), vec![arg!(object)], None);
 
 impl Function for KeysFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -529,7 +1288,7 @@ impl Function for KeysFn {
     }
 }
 
-defn!(LengthFn, vec![arg!(array | object | string)], None);
+defn!(LengthFn -> arg!(number), vec![arg!(array | object | string)], None);
 
 impl Function for LengthFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -546,7 +1305,23 @@ impl Function for LengthFn {
     }
 }
 
-defn!(MapFn, vec![arg!(expref), arg!(array)], None);
+defn!(LowerFn -> arg!(string), vec![arg!(string)], None);
+
+impl Function for LowerFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        Ok(Rcvar::new(Variable::String(subject.to_lowercase())))
+    }
+}
+
+defn!(MapFn -> arg!(array), vec![arg!(expref), arg!(array)], None);
 
 impl Function for MapFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -567,13 +1342,32 @@ impl Function for MapFn {
         })?;
         let mut results = vec![];
         for value in values {
-            results.push(interpret(&value, &ast, ctx)?);
+            results.push(interpret(value, ast, ctx)?);
         }
         Ok(Rcvar::new(Variable::Array(results)))
     }
 }
 
-defn!(MaxFn, vec![arg!(array_string | array_number)], None);
+#[cfg(feature = "regex")]
+regex_defn!(MatchesFn -> arg!(bool), vec![arg!(string), arg!(string)]);
+
+#[cfg(feature = "regex")]
+impl Function for MatchesFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let regex = self.cache.get(args, 1, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        Ok(Rcvar::new(Variable::Bool(regex.is_match(subject))))
+    }
+}
+
+defn!(MaxFn -> arg!(number | string | null), vec![arg!(array_string | array_number)], None);
 
 impl Function for MaxFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -582,7 +1376,7 @@ impl Function for MaxFn {
     }
 }
 
-defn!(MinFn, vec![arg!(array_string | array_number)], None);
+defn!(MinFn -> arg!(number | string | null), vec![arg!(array_string | array_number)], None);
 
 impl Function for MinFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -591,7 +1385,7 @@ impl Function for MinFn {
     }
 }
 
-defn!(MaxByFn, vec![arg!(array), arg!(expref)], None);
+defn!(MaxByFn -> arg!(any), vec![arg!(array), arg!(expref)], None);
 
 impl Function for MaxByFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -600,7 +1394,7 @@ impl Function for MaxByFn {
     }
 }
 
-defn!(MinByFn, vec![arg!(array), arg!(expref)], None);
+defn!(MinByFn -> arg!(any), vec![arg!(array), arg!(expref)], None);
 
 impl Function for MinByFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -609,12 +1403,12 @@ impl Function for MinByFn {
     }
 }
 
-defn!(MergeFn, vec![arg!(object)], Some(arg!(object)));
+defn!(MergeFn -> arg!(object), vec![arg!(object)], Some(arg!(object)));
 
 impl Function for MergeFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let mut result = BTreeMap::new();
+        let mut result = Map::new();
         for arg in args {
             result.extend(
                 arg.as_object()
@@ -632,7 +1426,7 @@ impl Function for MergeFn {
     }
 }
 
-defn!(NotNullFn, vec![arg!(any)], Some(arg!(any)));
+defn!(NotNullFn -> arg!(any), vec![arg!(any)], Some(arg!(any)));
 
 impl Function for NotNullFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -646,7 +1440,157 @@ impl Function for NotNullFn {
     }
 }
 
-defn!(ReverseFn, vec![arg!(array | string)], None);
+defn!(
+    PadLeftFn -> arg!(string),
+    Signature::new(vec![arg!(string), arg!(number)], None).with_default(arg!(string), " ".into())
+);
+
+impl Function for PadLeftFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let args = self.signature.fill_defaults(args);
+        pad!(ctx, args, true)
+    }
+}
+
+defn!(
+    PadRightFn -> arg!(string),
+    Signature::new(vec![arg!(string), arg!(number)], None).with_default(arg!(string), " ".into())
+);
+
+impl Function for PadRightFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let args = self.signature.fill_defaults(args);
+        pad!(ctx, args, false)
+    }
+}
+
+#[cfg(feature = "regex")]
+regex_defn!(RegexExtractFn -> arg!(string | null), vec![arg!(string), arg!(string)]);
+
+/// Returns the first match of a pattern, or null when there is no match.
+///
+/// When the pattern contains a capture group, the text of the first group
+/// is returned instead of the entire match.
+#[cfg(feature = "regex")]
+impl Function for RegexExtractFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let regex = self.cache.get(args, 1, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let group = if regex.captures_len() > 1 { 1 } else { 0 };
+        match regex.captures(subject).and_then(|c| c.get(group)) {
+            Some(m) => Ok(Rcvar::new(Variable::String(m.as_str().to_owned()))),
+            None => Ok(Rcvar::new(Variable::Null)),
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+regex_defn!(
+    RegexReplaceFn -> arg!(string),
+    vec![arg!(string), arg!(string), arg!(string)]
+);
+
+/// Replaces every match of a pattern. The replacement may refer to capture
+/// groups using `$1` or `${name}`.
+#[cfg(feature = "regex")]
+impl Function for RegexReplaceFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let regex = self.cache.get(args, 1, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let replacement = args[2].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[2] to be a string".to_owned()),
+            )
+        })?;
+        Ok(Rcvar::new(Variable::String(
+            regex
+                .replace_all(subject, replacement.as_str())
+                .into_owned(),
+        )))
+    }
+}
+
+#[cfg(feature = "regex")]
+regex_defn!(RegexSplitFn -> arg!(array_string), vec![arg!(string), arg!(string)]);
+
+#[cfg(feature = "regex")]
+impl Function for RegexSplitFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let regex = self.cache.get(args, 1, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let parts = regex
+            .split(subject)
+            .map(|part| Rcvar::new(Variable::String(part.to_owned())))
+            .collect();
+        Ok(Rcvar::new(Variable::Array(parts)))
+    }
+}
+
+defn!(
+    ReplaceFn -> arg!(string),
+    vec![arg!(string), arg!(string), arg!(string)],
+    vec![arg!(number)],
+    None
+);
+
+impl Function for ReplaceFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let old = args[1].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[1] to be a string".to_owned()),
+            )
+        })?;
+        let new = args[2].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[2] to be a string".to_owned()),
+            )
+        })?;
+        let result = match optional_non_negative_integer(args, 3, ctx)? {
+            Some(count) => subject.replacen(old, new, count),
+            None => subject.replace(old, new),
+        };
+        Ok(Rcvar::new(Variable::String(result)))
+    }
+}
+
+defn!(ReverseFn -> arg!(array | string), vec![arg!(array | string)], None);
 
 impl Function for ReverseFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -682,7 +1626,7 @@ impl Function for ReverseFn {
     }
 }
 
-defn!(SortFn, vec![arg!(array_string | array_number)], None);
+defn!(SortFn -> arg!(array), vec![arg!(array_string | array_number)], None);
 
 impl Function for SortFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -702,7 +1646,7 @@ impl Function for SortFn {
     }
 }
 
-defn!(SortByFn, vec![arg!(array), arg!(expref)], None);
+defn!(SortByFn -> arg!(array), vec![arg!(array), arg!(expref)], None);
 
 impl Function for SortByFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -728,7 +1672,7 @@ impl Function for SortByFn {
             )
         })?;
         let mut mapped: Vec<(Rcvar, Rcvar)> = vec![];
-        let first_value = interpret(&vals[0], &ast, ctx)?;
+        let first_value = interpret(&vals[0], ast, ctx)?;
         let first_type = first_value.get_type();
         if first_type != JmespathType::String && first_type != JmespathType::Number {
             let reason = ErrorReason::Runtime(RuntimeError::InvalidReturnType {
@@ -741,7 +1685,7 @@ impl Function for SortByFn {
         }
         mapped.push((vals[0].clone(), first_value));
         for (invocation, v) in vals.iter().enumerate().skip(1) {
-            let mapped_value = interpret(v, &ast, ctx)?;
+            let mapped_value = interpret(v, ast, ctx)?;
             if mapped_value.get_type() != first_type {
                 return Err(JmespathError::from_ctx(
                     ctx,
@@ -761,7 +1705,60 @@ impl Function for SortByFn {
     }
 }
 
-defn!(StartsWithFn, vec![arg!(string), arg!(string)], None);
+defn!(
+    SplitFn -> arg!(array_string),
+    vec![arg!(string), arg!(string)],
+    vec![arg!(number)],
+    None
+);
+
+impl Function for SplitFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        let search = args[1].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[1] to be a string".to_owned()),
+            )
+        })?;
+        let count = optional_non_negative_integer(args, 2, ctx)?;
+        let parts: Vec<String> = if search.is_empty() {
+            // An empty separator splits the subject into characters, keeping
+            // the remainder intact once the split count is reached.
+            let count = count.unwrap_or(usize::MAX);
+            let mut parts: Vec<String> = subject.chars().take(count).map(String::from).collect();
+            let rest: String = subject.chars().skip(count).collect();
+            if !rest.is_empty() {
+                parts.push(rest);
+            }
+            parts
+        } else {
+            match count {
+                Some(count) => subject
+                    .splitn(count + 1, search)
+                    .map(String::from)
+                    .collect(),
+                None => subject.split(search).map(String::from).collect(),
+            }
+        };
+        Ok(Rcvar::new(Variable::Array(
+            parts
+                .into_iter()
+                .map(|part| Rcvar::new(Variable::String(part)))
+                .collect(),
+        )))
+    }
+}
+
+defn!(StartsWithFn -> arg!(bool), vec![arg!(string), arg!(string)], None);
 
 impl Function for StartsWithFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -784,35 +1781,26 @@ impl Function for StartsWithFn {
     }
 }
 
-defn!(SumFn, vec![arg!(array_number)], None);
+defn!(SumFn -> arg!(number), vec![arg!(array_number)], None);
 
 impl Function for SumFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let result = args[0]
-            .as_array()
-            .ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
-                )
-            })?
-            .iter()
-            .fold(0.0, |acc, item| acc + item.as_number().unwrap_or(0.0));
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(result).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid number".to_owned()),
-                )
-            })?,
-        )))
+        let values = args[0].as_array().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
+            )
+        })?;
+        numeric_result(Numeric::sum(values.iter().filter_map(|v| match **v {
+            Variable::Number(ref n) => Some(n),
+            _ => None,
+        })))
     }
 }
 
-defn!(ToArrayFn, vec![arg!(any)], None);
+defn!(ToArrayFn -> arg!(array), vec![arg!(any)], None);
 
 impl Function for ToArrayFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -824,7 +1812,7 @@ impl Function for ToArrayFn {
     }
 }
 
-defn!(ToNumberFn, vec![arg!(any)], None);
+defn!(ToNumberFn -> arg!(number | null), vec![arg!(any)], None);
 
 impl Function for ToNumberFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -841,7 +1829,7 @@ impl Function for ToNumberFn {
 }
 
 defn!(
-    ToStringFn,
+    ToStringFn -> arg!(string),
     vec![arg!(object | array | bool | number | string | null)],
     None
 );
@@ -856,7 +1844,34 @@ impl Function for ToStringFn {
     }
 }
 
-defn!(TypeFn, vec![arg!(any)], None);
+defn!(TrimFn -> arg!(string), vec![arg!(string)], vec![arg!(string)], None);
+
+impl Function for TrimFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        trim!(args, trim, trim_matches)
+    }
+}
+
+defn!(TrimLeftFn -> arg!(string), vec![arg!(string)], vec![arg!(string)], None);
+
+impl Function for TrimLeftFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        trim!(args, trim_start, trim_start_matches)
+    }
+}
+
+defn!(TrimRightFn -> arg!(string), vec![arg!(string)], vec![arg!(string)], None);
+
+impl Function for TrimRightFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        trim!(args, trim_end, trim_end_matches)
+    }
+}
+
+defn!(TypeFn -> arg!(string), vec![arg!(any)], None);
 
 impl Function for TypeFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -865,7 +1880,23 @@ impl Function for TypeFn {
     }
 }
 
-defn!(ValuesFn, vec![arg!(object)], None);
+defn!(UpperFn -> arg!(string), vec![arg!(string)], None);
+
+impl Function for UpperFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let subject = args[0].as_string().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be a string".to_owned()),
+            )
+        })?;
+        Ok(Rcvar::new(Variable::String(subject.to_uppercase())))
+    }
+}
+
+defn!(ValuesFn -> arg!(array), vec![arg!(object)], None);
 
 impl Function for ValuesFn {
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
@@ -882,3 +1913,160 @@ impl Function for ValuesFn {
         )))
     }
 }
+
+defn!(ZipFn -> arg!(array), vec![arg!(array)], Some(arg!(array)));
+
+/// Zips arrays together, stopping at the end of the shortest array.
+impl Function for ZipFn {
+    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let arrays = args
+            .iter()
+            .map(|arg| {
+                arg.as_array().ok_or_else(|| {
+                    JmespathError::new(
+                        "",
+                        0,
+                        ErrorReason::Parse("Expected to be an array".to_owned()),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, JmespathError>>()?;
+        let len = arrays.iter().map(|a| a.len()).min().unwrap_or(0);
+        let result = (0..len)
+            .map(|i| {
+                Rcvar::new(Variable::Array(
+                    arrays.iter().map(|a| a[i].clone()).collect(),
+                ))
+            })
+            .collect();
+        Ok(Rcvar::new(Variable::Array(result)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compile, ErrorReason, Runtime};
+
+    #[test]
+    fn custom_functions_receive_default_arguments() {
+        let mut runtime = Runtime::new();
+        runtime.register_function(
+            "pad",
+            Box::new(CustomFunction::new(
+                Signature::new(vec![ArgumentType::String], None)
+                    .with_default(ArgumentType::Number, 3.into())
+                    .with_default(ArgumentType::String, "-".into()),
+                Box::new(|args: &[Rcvar], _: &mut Context<'_>| {
+                    let width = args[1].as_number().unwrap() as usize;
+                    let pad = args[2].as_string().unwrap();
+                    let s = args[0].as_string().unwrap();
+                    Ok(Rcvar::new(Variable::String(format!(
+                        "{}{}",
+                        pad.repeat(width.saturating_sub(s.len())),
+                        s
+                    ))))
+                }),
+            )),
+        );
+        let search = |expr: &str| runtime.compile(expr).unwrap().search(()).unwrap();
+        assert_eq!("--a", search("pad('a')").as_string().unwrap());
+        assert_eq!("a", search("pad('a', `1`)").as_string().unwrap());
+        assert_eq!("..a", search("pad('a', `3`, '.')").as_string().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match the argument type number")]
+    fn rejects_defaults_that_do_not_match_the_argument_type() {
+        Signature::new(vec![], None).with_default(ArgumentType::Number, "3".into());
+    }
+
+    #[test]
+    fn typed_functions_convert_arguments_and_results() {
+        let mut runtime = Runtime::new();
+        runtime.register_typed("total", |values: Vec<f64>, scale: Option<f64>| {
+            values.iter().sum::<f64>() * scale.unwrap_or(1.0)
+        });
+        runtime.register_typed("words", |s: String| -> Vec<String> {
+            s.split_whitespace().map(String::from).collect()
+        });
+        let search = |expr: &str| runtime.compile(expr).unwrap().search(());
+        assert_eq!(
+            6.0,
+            search("total(`[1, 2, 3]`)").unwrap().as_number().unwrap()
+        );
+        assert_eq!(
+            12.0,
+            search("total(`[1, 2, 3]`, `2`)")
+                .unwrap()
+                .as_number()
+                .unwrap()
+        );
+        assert_eq!(
+            6.0,
+            search("total(`[1, 2, 3]`, `null`)")
+                .unwrap()
+                .as_number()
+                .unwrap()
+        );
+        assert_eq!(
+            "[\"a\",\"b\"]",
+            search("words(' a b ')").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn typed_functions_validate_arguments() {
+        let mut runtime = Runtime::new();
+        runtime.register_typed("total", |values: Vec<f64>, scale: Option<f64>| {
+            values.iter().sum::<f64>() * scale.unwrap_or(1.0)
+        });
+        runtime.register_typed("byte", |n: u8| n);
+        let reason = |expr: &str| {
+            runtime
+                .compile(expr)
+                .unwrap()
+                .search(())
+                .unwrap_err()
+                .reason
+        };
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::InvalidType {
+                expected: "array[number]".to_owned(),
+                actual: "array".to_owned(),
+                position: 0,
+            }),
+            reason("total(`[\"a\"]`)")
+        );
+        assert_eq!(
+            "Runtime error: Too many arguments: expected 1 to 2, found 3",
+            reason("total(`[]`, `1`, `2`)").to_string()
+        );
+        match reason("byte(`300`)") {
+            ErrorReason::Runtime(RuntimeError::InvalidValue { position: 0, .. }) => (),
+            r => panic!("unexpected error: {}", r),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot follow optional arguments")]
+    fn typed_functions_require_trailing_optional_arguments() {
+        let mut runtime = Runtime::new();
+        runtime.register_typed("bad", |_: Option<String>, _: String| true);
+    }
+
+    #[test]
+    fn arity_errors_report_ranges() {
+        let err = compile("pad_left('a')").unwrap().search(()).unwrap_err();
+        assert_eq!(
+            "Runtime error: Not enough arguments: expected 2 to 3, found 1",
+            err.reason.to_string()
+        );
+        let err = compile("not_null()").unwrap().search(()).unwrap_err();
+        assert_eq!(
+            "Runtime error: Not enough arguments: expected at least 1, found 0",
+            err.reason.to_string()
+        );
+    }
+}
@@ -2,16 +2,95 @@
 
 use std::collections::BTreeMap;
 
-use super::ast::Ast;
+use super::ast::{ArithmeticOperator, Ast, UnaryOperator};
 use super::variable::Variable;
 use super::Context;
-use super::{ErrorReason, JmespathError, Rcvar, RuntimeError};
+use super::{ErrorReason, JmespathError, Map, Rcvar, RuntimeError};
 
 /// Result of searching data using a JMESPath Expression.
 pub type SearchResult = Result<Rcvar, JmespathError>;
 
 /// Interprets the given data using an AST node.
 pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    ctx.enter(node.offset())?;
+    ctx.trace_enter(node, data);
+    let result = interpret_node(data, node, ctx).and_then(|result| {
+        if produces_value(node) {
+            ctx.check_value(&result, node.offset())?;
+        }
+        Ok(result)
+    });
+    ctx.exit();
+    ctx.trace_exit(node, data, &result);
+    result
+}
+
+/// Returns true if evaluating the node can produce a new array, object, or
+/// string rather than selecting part of its input.
+pub(crate) fn produces_value(node: &Ast) -> bool {
+    matches!(
+        *node,
+        Ast::Projection { .. }
+            | Ast::Flatten { .. }
+            | Ast::ObjectValues { .. }
+            | Ast::Slice { .. }
+            | Ast::MultiList { .. }
+            | Ast::MultiHash { .. }
+            | Ast::Function { .. }
+    )
+}
+
+/// Applies an arithmetic operator to two values.
+///
+/// Fails with an invalid-type error if either operand is not a number. The
+/// position of the error is 0 for the left operand and 1 for the right.
+pub(crate) fn arithmetic(
+    left: &Rcvar,
+    operator: &ArithmeticOperator,
+    right: &Rcvar,
+    offset: usize,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    for (position, operand) in [left, right].iter().enumerate() {
+        if !operand.is_number() {
+            ctx.offset = offset;
+            let reason = ErrorReason::Runtime(RuntimeError::InvalidType {
+                expected: "number".to_owned(),
+                actual: operand.get_type().to_string(),
+                position,
+            });
+            return Err(JmespathError::from_ctx(ctx, reason));
+        }
+    }
+    Ok(left
+        .arithmetic(operator, right)
+        .map_or(Rcvar::new(Variable::Null), Rcvar::new))
+}
+
+/// Applies a unary arithmetic operator to the result of a node.
+///
+/// Fails with an invalid-type error if the operand is not a number.
+pub(crate) fn unary_arithmetic(
+    operator: &UnaryOperator,
+    operand: &Rcvar,
+    offset: usize,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    if !operand.is_number() {
+        ctx.offset = offset;
+        let reason = ErrorReason::Runtime(RuntimeError::InvalidType {
+            expected: "number".to_owned(),
+            actual: operand.get_type().to_string(),
+            position: 0,
+        });
+        return Err(JmespathError::from_ctx(ctx, reason));
+    }
+    Ok(operand
+        .unary_arithmetic(operator)
+        .map_or(Rcvar::new(Variable::Null), Rcvar::new))
+}
+
+fn interpret_node(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
     match *node {
         Ast::Field { ref name, .. } => Ok(data.get_field(name)),
         Ast::Subexpr {
@@ -21,6 +100,10 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
             interpret(&left_result, rhs, ctx)
         }
         Ast::Identity { .. } => Ok(data.clone()),
+        Ast::Root { .. } => Ok(ctx
+            .root()
+            .cloned()
+            .unwrap_or_else(|| Rcvar::new(Variable::Null))),
         Ast::Literal { ref value, .. } => Ok(value.clone()),
         Ast::Index { idx, .. } => {
             if idx >= 0 {
@@ -75,11 +158,29 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
             let left = interpret(data, lhs, ctx)?;
             let right = interpret(data, rhs, ctx)?;
             Ok(left
-                .compare(comparator, &*right)
+                .compare(comparator, &right)
                 .map_or(Rcvar::new(Variable::Null), |result| {
                     Rcvar::new(Variable::Bool(result))
                 }))
         }
+        Ast::Arithmetic {
+            ref operator,
+            ref lhs,
+            ref rhs,
+            offset,
+        } => {
+            let left = interpret(data, lhs, ctx)?;
+            let right = interpret(data, rhs, ctx)?;
+            arithmetic(&left, operator, &right, offset, ctx)
+        }
+        Ast::UnaryArithmetic {
+            ref operator,
+            ref node,
+            offset,
+        } => {
+            let operand = interpret(data, node, ctx)?;
+            unary_arithmetic(operator, &operand, offset, ctx)
+        }
         // Converts an object into a JSON array of its values.
         Ast::ObjectValues { ref node, .. } => {
             let subject = interpret(data, node, ctx)?;
@@ -135,7 +236,7 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
             if data.is_null() {
                 Ok(Rcvar::new(Variable::Null))
             } else {
-                let mut collected = BTreeMap::new();
+                let mut collected = Map::new();
                 for kvp in elements {
                     let value = interpret(data, &kvp.value, ctx)?;
                     collected.insert(kvp.key.clone(), value);
@@ -181,5 +282,30 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
                 }
             }
         }
+        // Bindings are evaluated in the enclosing scope, so they cannot see
+        // each other, and are only visible while evaluating the body.
+        Ast::Let {
+            ref bindings,
+            ref expr,
+            ..
+        } => {
+            let mut scope = BTreeMap::new();
+            for binding in bindings {
+                let value = interpret(data, &binding.value, ctx)?;
+                scope.insert(binding.name.clone(), value);
+            }
+            ctx.push_scope(scope);
+            let result = interpret(data, expr, ctx);
+            ctx.pop_scope();
+            result
+        }
+        Ast::VariableRef { ref name, offset } => match ctx.get_variable(name) {
+            Some(value) => Ok(value),
+            None => {
+                ctx.offset = offset;
+                let reason = ErrorReason::Runtime(RuntimeError::UndefinedVariable(name.to_owned()));
+                Err(JmespathError::from_ctx(ctx, reason))
+            }
+        },
     }
 }
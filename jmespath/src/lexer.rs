@@ -19,11 +19,18 @@ use crate::{ErrorReason, JmespathError, Rcvar};
 pub enum Token {
     Identifier(String),
     QuotedIdentifier(String),
+    Variable(String),
     Number(i32),
     Literal(Rcvar),
     Dot,
     Star,
+    Multiply,
     Flatten,
+    Plus,
+    Minus,
+    Slash,
+    DoubleSlash,
+    Percent,
     And,
     Or,
     Pipe,
@@ -39,7 +46,9 @@ pub enum Token {
     Gte,
     Lt,
     Lte,
+    Assign,
     At,
+    Root,
     Ampersand,
     Lparen,
     Rparen,
@@ -66,8 +75,14 @@ impl Token {
             Gte => 5,
             Lte => 5,
             Ne => 5,
+            Plus => 6,
+            Minus => 6,
+            Star => 7,
+            Multiply => 7,
+            Slash => 7,
+            DoubleSlash => 7,
+            Percent => 7,
             Flatten => 9,
-            Star => 20,
             Filter => 21,
             Dot => 40,
             Not => 45,
@@ -87,6 +102,12 @@ pub fn tokenize(expr: &str) -> Result<VecDeque<TokenTuple>, JmespathError> {
     Lexer::new(expr).tokenize()
 }
 
+/// Returns true if the character may follow the first character of an identifier.
+#[inline]
+fn is_identifier_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | '_' | 'A'..='Z' | '0'..='9')
+}
+
 struct Lexer<'a> {
     iter: Peekable<CharIndices<'a>>,
     expr: &'a str,
@@ -126,19 +147,25 @@ impl<'a> Lexer<'a> {
                         '"' => tokens.push_back((pos, self.consume_quoted_identifier(pos)?)),
                         '\'' => tokens.push_back((pos, self.consume_raw_string(pos)?)),
                         '`' => tokens.push_back((pos, self.consume_literal(pos)?)),
-                        '=' => match self.iter.next() {
-                            Some((_, c)) if c == '=' => tokens.push_back((pos, Eq)),
-                            _ => {
-                                let message = "'=' is not valid. Did you mean '=='?";
-                                let reason = ErrorReason::Parse(message.to_owned());
-                                return Err(JmespathError::new(self.expr, pos, reason));
-                            }
-                        },
+                        '=' => tokens.push_back((pos, self.alt('=', Eq, Assign))),
+                        '$' => tokens.push_back((pos, self.consume_variable())),
                         '>' => tokens.push_back((pos, self.alt('=', Gte, Gt))),
                         '<' => tokens.push_back((pos, self.alt('=', Lte, Lt))),
                         '!' => tokens.push_back((pos, self.alt('=', Ne, Not))),
                         '0'..='9' => tokens.push_back((pos, self.consume_number(ch, false))),
-                        '-' => tokens.push_back((pos, self.consume_negative_number(pos)?)),
+                        '-' => match self.iter.peek() {
+                            Some(&(_, c)) if c.is_ascii_digit() => {
+                                tokens.push_back((pos, self.consume_negative_number(pos)?))
+                            }
+                            _ => tokens.push_back((pos, Minus)),
+                        },
+                        '+' => tokens.push_back((pos, Plus)),
+                        '/' => tokens.push_back((pos, self.alt('/', DoubleSlash, Slash))),
+                        '%' => tokens.push_back((pos, Percent)),
+                        // Unicode aliases of arithmetic operators.
+                        '\u{2212}' => tokens.push_back((pos, Minus)),
+                        '\u{d7}' => tokens.push_back((pos, Multiply)),
+                        '\u{f7}' => tokens.push_back((pos, Slash)),
                         // Skip whitespace tokens
                         ' ' | '\n' | '\t' | '\r' => {}
                         c => {
@@ -193,16 +220,26 @@ impl<'a> Lexer<'a> {
     // Consume identifiers: ( ALPHA / "_" ) *( DIGIT / ALPHA / "_" )
     #[inline]
     fn consume_identifier(&mut self, first_char: char) -> Token {
-        Identifier(self.consume_while(first_char.to_string(), |c| match c {
-            'a'..='z' | '_' | 'A'..='Z' | '0'..='9' => true,
-            _ => false,
-        }))
+        Identifier(self.consume_while(first_char.to_string(), is_identifier_char))
+    }
+
+    // Consumes the root node "$" or a variable reference:
+    // "$" ( ALPHA / "_" ) *( DIGIT / ALPHA / "_" )
+    #[inline]
+    fn consume_variable(&mut self) -> Token {
+        match self.iter.peek() {
+            Some(&(_, c)) if c.is_ascii_alphabetic() || c == '_' => {
+                self.iter.next();
+                Variable(self.consume_while(c.to_string(), is_identifier_char))
+            }
+            _ => Root,
+        }
     }
 
     // Consumes numbers: *"-" "0" / ( %x31-39 *DIGIT )
     #[inline]
     fn consume_number(&mut self, first_char: char, is_negative: bool) -> Token {
-        let lexeme = self.consume_while(first_char.to_string(), |c| c.is_digit(10));
+        let lexeme = self.consume_while(first_char.to_string(), |c| c.is_ascii_digit());
         let numeric_value: i32 = lexeme.parse().expect("Expected valid number");
         if is_negative {
             Number(-numeric_value)
@@ -362,6 +399,22 @@ mod tests {
         assert_eq!(tokenize_queue(">="), vec![(0, Gte), (2, Eof)]);
     }
 
+    #[test]
+    fn tokenize_arithmetic_test() {
+        assert_eq!(tokenize_queue("+"), vec![(0, Plus), (1, Eof)]);
+        assert_eq!(tokenize_queue("-"), vec![(0, Minus), (1, Eof)]);
+        assert_eq!(
+            tokenize_queue("- 1"),
+            vec![(0, Minus), (2, Number(1)), (3, Eof)]
+        );
+        assert_eq!(tokenize_queue("/"), vec![(0, Slash), (1, Eof)]);
+        assert_eq!(tokenize_queue("//"), vec![(0, DoubleSlash), (2, Eof)]);
+        assert_eq!(tokenize_queue("%"), vec![(0, Percent), (1, Eof)]);
+        assert_eq!(tokenize_queue("\u{2212}"), vec![(0, Minus), (3, Eof)]);
+        assert_eq!(tokenize_queue("\u{d7}"), vec![(0, Multiply), (2, Eof)]);
+        assert_eq!(tokenize_queue("\u{f7}"), vec![(0, Slash), (2, Eof)]);
+    }
+
     #[test]
     fn tokenize_eq_ne_test() {
         assert_eq!(tokenize_queue("=="), vec![(0, Eq), (2, Eof)]);
@@ -370,8 +423,38 @@ mod tests {
     }
 
     #[test]
-    fn ensures_eq_valid() {
-        assert!(tokenize("=").is_err());
+    fn tokenize_assign_test() {
+        assert_eq!(tokenize_queue("="), vec![(0, Assign), (1, Eof)]);
+        assert_eq!(
+            tokenize_queue("= ="),
+            vec![(0, Assign), (2, Assign), (3, Eof)]
+        );
+    }
+
+    #[test]
+    fn tokenize_variable_test() {
+        assert_eq!(
+            tokenize_queue("$foo_1"),
+            vec![(0, Variable("foo_1".to_string())), (6, Eof)]
+        );
+        assert_eq!(
+            tokenize_queue("$_"),
+            vec![(0, Variable("_".to_string())), (2, Eof)]
+        );
+        assert_eq!(tokenize_queue("$"), vec![(0, Root), (1, Eof)]);
+        assert_eq!(
+            tokenize_queue("$.foo"),
+            vec![
+                (0, Root),
+                (1, Dot),
+                (2, Identifier("foo".to_string())),
+                (5, Eof)
+            ]
+        );
+        assert_eq!(
+            tokenize_queue("$1"),
+            vec![(0, Root), (1, Number(1)), (2, Eof)]
+        );
     }
 
     #[test]
@@ -53,11 +53,52 @@
 //! using serde's macros or code generation capabilities. This includes a
 //! number of common types, including serde's `serde_json::Value` enum.
 //!
+//! Large `serde_json::Value` documents can also be searched in place using
+//! `Expression::search_borrowed`, which only copies the parts of the
+//! document that are needed rather than converting the entire document.
+//! Other document types can be searched in place by implementing
+//! `jmespath::JmespathValue`, and Rust values can be searched in place
+//! without serializing them by implementing (or deriving) `jmespath::Searchable`
+//! and using `Expression::search_searchable`.
+//!
 //! The return value of searching data with JMESPath is also an `Rcvar`.
 //! `Variable` has a number of helper methods that make it a data type that
 //! can be used directly, or you can convert `Variable` to any serde value
 //! implementing `serde::de::Deserialize`.
 //!
+//! The fields of objects are sorted by key by default. Enabling the
+//! `preserve_order` feature stores the fields of objects in a `Map` that
+//! preserves the order in which they were inserted, so that the fields of
+//! parsed documents, multi-select hashes, `keys`, `values`, and `merge`
+//! keep the order in which the fields appear.
+//!
+//! Integers are compared and used in arithmetic and numeric functions
+//! (`sum`, `abs`, `max`, etc.) without being converted to `f64`, so large
+//! integers keep their exact value. Other numbers are `f64`s unless the
+//! `arbitrary_precision` feature is enabled, which keeps numbers as exact
+//! decimals.
+//!
+//! # Variables
+//!
+//! Expressions can reference variables using `$name`. Variables are bound
+//! by `let` expressions (e.g., `let $x = foo in bar[?baz == $x]`) or can
+//! be supplied when searching data using `Expression::search_with_vars`.
+//! A bare `$` always refers to the root of the searched data.
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use jmespath::{Rcvar, Variable};
+//!
+//! let expr = jmespath::compile("people[?age > $min_age].name").unwrap();
+//! let data = Variable::from_json(r#"{"people": [{"name": "a", "age": 20},
+//!                                               {"name": "b", "age": 40}]}"#).unwrap();
+//!
+//! let mut vars = BTreeMap::new();
+//! vars.insert("min_age".to_string(), Rcvar::new(Variable::from_json("30").unwrap()));
+//! let result = expr.search_with_vars(data, vars).unwrap();
+//! assert_eq!("[\"b\"]", result.to_string());
+//! ```
+//!
 //! # Custom Functions
 //!
 //! You can register custom functions with a JMESPath expression by using
@@ -91,35 +132,81 @@
 //! let expr = runtime.compile("identity('bar')").unwrap();
 //! assert_eq!("bar", expr.search(()).unwrap().as_string().unwrap());
 //! ```
+//!
+//! Plain Rust functions can also be registered using
+//! `Runtime::register_typed`, which derives the function signature from the
+//! argument types and converts arguments using serde.
+//!
+//! # Limits
+//!
+//! Expressions are evaluated without limits by default. When evaluating
+//! untrusted expressions, set `Limits` on a `Runtime` (or on a single
+//! `Expression`) to bound the number of evaluation steps, the depth of
+//! nested expressions, and the size of the arrays, objects, and strings
+//! that are produced. Evaluation fails with a `RuntimeError` when a limit
+//! is exceeded. The bytecode backend does not evaluate nodes recursively,
+//! so the depth limit only applies to the expressions that its functions
+//! evaluate (see `Limits::max_depth`).
+//!
+//! ```
+//! use jmespath::{Limits, Runtime};
+//!
+//! let mut runtime = Runtime::new();
+//! runtime.register_builtin_functions();
+//! runtime.set_limits(Limits::new().with_max_depth(64).with_max_size(1000));
+//! assert!(runtime.compile(&format!("{}@{}", "(".repeat(100), ")".repeat(100))).is_err());
+//!
+//! let expr = runtime.compile("[*][*][*]").unwrap();
+//! let data = jmespath::Variable::from_json("[[[1]]]").unwrap();
+//! assert_eq!("[[[1]]]", expr.search(data).unwrap().to_string());
+//! ```
 
 #![cfg_attr(feature = "specialized", feature(specialization))]
 
+pub use crate::borrowed::JmespathValue;
 pub use crate::errors::{ErrorReason, JmespathError, RuntimeError};
 pub use crate::parser::{parse, ParseResult};
-pub use crate::runtime::Runtime;
-pub use crate::variable::Variable;
+pub use crate::paths::{PathMatch, PathSegment};
+pub use crate::runtime::{Backend, Limits, Runtime};
+pub use crate::searchable::Searchable;
+pub use crate::trace::{NodeProfile, Profiler, TraceLog, Tracer};
+pub use crate::variable::{JmespathType, Variable};
 
+pub mod analysis;
 pub mod ast;
 pub mod functions;
 
 use serde::ser;
 #[cfg(feature = "specialized")]
 use serde_json::Value;
+use std::collections::BTreeMap;
 #[cfg(feature = "specialized")]
 use std::convert::TryInto;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use lazy_static::*;
 
+use crate::analysis::{Analysis, Shape};
 use crate::ast::Ast;
 use crate::interpreter::{interpret, SearchResult};
+use crate::vm::Program;
 
+mod borrowed;
 mod errors;
 mod interpreter;
 mod lexer;
+mod number;
+mod optimizer;
 mod parser;
+mod paths;
 mod runtime;
+mod searchable;
+mod trace;
 mod variable;
+mod vm;
 
 lazy_static! {
     pub static ref DEFAULT_RUNTIME: Runtime = {
@@ -136,6 +223,18 @@ pub type Rcvar = std::rc::Rc<Variable>;
 #[cfg(feature = "sync")]
 pub type Rcvar = std::sync::Arc<Variable>;
 
+/// Map used to store the fields of a `Variable::Object`, sorted by key.
+#[cfg(not(feature = "preserve_order"))]
+pub type Map<K, V> = BTreeMap<K, V>;
+/// Map used to store the fields of a `Variable::Object`, in the order in
+/// which the fields were inserted.
+#[cfg(feature = "preserve_order")]
+pub type Map<K, V> = indexmap::IndexMap<K, V>;
+
+/// Used by `#[derive(Searchable)]` to order the fields of structs like `Map`.
+#[doc(hidden)]
+pub const __PRESERVE_ORDER: bool = cfg!(feature = "preserve_order");
+
 /// Compiles a JMESPath expression using the default Runtime.
 ///
 /// The default Runtime is created lazily the first time it is dereferenced
@@ -148,6 +247,41 @@ pub fn compile(expression: &str) -> Result<Expression<'static>, JmespathError> {
     DEFAULT_RUNTIME.compile(expression)
 }
 
+/// Lazily compiles an expression that was validated by the `jmespath!`
+/// macro of the `jmespath-macros` crate.
+#[doc(hidden)]
+#[cfg(feature = "sync")]
+#[macro_export]
+macro_rules! __lazy_expression {
+    ($expression:expr) => {{
+        static EXPRESSION: ::std::sync::OnceLock<$crate::Expression<'static>> =
+            ::std::sync::OnceLock::new();
+        EXPRESSION.get_or_init(|| {
+            $crate::compile($expression).expect("expression was validated by jmespath!")
+        })
+    }};
+}
+
+/// Lazily compiles an expression that was validated by the `jmespath!`
+/// macro of the `jmespath-macros` crate.
+///
+/// Expressions cannot be shared between threads without the `sync`
+/// feature, so each thread compiles the expression once and returns an
+/// `Rc` of it. The expression is dropped when the thread exits.
+#[doc(hidden)]
+#[cfg(not(feature = "sync"))]
+#[macro_export]
+macro_rules! __lazy_expression {
+    ($expression:expr) => {{
+        ::std::thread_local! {
+            static EXPRESSION: ::std::rc::Rc<$crate::Expression<'static>> = ::std::rc::Rc::new(
+                $crate::compile($expression).expect("expression was validated by jmespath!"),
+            );
+        }
+        EXPRESSION.with(::std::rc::Rc::clone)
+    }};
+}
+
 /// Converts a value into a reference-counted JMESPath Variable.
 ///
 #[cfg_attr(
@@ -175,10 +309,10 @@ pub trait ToJmespath {
 }
 
 /// Create searchable values from Serde serializable values.
-impl<'a, T: ser::Serialize> ToJmespath for T {
+impl<T: ser::Serialize> ToJmespath for T {
     #[cfg(not(feature = "specialized"))]
     fn to_jmespath(self) -> Result<Rcvar, JmespathError> {
-        Ok(Variable::from_serializable(self).map(Rcvar::new)?)
+        Variable::from_serializable(self).map(Rcvar::new)
     }
 
     #[cfg(feature = "specialized")]
@@ -368,25 +502,88 @@ pub struct Expression<'a> {
     ast: Ast,
     expression: String,
     runtime: &'a Runtime,
+    program: Option<Program>,
+    limits: Limits,
 }
 
 impl<'a> Expression<'a> {
     /// Creates a new JMESPath expression.
     ///
     /// Normally you will create expressions using either `jmespath::compile()`
-    /// or using a jmespath::Runtime.
+    /// or using a jmespath::Runtime. Creating an expression directly is
+    /// useful when the AST was built or transformed without parsing (e.g.,
+    /// deserialized using the `serde-ast` feature), in which case the AST
+    /// can be formatted to provide the expression string:
+    ///
+    /// ```
+    /// use jmespath::{parse, Expression, Runtime};
+    ///
+    /// let runtime = Runtime::new();
+    /// let ast = parse("foo.bar").unwrap();
+    /// let expr = Expression::new(ast.to_string(), ast, &runtime);
+    /// assert_eq!("foo.bar", expr.as_str());
+    /// ```
     #[inline]
     pub fn new<S>(expression: S, ast: Ast, runtime: &'a Runtime) -> Expression<'a>
     where
         S: Into<String>,
     {
-        Expression {
+        let mut expression = Expression {
             expression: expression.into(),
             ast,
             runtime,
+            program: None,
+            limits: runtime.limits(),
+        };
+        expression.set_backend(runtime.backend());
+        expression
+    }
+
+    /// Sets the backend used to evaluate the expression.
+    ///
+    /// Expressions use the backend of the runtime that created them by
+    /// default. Selecting `Backend::Bytecode` compiles the AST to bytecode.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.program = match backend {
+            Backend::Interpreter => None,
+            Backend::Bytecode => Some(Program::compile(&self.ast)),
+        };
+    }
+
+    /// Returns the backend used to evaluate the expression.
+    pub fn backend(&self) -> Backend {
+        match self.program {
+            Some(_) => Backend::Bytecode,
+            None => Backend::Interpreter,
         }
     }
 
+    /// Sets the limits used to evaluate the expression.
+    ///
+    /// Expressions use the limits of the runtime that created them by
+    /// default.
+    ///
+    /// ```
+    /// use jmespath::{Limits, RuntimeError, ErrorReason};
+    ///
+    /// let mut expr = jmespath::compile("[*][*][*]").unwrap();
+    /// expr.set_limits(Limits::new().with_max_steps(10));
+    /// let data = jmespath::Variable::from_json("[[[1, 2], [3]], [[4]]]").unwrap();
+    /// let err = expr.search(data).unwrap_err();
+    /// assert_eq!(
+    ///     ErrorReason::Runtime(RuntimeError::StepLimitExceeded { limit: 10 }),
+    ///     err.reason
+    /// );
+    /// ```
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Returns the limits used to evaluate the expression.
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
     /// Returns the result of searching data with the compiled expression.
     ///
     /// The SearchResult contains a JMESPath Rcvar, or a reference counted
@@ -394,8 +591,180 @@ impl<'a> Expression<'a> {
     /// Alternatively, Variable does implement Serde serialzation and
     /// deserialization, so it can easily be marshalled to another type.
     pub fn search<T: ToJmespath>(&self, data: T) -> SearchResult {
-        let mut ctx = Context::new(&self.expression, self.runtime);
-        interpret(&data.to_jmespath()?, &self.ast, &mut ctx)
+        let data = data.to_jmespath()?;
+        let mut ctx = Context::new(&self.expression, self.runtime)
+            .with_root(data.clone())
+            .with_limits(self.limits);
+        self.evaluate(&data, &mut ctx)
+    }
+
+    /// Returns the result of searching data with the compiled expression,
+    /// binding the given variables so that they can be referenced as
+    /// `$name` from anywhere in the expression.
+    ///
+    /// Variables bound by `let` expressions shadow variables of the same
+    /// name provided here.
+    pub fn search_with_vars<T: ToJmespath>(
+        &self,
+        data: T,
+        vars: BTreeMap<String, Rcvar>,
+    ) -> SearchResult {
+        let data = data.to_jmespath()?;
+        let mut ctx = Context::new(&self.expression, self.runtime)
+            .with_root(data.clone())
+            .with_limits(self.limits)
+            .with_variables(vars);
+        self.evaluate(&data, &mut ctx)
+    }
+
+    /// Returns a context that can be used to search with the expression.
+    ///
+    /// The context can be configured, e.g., with a deadline, a
+    /// cancellation token, or a tracer, and passed to `search_with_context`
+    /// or one of the other `*_with_context` methods.
+    pub fn context(&self) -> Context<'_> {
+        Context::new(&self.expression, self.runtime).with_limits(self.limits)
+    }
+
+    /// Returns the result of searching data with the compiled expression
+    /// using the given context.
+    ///
+    /// Evaluation periodically checks the deadline and cancellation token
+    /// of the context, failing with `RuntimeError::Timeout` or
+    /// `RuntimeError::Cancelled`. Functions are not interrupted while they
+    /// are running, but the expressions passed to functions like `sort_by`
+    /// and `map` are.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let expr = jmespath::compile("foo[*].bar").unwrap();
+    /// let data = jmespath::Variable::from_json(r#"{"foo": [{"bar": 1}]}"#).unwrap();
+    /// let ctx = expr
+    ///     .context()
+    ///     .with_deadline(Instant::now() + Duration::from_secs(1));
+    /// assert_eq!("[1]", expr.search_with_context(data, ctx).unwrap().to_string());
+    /// ```
+    pub fn search_with_context<T: ToJmespath>(&self, data: T, ctx: Context<'_>) -> SearchResult {
+        let data = data.to_jmespath()?;
+        let mut ctx = ctx.with_root(data.clone());
+        self.evaluate(&data, &mut ctx)
+    }
+
+    /// Returns the values selected by searching data with the expression,
+    /// along with the path of each value in the data.
+    ///
+    /// Paths can only be found for expressions that select parts of the
+    /// data: fields, indexes, slices, wildcards, flattens, filters, and
+    /// pipes of them. Other expressions fail with
+    /// `RuntimeError::UnsupportedPathExpression`. Null values are not
+    /// matched, and the values selected by nested projections are
+    /// flattened into a single list.
+    ///
+    /// ```
+    /// let data = jmespath::Variable::from_json(
+    ///     r#"{"reservations": [{"instances": [{"id": "a"}, {"id": "b"}]}]}"#,
+    /// )
+    /// .unwrap();
+    /// let expr = jmespath::compile("reservations[*].instances[?id == 'b'].id").unwrap();
+    /// let matches = expr.search_paths(data).unwrap();
+    /// assert_eq!("reservations[0].instances[1].id", matches[0].jmespath());
+    /// assert_eq!("/reservations/0/instances/1/id", matches[0].json_pointer());
+    /// assert_eq!("b", matches[0].value.as_string().unwrap());
+    /// ```
+    pub fn search_paths<T: ToJmespath>(&self, data: T) -> Result<Vec<PathMatch>, JmespathError> {
+        self.search_paths_with_context(data, self.context())
+    }
+
+    /// Returns the values selected by searching data with the expression,
+    /// along with their paths, using the given context.
+    ///
+    /// See `search_paths` and `search_with_context`.
+    pub fn search_paths_with_context<T: ToJmespath>(
+        &self,
+        data: T,
+        ctx: Context<'_>,
+    ) -> Result<Vec<PathMatch>, JmespathError> {
+        let data = data.to_jmespath()?;
+        let mut ctx = ctx.with_root(data.clone());
+        paths::search_paths(&data, &self.ast, &mut ctx)
+    }
+
+    /// Returns the result of searching borrowed data with the compiled
+    /// expression, without first converting all of the data into a
+    /// `Variable`.
+    ///
+    /// Only the parts of the data that are part of the result, are passed
+    /// to functions, or are compared are copied, which is much faster than
+    /// `search` when extracting a few values from a large document. The
+    /// data is always searched using the interpreter, regardless of the
+    /// backend of the expression.
+    ///
+    /// ```
+    /// use serde_json::json;
+    ///
+    /// let data = json!({"foo": [{"bar": 1}, {"bar": 2}]});
+    /// let expr = jmespath::compile("foo[?bar > `1`].bar").unwrap();
+    /// let result = expr.search_borrowed(&data).unwrap();
+    /// assert_eq!(jmespath::Variable::from_json("[2]").unwrap(), *result);
+    /// ```
+    pub fn search_borrowed<V: JmespathValue + ?Sized>(&self, data: &V) -> SearchResult {
+        self.search_borrowed_with_context(data, self.context())
+    }
+
+    /// Returns the result of searching borrowed data with the compiled
+    /// expression using the given context.
+    ///
+    /// See `search_borrowed` and `search_with_context`. A tracer set on
+    /// the context only receives events for the expressions that are
+    /// evaluated by functions.
+    pub fn search_borrowed_with_context<V: JmespathValue + ?Sized>(
+        &self,
+        data: &V,
+        ctx: Context<'_>,
+    ) -> SearchResult {
+        let mut ctx = ctx;
+        borrowed::search(data, &self.ast, &mut ctx)
+    }
+
+    /// Returns the result of searching a Rust value in place, without
+    /// serializing it into a `Variable`.
+    ///
+    /// Like `search_borrowed`, only the parts of the value that are needed
+    /// are copied. See `Searchable` for the types that can be searched.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut ports = BTreeMap::new();
+    /// ports.insert("http".to_string(), vec![80, 8080]);
+    /// ports.insert("https".to_string(), vec![443]);
+    /// let expr = jmespath::compile("http[-1]").unwrap();
+    /// let result = expr.search_searchable(&ports).unwrap();
+    /// assert_eq!(jmespath::Variable::from_json("8080").unwrap(), *result);
+    /// ```
+    pub fn search_searchable<S: Searchable>(&self, data: &S) -> SearchResult {
+        self.search_borrowed::<dyn Searchable>(data)
+    }
+
+    /// Returns the result of searching a Rust value in place using the
+    /// given context.
+    ///
+    /// See `search_searchable` and `search_borrowed_with_context`.
+    pub fn search_searchable_with_context<S: Searchable>(
+        &self,
+        data: &S,
+        ctx: Context<'_>,
+    ) -> SearchResult {
+        self.search_borrowed_with_context::<dyn Searchable>(data, ctx)
+    }
+
+    fn evaluate(&self, data: &Rcvar, ctx: &mut Context<'_>) -> SearchResult {
+        match self.program {
+            // Tracers receive events for AST nodes, so traced searches are interpreted.
+            Some(ref program) if !ctx.is_tracing() => program.execute(data, ctx),
+            _ => interpret(data, &self.ast, ctx),
+        }
     }
 
     /// Returns the JMESPath expression from which the Expression was compiled.
@@ -412,6 +781,14 @@ impl<'a> Expression<'a> {
     pub fn as_ast(&self) -> &Ast {
         &self.ast
     }
+
+    /// Infers the shape of the result of the expression when searching data
+    /// of the given shape, reporting problems found in the expression.
+    ///
+    /// See the `analysis` module for more information.
+    pub fn analyze(&self, input: &Shape) -> Analysis<'_> {
+        analysis::analyze(&self.ast, input, self.runtime)
+    }
 }
 
 impl<'a> fmt::Display for Expression<'a> {
@@ -445,6 +822,22 @@ pub struct Context<'a> {
     pub runtime: &'a Runtime,
     /// Ast offset that is currently being evaluated.
     pub offset: usize,
+    /// Stack of variable scopes, with the innermost scope last.
+    scopes: Vec<BTreeMap<String, Rcvar>>,
+    /// Root node that the expression is evaluated against.
+    root: Option<Rcvar>,
+    /// Limits on the resources used to evaluate the expression.
+    limits: Limits,
+    /// Number of steps taken so far.
+    steps: usize,
+    /// Depth of the node that is currently being evaluated.
+    depth: usize,
+    /// Instant after which evaluation fails.
+    deadline: Option<Instant>,
+    /// Token that cancels evaluation.
+    cancellation: Option<CancellationToken>,
+    /// Tracer that receives an event for each node that is evaluated.
+    tracer: Option<&'a mut dyn Tracer>,
 }
 
 impl<'a> Context<'a> {
@@ -455,8 +848,256 @@ impl<'a> Context<'a> {
             expression,
             runtime,
             offset: 0,
+            scopes: vec![],
+            root: None,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            deadline: None,
+            cancellation: None,
+            tracer: None,
+        }
+    }
+
+    /// Sets the root node that `$` resolves to.
+    #[inline]
+    pub fn with_root(mut self, root: Rcvar) -> Context<'a> {
+        self.root = Some(root);
+        self
+    }
+
+    /// Sets the limits on the resources used to evaluate the expression.
+    #[inline]
+    pub fn with_limits(mut self, limits: Limits) -> Context<'a> {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the instant after which evaluation fails with
+    /// `RuntimeError::Timeout`.
+    #[inline]
+    pub fn with_deadline(mut self, deadline: Instant) -> Context<'a> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets a token that makes evaluation fail with
+    /// `RuntimeError::Cancelled` once it is cancelled.
+    #[inline]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Context<'a> {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets a tracer that receives an event when each node is entered and
+    /// exited. See `Tracer` for more information.
+    #[inline]
+    pub fn with_tracer(mut self, tracer: &'a mut dyn Tracer) -> Context<'a> {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Binds variables in a new scope on the context.
+    ///
+    /// This can be called multiple times; later bindings shadow earlier
+    /// bindings of the same name.
+    #[inline]
+    pub fn with_variables(mut self, vars: BTreeMap<String, Rcvar>) -> Context<'a> {
+        self.push_scope(vars);
+        self
+    }
+
+    /// Returns the root node that `$` resolves to, if one was set.
+    #[inline]
+    pub fn root(&self) -> Option<&Rcvar> {
+        self.root.as_ref()
+    }
+
+    /// Pushes a scope of variable bindings onto the scope stack.
+    ///
+    /// Bindings in the pushed scope shadow bindings of the same name in
+    /// any enclosing scope until the scope is popped.
+    #[inline]
+    pub fn push_scope(&mut self, scope: BTreeMap<String, Rcvar>) {
+        self.scopes.push(scope);
+    }
+
+    /// Pops the innermost scope from the scope stack.
+    #[inline]
+    pub fn pop_scope(&mut self) -> Option<BTreeMap<String, Rcvar>> {
+        self.scopes.pop()
+    }
+
+    /// Resolves a variable by name, starting from the innermost scope.
+    ///
+    /// Returns None if the variable is not bound in any scope.
+    pub fn get_variable(&self, name: &str) -> Option<Rcvar> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    /// Counts a step of evaluation, failing if the step limit is exceeded.
+    ///
+    /// Errors point to the given offset in the expression.
+    #[inline]
+    pub(crate) fn step(&mut self, offset: usize) -> Result<(), JmespathError> {
+        self.steps += 1;
+        if self.steps % INTERRUPT_INTERVAL == 1 {
+            self.check_interrupted(offset)?;
+        }
+        match self.limits.max_steps {
+            Some(limit) if self.steps > limit => {
+                Err(self.limit_error(offset, RuntimeError::StepLimitExceeded { limit }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a step and enters a nested node, failing if the step or depth
+    /// limit is exceeded. Every successful call must be paired with a call
+    /// to `exit`.
+    #[inline]
+    pub(crate) fn enter(&mut self, offset: usize) -> Result<(), JmespathError> {
+        self.step(offset)?;
+        match self.limits.max_depth {
+            Some(limit) if self.depth >= limit => {
+                Err(self.limit_error(offset, RuntimeError::DepthLimitExceeded { limit }))
+            }
+            _ => {
+                self.depth += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Leaves a node that was entered using `enter`.
+    #[inline]
+    pub(crate) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Fails if a produced value is larger than allowed by the size or
+    /// string length limits.
+    pub(crate) fn check_value(&self, value: &Variable, offset: usize) -> Result<(), JmespathError> {
+        match *value {
+            Variable::Array(ref a) => self.check_size(a.len(), offset),
+            Variable::Object(ref o) => self.check_size(o.len(), offset),
+            Variable::String(ref s) => self.check_string_length(s.len(), offset),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a string of the given length in bytes is longer than
+    /// allowed by the string length limit.
+    pub(crate) fn check_string_length(
+        &self,
+        length: usize,
+        offset: usize,
+    ) -> Result<(), JmespathError> {
+        match self.limits.max_string_length {
+            Some(limit) if length > limit => Err(self.limit_error(
+                offset,
+                RuntimeError::StringLengthLimitExceeded { limit, length },
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a produced array or object has more elements than allowed
+    /// by the size limit.
+    pub(crate) fn check_size(&self, size: usize, offset: usize) -> Result<(), JmespathError> {
+        match self.limits.max_size {
+            Some(limit) if size > limit => {
+                Err(self.limit_error(offset, RuntimeError::SizeLimitExceeded { limit, size }))
+            }
+            _ => Ok(()),
         }
     }
+
+    /// Returns true if a tracer is attached to the context.
+    #[inline]
+    pub(crate) fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    /// Notifies the tracer, if any, that a node is being entered.
+    #[inline]
+    pub(crate) fn trace_enter(&mut self, node: &Ast, input: &Rcvar) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.enter(node, input);
+        }
+    }
+
+    /// Notifies the tracer, if any, that a node was exited.
+    #[inline]
+    pub(crate) fn trace_exit(&mut self, node: &Ast, input: &Rcvar, result: &SearchResult) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.exit(node, input, result);
+        }
+    }
+
+    /// Fails if evaluation was cancelled or reached its deadline.
+    fn check_interrupted(&self, offset: usize) -> Result<(), JmespathError> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            Err(self.limit_error(offset, RuntimeError::Cancelled))
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(self.limit_error(offset, RuntimeError::Timeout))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn limit_error(&self, offset: usize, error: RuntimeError) -> JmespathError {
+        JmespathError::new(self.expression, offset, ErrorReason::Runtime(error))
+    }
+}
+
+/// Number of steps between checks for cancellation and deadlines.
+const INTERRUPT_INTERVAL: usize = 64;
+
+/// Cancels searches, possibly from another thread.
+///
+/// Clones of a token share their state, so cancelling any clone cancels
+/// every search that uses the token.
+///
+/// ```
+/// use jmespath::{CancellationToken, ErrorReason, RuntimeError};
+///
+/// let expr = jmespath::compile("foo[*].bar").unwrap();
+/// let token = CancellationToken::new();
+/// token.cancel();
+/// let ctx = expr.context().with_cancellation(token);
+/// let err = expr.search_with_context(jmespath::Variable::Null, ctx).unwrap_err();
+/// assert_eq!(ErrorReason::Runtime(RuntimeError::Cancelled), err.reason);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> CancellationToken {
+        Default::default()
+    }
+
+    /// Cancels the searches that use the token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 #[cfg(test)]
@@ -484,6 +1125,291 @@ mod test {
         assert_eq!(Rcvar::new(Variable::Bool(true)), expr.search(var).unwrap());
     }
 
+    #[test]
+    fn can_search_with_variables() {
+        let expr = compile("[$foo, let $foo = 'inner' in $foo, $bar]").unwrap();
+        let mut vars = BTreeMap::new();
+        vars.insert("foo".to_string(), Rcvar::new(Variable::Bool(true)));
+        vars.insert("bar".to_string(), Rcvar::new(Variable::Null));
+        assert_eq!(
+            "[true,\"inner\",null]",
+            expr.search_with_vars(Variable::from_json("{}").unwrap(), vars)
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    fn assert_object_key_order(cases: &[(&str, &str)]) {
+        let data = Variable::from_json(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        for &(expression, expected) in cases {
+            let mut expr = compile(expression).unwrap();
+            assert_eq!(expected, expr.search(&data).unwrap().to_string());
+            assert_eq!(expected, expr.search_borrowed(&json).unwrap().to_string());
+            expr.set_backend(Backend::Bytecode);
+            assert_eq!(expected, expr.search(&data).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "preserve_order"))]
+    fn sorts_object_keys() {
+        assert_object_key_order(&[
+            ("@", r#"{"a":2,"m":3,"z":1}"#),
+            ("{z: z, a: a}", r#"{"a":2,"z":1}"#),
+            ("keys(@)", r#"["a","m","z"]"#),
+            ("values(@)", "[2,3,1]"),
+            ("merge(@, {b: `4`, z: `5`})", r#"{"a":2,"b":4,"m":3,"z":5}"#),
+            (
+                "keys(group_by(`[{\"k\": \"y\"}, {\"k\": \"x\"}]`, &k))",
+                r#"["x","y"]"#,
+            ),
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn preserves_object_key_order() {
+        assert_object_key_order(&[
+            ("@", r#"{"z":1,"a":2,"m":3}"#),
+            ("{z: z, a: a}", r#"{"z":1,"a":2}"#),
+            ("keys(@)", r#"["z","a","m"]"#),
+            ("values(@)", "[1,2,3]"),
+            ("merge(@, {b: `4`, z: `5`})", r#"{"z":5,"a":2,"m":3,"b":4}"#),
+            (
+                "keys(group_by(`[{\"k\": \"y\"}, {\"k\": \"x\"}]`, &k))",
+                r#"["y","x"]"#,
+            ),
+        ]);
+    }
+
+    #[test]
+    fn unbound_variables_are_errors() {
+        let expr = compile("$foo").unwrap();
+        let err = expr.search_with_vars((), BTreeMap::new()).unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::UndefinedVariable("foo".to_string())),
+            err.reason
+        );
+    }
+
+    fn assert_limit_error(limits: Limits, expression: &str, expected: RuntimeError) {
+        let data = Variable::from_json(r#"{"a": [1, 2, 3]}"#).unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        let mut expr = compile(expression).unwrap();
+        expr.set_limits(limits);
+        let expected = ErrorReason::Runtime(expected);
+        assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+        assert_eq!(expected, expr.search_borrowed(&json).unwrap_err().reason);
+        expr.set_backend(Backend::Bytecode);
+        assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+    }
+
+    #[test]
+    fn limits_size_of_produced_values() {
+        let limits = Limits::new().with_max_size(2);
+        let error = RuntimeError::SizeLimitExceeded { limit: 2, size: 3 };
+        assert_limit_error(limits, "a[*]", error.clone());
+        assert_limit_error(limits, "[a[0], a[1], a[2]]", error.clone());
+        assert_limit_error(limits, "sort(a)", error);
+        // Selecting part of the input does not produce a new value.
+        let mut expr = compile("a").unwrap();
+        expr.set_limits(limits);
+        let data = Variable::from_json(r#"{"a": [1, 2, 3]}"#).unwrap();
+        assert_eq!("[1,2,3]", expr.search(data).unwrap().to_string());
+    }
+
+    #[test]
+    fn limits_length_of_produced_strings() {
+        let limits = Limits::new().with_max_string_length(4);
+        let error = RuntimeError::StringLengthLimitExceeded {
+            limit: 4,
+            length: 7,
+        };
+        assert_limit_error(limits, "to_string(a)", error);
+        // Padding fails before the padded string is built.
+        let error = RuntimeError::StringLengthLimitExceeded {
+            limit: 4,
+            length: 1_000_000_000_000,
+        };
+        assert_limit_error(limits, "pad_left('ab', `1e12`)", error);
+    }
+
+    #[test]
+    fn limits_steps() {
+        let data = Variable::from_json("[[[1, 2], [3]], [[4]]]").unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        let mut expr = compile("[*][*][*]").unwrap();
+        expr.set_limits(Limits::new().with_max_steps(10));
+        let expected = ErrorReason::Runtime(RuntimeError::StepLimitExceeded { limit: 10 });
+        assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+        assert_eq!(expected, expr.search_borrowed(&json).unwrap_err().reason);
+        expr.set_backend(Backend::Bytecode);
+        assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+        expr.set_limits(Limits::new().with_max_steps(1000));
+        assert_eq!(
+            "[[[1,2],[3]],[[4]]]",
+            expr.search(&data).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn limits_depth() {
+        let data = Variable::from_json(r#"{"a": {"b": {"c": {"d": 1}}}}"#).unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        let mut expr = compile("a.b.c.d").unwrap();
+        expr.set_limits(Limits::new().with_max_depth(3));
+        let expected = ErrorReason::Runtime(RuntimeError::DepthLimitExceeded { limit: 3 });
+        assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+        assert_eq!(expected, expr.search_borrowed(&json).unwrap_err().reason);
+        expr.set_limits(Limits::new().with_max_depth(4));
+        assert_eq!("1", expr.search(&data).unwrap().to_string());
+        // Expressions evaluated by functions are limited by every backend.
+        let mut expr = compile("sort_by([@], &a.b.c.d)").unwrap();
+        expr.set_limits(Limits::new().with_max_depth(3));
+        for backend in [Backend::Interpreter, Backend::Bytecode] {
+            expr.set_backend(backend);
+            assert_eq!(expected, expr.search(&data).unwrap_err().reason);
+        }
+    }
+
+    #[test]
+    fn runtime_limits_nesting_of_compiled_expressions() {
+        let mut runtime = Runtime::new();
+        runtime.set_limits(Limits::new().with_max_depth(3));
+        assert!(runtime.compile("((a))").is_ok());
+        let err = runtime.compile("(((a)))").unwrap_err();
+        assert_eq!(
+            ErrorReason::Parse(
+                "Expression is nested more than 3 levels deep -- found Identifier(\"a\")"
+                    .to_string()
+            ),
+            err.reason
+        );
+        let deep = format!("{}a{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(runtime.compile(&deep).is_err());
+        assert_eq!(
+            Limits::new().with_max_depth(3),
+            runtime.compile("a").unwrap().limits()
+        );
+    }
+
+    #[test]
+    fn searches_fail_after_deadline() {
+        let data = Variable::from_json("[[1, 2], [3]]").unwrap();
+        let mut expr = compile("[*][*]").unwrap();
+        for backend in [Backend::Interpreter, Backend::Bytecode] {
+            expr.set_backend(backend);
+            let ctx = expr.context().with_deadline(Instant::now());
+            let err = expr.search_with_context(&data, ctx).unwrap_err();
+            assert_eq!(ErrorReason::Runtime(RuntimeError::Timeout), err.reason);
+        }
+    }
+
+    #[test]
+    fn cancels_searches_while_iterating() {
+        let data = Variable::from_json(&format!("{:?}", vec![1; 100])).unwrap();
+        for backend in [Backend::Interpreter, Backend::Bytecode] {
+            // Each backend gets a fresh token, which is only cancelled while
+            // the search is iterating over the data.
+            let token = CancellationToken::new();
+            let mut runtime = Runtime::new();
+            runtime.register_builtin_functions();
+            let cancel = token.clone();
+            runtime.register_function(
+                "cancel",
+                Box::new(move |args: &[Rcvar], _: &mut Context<'_>| {
+                    cancel.cancel();
+                    Ok(args[0].clone())
+                }),
+            );
+            let mut expr = runtime.compile("map(&cancel(@), @)").unwrap();
+            expr.set_backend(backend);
+            let ctx = expr.context().with_cancellation(token.clone());
+            let err = expr.search_with_context(&data, ctx).unwrap_err();
+            assert_eq!(ErrorReason::Runtime(RuntimeError::Cancelled), err.reason);
+            assert!(err.offset > 0, "{:?} failed before iterating", backend);
+            let expr = runtime.compile("length(@)").unwrap();
+            assert_eq!("100", expr.search(&data).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn searches_borrowed_data_with_context() {
+        let data = Variable::from_json(r#"{"a": [1, 2]}"#).unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        let mut value = BTreeMap::new();
+        value.insert("a".to_string(), vec![1, 2]);
+        let expr = compile("a[?@ > $min]").unwrap();
+        let mut vars = BTreeMap::new();
+        vars.insert("min".to_string(), Rcvar::new(Variable::Number(1.into())));
+        let ctx = || expr.context().with_variables(vars.clone());
+        let expected = Variable::from_json("[2]").unwrap();
+        assert_eq!(
+            expected,
+            *expr.search_borrowed_with_context(&json, ctx()).unwrap()
+        );
+        assert_eq!(
+            expected,
+            *expr.search_searchable_with_context(&value, ctx()).unwrap()
+        );
+        let matches = expr.search_paths_with_context(&data, ctx()).unwrap();
+        assert_eq!(
+            vec!["a[1]"],
+            matches.iter().map(|m| m.jmespath()).collect::<Vec<_>>()
+        );
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let ctx = || expr.context().with_cancellation(token.clone());
+        let cancelled = ErrorReason::Runtime(RuntimeError::Cancelled);
+        let err = expr.search_borrowed_with_context(&json, ctx()).unwrap_err();
+        assert_eq!(cancelled, err.reason);
+        let err = expr
+            .search_searchable_with_context(&value, ctx())
+            .unwrap_err();
+        assert_eq!(cancelled, err.reason);
+        let err = expr.search_paths_with_context(&data, ctx()).unwrap_err();
+        assert_eq!(cancelled, err.reason);
+    }
+
+    #[test]
+    fn later_context_variables_shadow_earlier_ones() {
+        let runtime = Runtime::new();
+        let mut a = BTreeMap::new();
+        a.insert("x".to_string(), Rcvar::new(Variable::Bool(true)));
+        let mut b = BTreeMap::new();
+        b.insert("x".to_string(), Rcvar::new(Variable::Bool(false)));
+        let ctx = Context::new("$x", &runtime)
+            .with_variables(a)
+            .with_variables(b);
+        assert_eq!(
+            Some(Rcvar::new(Variable::Bool(false))),
+            ctx.get_variable("x")
+        );
+    }
+
+    #[cfg(feature = "serde-ast")]
+    #[test]
+    fn creates_expression_from_deserialized_ast() {
+        use crate::ast::VersionedAst;
+        let json = r#"{
+            "version": 1,
+            "ast": {
+                "type": "subexpr",
+                "offset": 0,
+                "lhs": {"type": "field", "offset": 0, "name": "foo"},
+                "rhs": {"type": "field", "offset": 4, "name": "bar"}
+            }
+        }"#;
+        let VersionedAst(ast) = serde_json::from_str(json).unwrap();
+        let runtime = Runtime::new();
+        let expr = Expression::new(ast.to_string(), ast, &runtime);
+        let data = Variable::from_json("{\"foo\": {\"bar\": true}}").unwrap();
+        assert_eq!("foo.bar", expr.as_str());
+        assert_eq!(Rcvar::new(Variable::Bool(true)), expr.search(data).unwrap());
+    }
+
     #[test]
     fn can_get_expression_ast() {
         let expr = compile("foo").unwrap();
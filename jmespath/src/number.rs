@@ -0,0 +1,380 @@
+//! Exact arithmetic and comparisons of JMESPath numbers.
+//!
+//! Integers are compared and combined as integers rather than being
+//! converted to `f64`, so large integers (e.g., 64-bit IDs) keep their
+//! exact value. Numbers that are not integers are `f64`s, or exact
+//! decimals when the `arbitrary_precision` feature is enabled.
+
+use std::cmp::Ordering;
+
+use serde_json::Number;
+
+use crate::ast::ArithmeticOperator;
+
+/// A number that is used in arithmetic or comparisons.
+#[derive(Clone, Debug)]
+pub enum Numeric {
+    Integer(i128),
+    Real(real::Real),
+}
+
+impl Numeric {
+    pub fn new(n: &Number) -> Numeric {
+        match n.as_i128() {
+            Some(i) => Numeric::Integer(i),
+            None => Numeric::Real(real::from_number(n)),
+        }
+    }
+
+    /// Returns the sum of numbers, which is an integer if all of the
+    /// numbers are integers and the sum does not overflow.
+    pub fn sum<'a, I: IntoIterator<Item = &'a Number>>(numbers: I) -> Numeric {
+        numbers
+            .into_iter()
+            .fold(Numeric::Integer(0), |sum, n| match (sum, Numeric::new(n)) {
+                (Numeric::Integer(a), Numeric::Integer(b)) if a.checked_add(b).is_some() => {
+                    Numeric::Integer(a + b)
+                }
+                (a, b) => Numeric::Real(real::add(a.into_real(), b.into_real())),
+            })
+    }
+
+    /// Converts the number to a JSON number, returning None if the number
+    /// is not finite.
+    pub fn into_number(self) -> Option<Number> {
+        match self {
+            Numeric::Integer(i) => {
+                Number::from_i128(i).or_else(|| real::to_number(real::from_integer(i)))
+            }
+            Numeric::Real(r) => real::to_number(r),
+        }
+    }
+
+    fn into_real(self) -> real::Real {
+        match self {
+            Numeric::Integer(i) => real::from_integer(i),
+            Numeric::Real(r) => r,
+        }
+    }
+
+    pub fn equals(&self, other: &Numeric) -> bool {
+        match (self, other) {
+            (Numeric::Integer(a), Numeric::Integer(b)) => a == b,
+            _ => real::equals(&self.clone().into_real(), &other.clone().into_real()),
+        }
+    }
+
+    pub fn compare(&self, other: &Numeric) -> Ordering {
+        match (self, other) {
+            (Numeric::Integer(a), Numeric::Integer(b)) => a.cmp(b),
+            _ => real::compare(&self.clone().into_real(), &other.clone().into_real()),
+        }
+    }
+
+    /// Applies an arithmetic operator, returning None if the result is
+    /// undefined (e.g., when dividing by zero).
+    ///
+    /// Integer operands produce an integer result unless the operator is
+    /// `Divide` or the result overflows.
+    pub fn arithmetic(self, operator: &ArithmeticOperator, other: Numeric) -> Option<Numeric> {
+        if let (Numeric::Integer(a), Numeric::Integer(b)) = (&self, &other) {
+            if let Some(result) = integer_arithmetic(operator, *a, *b) {
+                return Some(Numeric::Integer(result));
+            }
+        }
+        real::arithmetic(operator, self.into_real(), other.into_real()).map(Numeric::Real)
+    }
+
+    /// Returns the number with its sign flipped.
+    pub fn negate(self) -> Numeric {
+        match self {
+            Numeric::Integer(i) => match i.checked_neg() {
+                Some(n) => Numeric::Integer(n),
+                None => Numeric::Real(real::negate(real::from_integer(i))),
+            },
+            Numeric::Real(r) => Numeric::Real(real::negate(r)),
+        }
+    }
+
+    pub fn abs(self) -> Numeric {
+        match self {
+            Numeric::Integer(i) => Numeric::Integer(i.abs()),
+            Numeric::Real(r) => Numeric::Real(real::abs(r)),
+        }
+    }
+
+    pub fn ceil(self) -> Numeric {
+        match self {
+            Numeric::Integer(i) => Numeric::Integer(i),
+            Numeric::Real(r) => Numeric::Real(real::ceil(r)),
+        }
+    }
+
+    pub fn floor(self) -> Numeric {
+        match self {
+            Numeric::Integer(i) => Numeric::Integer(i),
+            Numeric::Real(r) => Numeric::Real(real::floor(r)),
+        }
+    }
+}
+
+fn integer_arithmetic(operator: &ArithmeticOperator, a: i128, b: i128) -> Option<i128> {
+    match *operator {
+        ArithmeticOperator::Add => a.checked_add(b),
+        ArithmeticOperator::Subtract => a.checked_sub(b),
+        ArithmeticOperator::Multiply => a.checked_mul(b),
+        ArithmeticOperator::Divide => None,
+        ArithmeticOperator::Modulo => a.checked_rem(b).map(|r| {
+            if r != 0 && (r < 0) != (b < 0) {
+                r + b
+            } else {
+                r
+            }
+        }),
+        ArithmeticOperator::IntegerDivide => a.checked_div(b).map(|q| {
+            if a % b != 0 && (a < 0) != (b < 0) {
+                q - 1
+            } else {
+                q
+            }
+        }),
+    }
+}
+
+/// Numbers that are not integers, represented as `f64`s.
+#[cfg(not(feature = "arbitrary_precision"))]
+mod real {
+    use std::cmp::Ordering;
+
+    use serde_json::Number;
+
+    use crate::ast::ArithmeticOperator;
+
+    pub type Real = f64;
+
+    pub fn from_number(n: &Number) -> Real {
+        n.as_f64().unwrap_or(f64::NAN)
+    }
+
+    pub fn from_integer(i: i128) -> Real {
+        i as f64
+    }
+
+    pub fn to_number(r: Real) -> Option<Number> {
+        Number::from_f64(r)
+    }
+
+    /// Compares two floats for equality.
+    ///
+    /// Allows for equivalence of floating point numbers like
+    /// 0.7100000000000002 and 0.71.
+    ///
+    /// Based on http://stackoverflow.com/a/4915891
+    pub fn equals(a: &Real, b: &Real) -> bool {
+        let (a, b) = (*a, *b);
+        let abs_a = a.abs();
+        let abs_b = b.abs();
+        let diff = (a - b).abs();
+        if a == b {
+            true
+        } else if !a.is_normal() || !b.is_normal() {
+            // a or b is zero or both are extremely close to it
+            // relative error is less meaningful here.
+            diff < (f64::EPSILON * f64::MIN_POSITIVE)
+        } else {
+            // use relative error.
+            diff / (abs_a + abs_b) < f64::EPSILON
+        }
+    }
+
+    pub fn compare(a: &Real, b: &Real) -> Ordering {
+        a.partial_cmp(b).unwrap_or(Ordering::Less)
+    }
+
+    pub fn add(a: Real, b: Real) -> Real {
+        a + b
+    }
+
+    pub fn arithmetic(operator: &ArithmeticOperator, a: Real, b: Real) -> Option<Real> {
+        let result = match *operator {
+            ArithmeticOperator::Add => a + b,
+            ArithmeticOperator::Subtract => a - b,
+            ArithmeticOperator::Multiply => a * b,
+            ArithmeticOperator::Divide => a / b,
+            ArithmeticOperator::Modulo => {
+                let r = a % b;
+                if r != 0.0 && (r < 0.0) != (b < 0.0) {
+                    r + b
+                } else {
+                    r
+                }
+            }
+            ArithmeticOperator::IntegerDivide => (a / b).floor(),
+        };
+        Some(result).filter(|r| r.is_finite())
+    }
+
+    pub fn negate(r: Real) -> Real {
+        -r
+    }
+
+    pub fn abs(r: Real) -> Real {
+        r.abs()
+    }
+
+    pub fn ceil(r: Real) -> Real {
+        r.ceil()
+    }
+
+    pub fn floor(r: Real) -> Real {
+        r.floor()
+    }
+}
+
+/// Numbers that are not integers, represented as exact decimals.
+///
+/// Division is rounded to the precision used by `bigdecimal` (100 digits
+/// unless `RUST_BIGDECIMAL_DEFAULT_PRECISION` is set when building).
+#[cfg(feature = "arbitrary_precision")]
+mod real {
+    use std::cmp::Ordering;
+    use std::str::FromStr;
+
+    use bigdecimal::{BigDecimal, RoundingMode, Zero};
+    use serde_json::Number;
+
+    use crate::ast::ArithmeticOperator;
+
+    pub type Real = BigDecimal;
+
+    pub fn from_number(n: &Number) -> Real {
+        BigDecimal::from_str(&n.to_string()).expect("JSON numbers are valid decimals")
+    }
+
+    pub fn from_integer(i: i128) -> Real {
+        BigDecimal::from(i)
+    }
+
+    pub fn to_number(r: Real) -> Option<Number> {
+        Number::from_str(&r.to_string()).ok()
+    }
+
+    pub fn equals(a: &Real, b: &Real) -> bool {
+        a == b
+    }
+
+    pub fn compare(a: &Real, b: &Real) -> Ordering {
+        a.cmp(b)
+    }
+
+    pub fn add(a: Real, b: Real) -> Real {
+        a + b
+    }
+
+    pub fn arithmetic(operator: &ArithmeticOperator, a: Real, b: Real) -> Option<Real> {
+        match *operator {
+            ArithmeticOperator::Add => Some(a + b),
+            ArithmeticOperator::Subtract => Some(a - b),
+            ArithmeticOperator::Multiply => Some(a * b),
+            _ if b.is_zero() => None,
+            ArithmeticOperator::Divide => Some(a / b),
+            ArithmeticOperator::Modulo => {
+                let r = &a % &b;
+                if !r.is_zero() && (r < Real::zero()) != (b < Real::zero()) {
+                    Some(r + b)
+                } else {
+                    Some(r)
+                }
+            }
+            ArithmeticOperator::IntegerDivide => Some(floor(a / b)),
+        }
+    }
+
+    pub fn negate(r: Real) -> Real {
+        -r
+    }
+
+    pub fn abs(r: Real) -> Real {
+        r.abs()
+    }
+
+    pub fn ceil(r: Real) -> Real {
+        r.with_scale_round(0, RoundingMode::Ceiling)
+    }
+
+    pub fn floor(r: Real) -> Real {
+        r.with_scale_round(0, RoundingMode::Floor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn numeric(json: &str) -> Numeric {
+        Numeric::new(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn compares_large_integers_exactly() {
+        let a = numeric("9007199254740993");
+        let b = numeric("9007199254740992");
+        assert!(!a.equals(&b));
+        assert_eq!(Ordering::Greater, a.compare(&b));
+        assert_eq!(
+            Ordering::Greater,
+            numeric("18446744073709551615").compare(&numeric("18446744073709551614"))
+        );
+        assert!(numeric("2").equals(&numeric("2.0")));
+    }
+
+    #[test]
+    fn sums_integers_exactly() {
+        let numbers: Vec<Number> = vec![
+            Number::from(i64::MAX),
+            Number::from(i64::MAX),
+            Number::from(-i64::MAX),
+        ];
+        assert_eq!(
+            Some(Number::from(i64::MAX)),
+            Numeric::sum(&numbers).into_number()
+        );
+        let mixed = vec![Number::from(1), Number::from_f64(0.5).unwrap()];
+        assert_eq!(
+            Some(Number::from_f64(1.5).unwrap()),
+            Numeric::sum(&mixed).into_number()
+        );
+    }
+
+    #[test]
+    fn integer_results_can_exceed_i64() {
+        let max = numeric("18446744073709551615");
+        let result = max.arithmetic(&ArithmeticOperator::Subtract, numeric("1"));
+        assert_eq!(
+            Some(Number::from(u64::MAX - 1)),
+            result.and_then(Numeric::into_number)
+        );
+        assert_eq!(
+            Some(Number::from(1u64 << 63)),
+            numeric("-9223372036854775808").abs().into_number()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn uses_exact_decimals() {
+        let sum = numeric("0.1").arithmetic(&ArithmeticOperator::Add, numeric("0.2"));
+        assert!(sum.unwrap().equals(&numeric("0.3")));
+        assert_eq!(
+            "-3",
+            numeric("-2.5").floor().into_number().unwrap().to_string()
+        );
+        let product = numeric("12345678901234567890.5")
+            .arithmetic(&ArithmeticOperator::Multiply, numeric("2"))
+            .unwrap();
+        assert_eq!(
+            "24691357802469135781.0",
+            product.into_number().unwrap().to_string()
+        );
+    }
+}
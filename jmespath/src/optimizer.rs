@@ -0,0 +1,273 @@
+//! Simplifies ASTs before they are interpreted.
+//!
+//! Optimizations never change the result of an expression, including the
+//! errors that it raises. Subtrees that would fail when evaluated are left
+//! unchanged so that they fail when the expression is searched.
+
+use crate::ast::{fold_ast, walk_ast, Ast, Fold, Visitor};
+use crate::interpreter::interpret;
+use crate::variable::Variable;
+use crate::{Context, Rcvar, Runtime};
+
+/// Returns an optimized version of the AST.
+pub fn optimize(ast: Ast, runtime: &Runtime) -> Ast {
+    Optimizer { runtime }.fold_ast(ast)
+}
+
+struct Optimizer<'a> {
+    runtime: &'a Runtime,
+}
+
+impl Fold for Optimizer<'_> {
+    fn fold_ast(&mut self, node: Ast) -> Ast {
+        let node = simplify(fold_ast(self, node));
+        if self.is_constant(&node) {
+            self.evaluate(node)
+        } else {
+            node
+        }
+    }
+}
+
+impl Optimizer<'_> {
+    /// Returns true if a node does not depend on the data being searched
+    /// and can be replaced with the literal that it evaluates to.
+    fn is_constant(&self, node: &Ast) -> bool {
+        match *node {
+            Ast::Comparison {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Arithmetic {
+                ref lhs, ref rhs, ..
+            } => is_literal(lhs) && is_literal(rhs),
+            Ast::Not { ref node, .. }
+            | Ast::UnaryArithmetic { ref node, .. }
+            | Ast::Flatten { ref node, .. }
+            | Ast::ObjectValues { ref node, .. } => is_literal(node),
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Projection {
+                ref lhs, ref rhs, ..
+            } => is_literal(lhs) && self.is_closed(rhs),
+            Ast::Function { ref args, .. } => {
+                self.is_closed(node)
+                    && args.iter().all(|arg| match *arg {
+                        Ast::Expref { .. } => true,
+                        _ => is_literal(arg),
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if a node only depends on the value it is evaluated
+    /// against, meaning it does not reference the root node or variables
+    /// and only calls pure functions.
+    fn is_closed(&self, node: &Ast) -> bool {
+        let mut checker = ClosedChecker {
+            runtime: self.runtime,
+            closed: true,
+        };
+        checker.visit_ast(node);
+        checker.closed
+    }
+
+    /// Replaces a node with a literal of its result, or returns the node
+    /// unchanged if evaluating it fails, including when the result exceeds
+    /// the limits of the runtime.
+    fn evaluate(&self, node: Ast) -> Ast {
+        let mut ctx = Context::new("", self.runtime).with_limits(self.runtime.limits());
+        match interpret(&Rcvar::new(Variable::Null), &node, &mut ctx) {
+            Ok(ref value) if value.is_expref() => node,
+            Ok(value) => Ast::Literal {
+                offset: node.offset(),
+                value,
+            },
+            Err(_) => node,
+        }
+    }
+}
+
+/// Applies simplifications that do not require evaluating the node.
+fn simplify(node: Ast) -> Ast {
+    match node {
+        // `@.foo` and `foo | @` are equivalent to `foo`.
+        Ast::Subexpr { lhs, rhs, .. } if is_identity(&lhs) => *rhs,
+        Ast::Subexpr { lhs, rhs, .. } if is_identity(&rhs) => *lhs,
+        // `foo | [*].bar` is equivalent to `foo[*].bar`.
+        Ast::Subexpr { offset, lhs, rhs } => match *rhs {
+            Ast::Projection {
+                lhs: projected,
+                rhs: then,
+                ..
+            } if is_identity(&projected) => simplify(Ast::Projection {
+                offset,
+                lhs,
+                rhs: then,
+            }),
+            rhs => Ast::Subexpr {
+                offset,
+                lhs,
+                rhs: Box::new(rhs),
+            },
+        },
+        // Projections only produce arrays without null values, so
+        // projecting the result again without a right hand side is a no-op.
+        Ast::Projection { lhs, rhs, .. } if is_identity(&rhs) && is_projection(&lhs) => *lhs,
+        Ast::Or { lhs, rhs, .. } if is_literal(&lhs) => {
+            if is_truthy(&lhs) {
+                *lhs
+            } else {
+                *rhs
+            }
+        }
+        Ast::And { lhs, rhs, .. } if is_literal(&lhs) => {
+            if is_truthy(&lhs) {
+                *rhs
+            } else {
+                *lhs
+            }
+        }
+        Ast::Condition {
+            offset,
+            predicate,
+            then,
+        } if is_literal(&predicate) => {
+            if is_truthy(&predicate) {
+                *then
+            } else {
+                Ast::Literal {
+                    offset,
+                    value: Rcvar::new(Variable::Null),
+                }
+            }
+        }
+        node => node,
+    }
+}
+
+fn is_identity(node: &Ast) -> bool {
+    matches!(*node, Ast::Identity { .. })
+}
+
+fn is_literal(node: &Ast) -> bool {
+    matches!(*node, Ast::Literal { .. })
+}
+
+fn is_truthy(node: &Ast) -> bool {
+    match *node {
+        Ast::Literal { ref value, .. } => value.is_truthy(),
+        _ => false,
+    }
+}
+
+fn is_projection(node: &Ast) -> bool {
+    matches!(*node, Ast::Projection { .. })
+}
+
+/// Determines if a node only depends on the value it is evaluated against.
+struct ClosedChecker<'a> {
+    runtime: &'a Runtime,
+    closed: bool,
+}
+
+impl Visitor for ClosedChecker<'_> {
+    fn visit_ast(&mut self, node: &Ast) {
+        match *node {
+            Ast::Root { .. } | Ast::VariableRef { .. } | Ast::Let { .. } => self.closed = false,
+            Ast::Function { ref name, .. } => {
+                let pure = self.runtime.get_function(name).is_some_and(|f| f.is_pure());
+                if pure {
+                    walk_ast(self, node);
+                } else {
+                    self.closed = false;
+                }
+            }
+            _ => walk_ast(self, node),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse, Limits};
+
+    fn optimized(expression: &str) -> String {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        optimize(parse(expression).unwrap(), &runtime).to_string()
+    }
+
+    #[test]
+    fn folds_constant_subtrees() {
+        assert_eq!("`true`", optimized("`1` < `2`"));
+        assert_eq!("`false`", optimized("!`[1]`"));
+        assert_eq!("`3`", optimized("`1` + `2`"));
+        assert_eq!("`-3`", optimized("-(`1` + `2`)"));
+        assert_eq!("'b'", optimized("not_null(`null`, 'b', 'c')"));
+        assert_eq!("`4`", optimized("length(join(',', `[\"ab\", \"c\"]`))"));
+        assert_eq!("`[1,2]`", optimized("`[{\"a\": 1}, {\"a\": 2}]`[*].a"));
+        assert_eq!(
+            "`[\"b\",\"a\"]`",
+            optimized("sort_by(`[\"b\", \"a\"]`, &@)[::-1]")
+        );
+        assert_eq!("foo[*].bar", optimized("foo[?`1` == `1`].bar"));
+    }
+
+    #[test]
+    fn does_not_fold_data_dependent_nodes() {
+        assert_eq!("[`1`]", optimized("[`1`]"));
+        assert_eq!("{a: `1`}", optimized("{a: `1`}"));
+        assert_eq!("length(@)", optimized("length(@)"));
+        assert_eq!("`{\"a\":1}` | $.a", optimized("`{\"a\": 1}` | $.a"));
+        assert_eq!(
+            "let $x = `1` in $x == `1`",
+            optimized("let $x = `1` in $x == `1`")
+        );
+        assert_eq!("unknown(`1`)", optimized("unknown(`1`)"));
+    }
+
+    #[test]
+    fn does_not_fold_errors() {
+        assert_eq!("abs('a')", optimized("abs('a')"));
+        assert_eq!("`[1]`[::0]", optimized("`[1]`[::0]"));
+    }
+
+    #[test]
+    fn does_not_fold_nodes_that_exceed_limits() {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        runtime.set_limits(Limits::new().with_max_size(2));
+        let ast = optimize(parse("`[1, 2, 3]`[*]").unwrap(), &runtime);
+        assert_eq!("`[1,2,3]`[*]", ast.to_string());
+        let ast = optimize(parse("`[1, 2]`[*]").unwrap(), &runtime);
+        assert_eq!("`[1,2]`", ast.to_string());
+    }
+
+    #[test]
+    fn removes_redundant_nodes() {
+        assert_eq!("foo", optimized("@.foo"));
+        assert_eq!("foo", optimized("foo | @"));
+        assert_eq!("foo[*].bar", optimized("foo | [*].bar"));
+        assert_eq!("foo[*].bar", optimized("(foo[*].bar)[*]"));
+        assert_eq!("foo[?a].b", optimized("foo | [?a].b"));
+        assert_eq!("a", optimized("`false` || a"));
+        assert_eq!("`1`", optimized("`1` || a"));
+        assert_eq!("a", optimized("`true` && a"));
+        assert_eq!("foo[*].bar", optimized("foo[?`true`].bar"));
+    }
+
+    #[test]
+    fn does_not_fold_impure_functions() {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        runtime.register_function(
+            "length",
+            Box::new(|_: &[Rcvar], _: &mut Context<'_>| Ok(Rcvar::new(Variable::Null))),
+        );
+        let ast = optimize(parse("length('abc')").unwrap(), &runtime);
+        assert_eq!("length('abc')", ast.to_string());
+    }
+}
@@ -6,7 +6,9 @@
 
 use std::collections::VecDeque;
 
-use crate::ast::{Ast, Comparator, KeyValuePair};
+use crate::ast::{
+    ArithmeticOperator, Ast, Comparator, KeyValuePair, UnaryOperator, VariableBinding,
+};
 use crate::lexer::{tokenize, Token, TokenTuple};
 use crate::{ErrorReason, JmespathError};
 
@@ -15,12 +17,26 @@ pub type ParseResult = Result<Ast, JmespathError>;
 
 /// Parses a JMESPath expression into an AST.
 pub fn parse(expr: &str) -> ParseResult {
+    parse_with_max_depth(expr, None)
+}
+
+/// Parses a JMESPath expression, failing if expressions are nested more
+/// deeply than the given depth.
+pub(crate) fn parse_with_max_depth(expr: &str, max_depth: Option<usize>) -> ParseResult {
     let tokens = tokenize(expr)?;
-    Parser::new(tokens, expr).parse()
+    Parser::new(tokens, expr, max_depth).parse()
 }
 
 /// The maximum binding power for a token that can stop a projection.
-const PROJECTION_STOP: usize = 10;
+pub(crate) const PROJECTION_STOP: usize = 10;
+
+/// The binding power used to parse the right hand side of wildcard and
+/// slice projections.
+pub(crate) const WILDCARD_BP: usize = 20;
+
+/// The binding power used to parse the operand of a unary arithmetic
+/// operator, which binds more tightly than any binary operator.
+pub(crate) const UNARY_BP: usize = 8;
 
 struct Parser<'a> {
     /// Parsed tokens
@@ -31,15 +47,21 @@ struct Parser<'a> {
     expr: &'a str,
     /// The current character offset in the expression
     offset: usize,
+    /// Current depth of nested expressions
+    depth: usize,
+    /// Maximum depth of nested expressions
+    max_depth: Option<usize>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: VecDeque<TokenTuple>, expr: &'a str) -> Parser<'a> {
+    fn new(tokens: VecDeque<TokenTuple>, expr: &'a str, max_depth: Option<usize>) -> Parser<'a> {
         Parser {
             token_queue: tokens,
             eof_token: Token::Eof,
             offset: 0,
             expr,
+            depth: 0,
+            max_depth,
         }
     }
 
@@ -73,7 +95,7 @@ impl<'a> Parser<'a> {
     #[inline]
     fn peek(&self, lookahead: usize) -> &Token {
         match self.token_queue.get(lookahead) {
-            Some(&(_, ref t)) => t,
+            Some((_, t)) => t,
             None => &self.eof_token,
         }
     }
@@ -84,19 +106,27 @@ impl<'a> Parser<'a> {
         let mut buff = error_msg.to_string();
         buff.push_str(&format!(" -- found {:?}", current_token));
         if is_peek {
-            if let Some(&(p, _)) = self.token_queue.get(0) {
+            if let Some(&(p, _)) = self.token_queue.front() {
                 actual_pos = p;
             }
         }
-        JmespathError::new(&self.expr, actual_pos, ErrorReason::Parse(buff))
+        JmespathError::new(self.expr, actual_pos, ErrorReason::Parse(buff))
     }
 
     /// Main parse function of the Pratt parser that parses while RBP < LBP
     fn expr(&mut self, rbp: usize) -> ParseResult {
+        if let Some(max_depth) = self.max_depth {
+            if self.depth >= max_depth {
+                let msg = format!("Expression is nested more than {} levels deep", max_depth);
+                return Err(self.err(self.peek(0), &msg, true));
+            }
+        }
+        self.depth += 1;
         let mut left = self.nud();
         while rbp < self.peek(0).lbp() {
             left = self.led(Box::new(left?));
         }
+        self.depth -= 1;
         left
     }
 
@@ -104,10 +134,16 @@ impl<'a> Parser<'a> {
         let (offset, token) = self.advance_with_pos();
         match token {
             Token::At => Ok(Ast::Identity { offset }),
+            Token::Root => Ok(Ast::Root { offset }),
+            // "let" is only a keyword when followed by a variable binding.
+            Token::Identifier(ref value) if value == "let" && self.is_variable(0) => {
+                self.parse_let(offset)
+            }
             Token::Identifier(value) => Ok(Ast::Field {
                 name: value,
                 offset,
             }),
+            Token::Variable(name) => Ok(Ast::VariableRef { name, offset }),
             Token::QuotedIdentifier(value) => match self.peek(0) {
                 Token::Lparen => {
                     let message = "Quoted strings can't be a function name";
@@ -154,6 +190,8 @@ impl<'a> Parser<'a> {
                     offset,
                 })
             }
+            Token::Plus => self.parse_unary_arithmetic(UnaryOperator::Plus, offset),
+            Token::Minus => self.parse_unary_arithmetic(UnaryOperator::Minus, offset),
             t @ Token::Not => Ok(Ast::Not {
                 node: Box::new(self.expr(t.lbp())?),
                 offset,
@@ -179,7 +217,6 @@ impl<'a> Parser<'a> {
                     self.advance();
                     self.parse_wildcard_values(left)
                 } else {
-                    let offset = offset;
                     let rhs = self.parse_dot(t.lbp())?;
                     Ok(Ast::Subexpr {
                         offset,
@@ -205,7 +242,6 @@ impl<'a> Parser<'a> {
                 }
             }
             t @ Token::Or => {
-                let offset = offset;
                 let rhs = self.expr(t.lbp())?;
                 Ok(Ast::Or {
                     offset,
@@ -214,7 +250,6 @@ impl<'a> Parser<'a> {
                 })
             }
             t @ Token::And => {
-                let offset = offset;
                 let rhs = self.expr(t.lbp())?;
                 Ok(Ast::And {
                     offset,
@@ -223,7 +258,6 @@ impl<'a> Parser<'a> {
                 })
             }
             t @ Token::Pipe => {
-                let offset = offset;
                 let rhs = self.expr(t.lbp())?;
                 Ok(Ast::Subexpr {
                     offset,
@@ -247,6 +281,20 @@ impl<'a> Parser<'a> {
             Token::Gte => self.parse_comparator(Comparator::GreaterThanEqual, left),
             Token::Lt => self.parse_comparator(Comparator::LessThan, left),
             Token::Lte => self.parse_comparator(Comparator::LessThanEqual, left),
+            t @ Token::Plus => self.parse_arithmetic(ArithmeticOperator::Add, &t, offset, left),
+            t @ Token::Minus => {
+                self.parse_arithmetic(ArithmeticOperator::Subtract, &t, offset, left)
+            }
+            t @ Token::Star | t @ Token::Multiply => {
+                self.parse_arithmetic(ArithmeticOperator::Multiply, &t, offset, left)
+            }
+            t @ Token::Slash => self.parse_arithmetic(ArithmeticOperator::Divide, &t, offset, left),
+            t @ Token::Percent => {
+                self.parse_arithmetic(ArithmeticOperator::Modulo, &t, offset, left)
+            }
+            t @ Token::DoubleSlash => {
+                self.parse_arithmetic(ArithmeticOperator::IntegerDivide, &t, offset, left)
+            }
             ref t => Err(self.err(t, "Unexpected led token", false)),
         }
     }
@@ -268,6 +316,41 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Returns true if the token at the given lookahead is a variable reference.
+    #[inline]
+    fn is_variable(&self, lookahead: usize) -> bool {
+        matches!(self.peek(lookahead), Token::Variable(_))
+    }
+
+    /// Parses a let expression (e.g., "let $foo = bar, $baz = qux in expr")
+    fn parse_let(&mut self, offset: usize) -> ParseResult {
+        let mut bindings = vec![];
+        loop {
+            match self.advance() {
+                Token::Variable(name) => match self.advance() {
+                    Token::Assign => bindings.push(VariableBinding {
+                        name,
+                        value: self.expr(0)?,
+                    }),
+                    ref t => return Err(self.err(t, "Expected '=' to follow variable", false)),
+                },
+                ref t => return Err(self.err(t, "Expected variable binding", false)),
+            }
+            match self.advance() {
+                // Skip commas as they are used to delineate bindings
+                Token::Comma => continue,
+                // Terminal condition is the "in" keyword
+                Token::Identifier(ref value) if value == "in" => break,
+                ref t => return Err(self.err(t, "Expected 'in' or ','", false)),
+            }
+        }
+        Ok(Ast::Let {
+            offset,
+            bindings,
+            expr: Box::new(self.expr(0)?),
+        })
+    }
+
     /// Parses a filter token into a Projection that filters the right
     /// side of the projection using a Condition node. If the Condition node
     /// returns a truthy value, then the value is yielded by the projection.
@@ -315,6 +398,33 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses an arithmetic operator token into an Arithmetic node (e.g., foo + bar)
+    fn parse_arithmetic(
+        &mut self,
+        operator: ArithmeticOperator,
+        token: &Token,
+        offset: usize,
+        lhs: Box<Ast>,
+    ) -> ParseResult {
+        let rhs = Box::new(self.expr(token.lbp())?);
+        Ok(Ast::Arithmetic {
+            offset,
+            operator,
+            lhs,
+            rhs,
+        })
+    }
+
+    /// Parses the operand of a unary arithmetic operator (e.g., -foo)
+    fn parse_unary_arithmetic(&mut self, operator: UnaryOperator, offset: usize) -> ParseResult {
+        let node = Box::new(self.expr(UNARY_BP)?);
+        Ok(Ast::UnaryArithmetic {
+            offset,
+            operator,
+            node,
+        })
+    }
+
     /// Parses the right hand side of a dot expression.
     fn parse_dot(&mut self, lbp: usize) -> ParseResult {
         if match self.peek(0) {
@@ -339,12 +449,12 @@ impl<'a> Parser<'a> {
         if match self.peek(0) {
             &Token::Dot => true,
             &Token::Lbracket | &Token::Filter => false,
-            ref t if t.lbp() < PROJECTION_STOP => {
+            t if t.lbp() < PROJECTION_STOP => {
                 return Ok(Ast::Identity {
                     offset: self.offset,
                 });
             }
-            ref t => {
+            t => {
                 return Err(self.err(t, "Expected '.', '[', or '[?'", true));
             }
         } {
@@ -359,7 +469,7 @@ impl<'a> Parser<'a> {
     fn parse_wildcard_index(&mut self, lhs: Box<Ast>) -> ParseResult {
         match self.advance() {
             Token::Rbracket => {
-                let rhs = Box::new(self.projection_rhs(Token::Star.lbp())?);
+                let rhs = Box::new(self.projection_rhs(WILDCARD_BP)?);
                 Ok(Ast::Projection {
                     offset: self.offset,
                     lhs,
@@ -372,7 +482,7 @@ impl<'a> Parser<'a> {
 
     /// Creates a projection for "*"
     fn parse_wildcard_values(&mut self, lhs: Box<Ast>) -> ParseResult {
-        let rhs = Box::new(self.projection_rhs(Token::Star.lbp())?);
+        let rhs = Box::new(self.projection_rhs(WILDCARD_BP)?);
         Ok(Ast::Projection {
             offset: self.offset,
             lhs: Box::new(Ast::ObjectValues {
@@ -404,7 +514,7 @@ impl<'a> Parser<'a> {
                     pos += 1;
                     match self.peek(0) {
                         &Token::Number(_) | &Token::Colon | &Token::Rbracket => continue,
-                        ref t => return Err(self.err(t, "Expected number, ':', or ']'", true)),
+                        t => return Err(self.err(t, "Expected number, ':', or ']'", true)),
                     };
                 }
                 ref t => return Err(self.err(t, "Expected number, ':', or ']'", false)),
@@ -417,7 +527,7 @@ impl<'a> Parser<'a> {
                 offset: self.offset,
                 idx: parts[0].ok_or_else(|| {
                     JmespathError::new(
-                        &self.expr,
+                        self.expr,
                         self.offset,
                         ErrorReason::Parse(
                             "Expected parts[0] to be Some; but found None".to_owned(),
@@ -435,7 +545,7 @@ impl<'a> Parser<'a> {
                     stop: parts[1],
                     step: parts[2].unwrap_or(1),
                 }),
-                rhs: Box::new(self.projection_rhs(Token::Star.lbp())?),
+                rhs: Box::new(self.projection_rhs(WILDCARD_BP)?),
             })
         }
     }
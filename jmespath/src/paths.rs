@@ -0,0 +1,425 @@
+//! Finds the locations of the values selected by an expression.
+//!
+//! Only expressions that select parts of a document (fields, indexes,
+//! slices, wildcards, flattens, filters, and pipes of them) are supported,
+//! since values produced by other expressions (e.g., function calls) do not
+//! have a location in the document.
+
+use std::fmt::Write;
+
+use crate::ast::{push_identifier, Ast};
+use crate::interpreter::interpret;
+use crate::variable::slice_indices;
+use crate::{Context, ErrorReason, JmespathError, Rcvar, RuntimeError, Variable};
+
+/// A step of the path from the root of a document to a value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PathSegment {
+    /// Field of an object.
+    Field(String),
+    /// Element of an array.
+    Index(usize),
+}
+
+/// A value selected by an expression and its path in the searched document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathMatch {
+    /// Path from the root of the document to the value.
+    pub path: Vec<PathSegment>,
+    /// Selected value.
+    pub value: Rcvar,
+}
+
+impl PathMatch {
+    /// Returns the path as a JSON Pointer (RFC 6901), e.g.,
+    /// `/reservations/0/instances/2/id`.
+    pub fn json_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.path {
+            pointer.push('/');
+            match *segment {
+                PathSegment::Field(ref name) => {
+                    pointer.push_str(&name.replace('~', "~0").replace('/', "~1"))
+                }
+                PathSegment::Index(i) => write!(pointer, "{}", i).expect("writes to a String"),
+            }
+        }
+        pointer
+    }
+
+    /// Returns the path as a JMESPath expression that only selects the
+    /// value, e.g., `reservations[0].instances[2].id`.
+    pub fn jmespath(&self) -> String {
+        if self.path.is_empty() {
+            return "@".to_owned();
+        }
+        let mut expression = String::new();
+        for segment in &self.path {
+            match *segment {
+                PathSegment::Field(ref name) => {
+                    if !expression.is_empty() {
+                        expression.push('.');
+                    }
+                    push_identifier(&mut expression, name);
+                }
+                PathSegment::Index(i) => write!(expression, "[{}]", i).expect("writes to a String"),
+            }
+        }
+        expression
+    }
+}
+
+/// Returns the non-null values selected by an AST node and their paths.
+///
+/// Values selected by nested projections are flattened into a single list.
+pub fn search_paths(
+    data: &Rcvar,
+    ast: &Ast,
+    ctx: &mut Context<'_>,
+) -> Result<Vec<PathMatch>, JmespathError> {
+    if let Some(node) = unsupported(ast) {
+        return Err(JmespathError::new(
+            ctx.expression,
+            node.offset(),
+            ErrorReason::Runtime(RuntimeError::UnsupportedPathExpression),
+        ));
+    }
+    let result = locate(Node::Located(vec![], data.clone()), ast, ctx)?;
+    let mut matches = vec![];
+    result.collect(&mut matches);
+    Ok(matches)
+}
+
+/// Returns the first node that can produce values that are not part of
+/// the searched document, if any.
+fn unsupported(node: &Ast) -> Option<&Ast> {
+    match *node {
+        Ast::Field { .. }
+        | Ast::Index { .. }
+        | Ast::Identity { .. }
+        | Ast::Root { .. }
+        | Ast::Slice { .. } => None,
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        }
+        | Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => unsupported(lhs).or_else(|| unsupported(rhs)),
+        Ast::ObjectValues { ref node, .. } | Ast::Flatten { ref node, .. } => unsupported(node),
+        // Predicates of filters are only used to select values.
+        Ast::Condition { ref then, .. } => unsupported(then),
+        _ => Some(node),
+    }
+}
+
+/// Returns the position of an index, which counts from the end of the
+/// array if it is negative, if it is within an array of the given length.
+fn resolve_index(len: usize, idx: i32) -> Option<usize> {
+    if idx >= 0 {
+        Some(idx as usize).filter(|&i| i < len)
+    } else {
+        len.checked_sub((-idx) as usize)
+    }
+}
+
+/// The result of evaluating a node.
+#[derive(Clone)]
+enum Node {
+    /// A value of the document and its path.
+    Located(Vec<PathSegment>, Rcvar),
+    /// An array of nodes produced by a projection, flatten, or slice.
+    Array(Vec<Node>),
+}
+
+impl Node {
+    fn null() -> Node {
+        Node::Located(vec![], Rcvar::new(Variable::Null))
+    }
+
+    fn is_null(&self) -> bool {
+        match *self {
+            Node::Located(_, ref v) => v.is_null(),
+            Node::Array(_) => false,
+        }
+    }
+
+    fn child(path: &[PathSegment], segment: PathSegment, value: Rcvar) -> Node {
+        let mut path = path.to_vec();
+        path.push(segment);
+        Node::Located(path, value)
+    }
+
+    fn get_field(&self, name: &str) -> Node {
+        match *self {
+            Node::Located(ref path, ref v) => match v.as_object().and_then(|o| o.get(name)) {
+                Some(value) => {
+                    Node::child(path, PathSegment::Field(name.to_owned()), value.clone())
+                }
+                None => Node::null(),
+            },
+            Node::Array(_) => Node::null(),
+        }
+    }
+
+    fn get_index(&self, idx: i32) -> Node {
+        match *self {
+            Node::Located(ref path, ref v) => match v.as_array() {
+                Some(a) => match resolve_index(a.len(), idx) {
+                    Some(i) => Node::child(path, PathSegment::Index(i), a[i].clone()),
+                    None => Node::null(),
+                },
+                None => Node::null(),
+            },
+            Node::Array(ref v) => {
+                resolve_index(v.len(), idx).map_or_else(Node::null, |i| v[i].clone())
+            }
+        }
+    }
+
+    fn elements(&self) -> Option<Vec<Node>> {
+        match *self {
+            Node::Located(ref path, ref v) => v.as_array().map(|a| {
+                a.iter()
+                    .enumerate()
+                    .map(|(i, value)| Node::child(path, PathSegment::Index(i), value.clone()))
+                    .collect()
+            }),
+            Node::Array(ref v) => Some(v.clone()),
+        }
+    }
+
+    fn values(&self) -> Option<Vec<Node>> {
+        match *self {
+            Node::Located(ref path, ref v) => v.as_object().map(|o| {
+                o.iter()
+                    .map(|(key, value)| {
+                        Node::child(path, PathSegment::Field(key.clone()), value.clone())
+                    })
+                    .collect()
+            }),
+            Node::Array(_) => None,
+        }
+    }
+
+    fn to_rcvar(&self) -> Rcvar {
+        match *self {
+            Node::Located(_, ref v) => v.clone(),
+            Node::Array(ref v) => {
+                Rcvar::new(Variable::Array(v.iter().map(Node::to_rcvar).collect()))
+            }
+        }
+    }
+
+    fn collect(self, matches: &mut Vec<PathMatch>) {
+        match self {
+            Node::Located(path, value) => {
+                if !value.is_null() {
+                    matches.push(PathMatch { path, value });
+                }
+            }
+            Node::Array(nodes) => {
+                for node in nodes {
+                    node.collect(matches);
+                }
+            }
+        }
+    }
+}
+
+fn locate(data: Node, node: &Ast, ctx: &mut Context<'_>) -> Result<Node, JmespathError> {
+    ctx.enter(node.offset())?;
+    let result = locate_node(data, node, ctx);
+    ctx.exit();
+    result
+}
+
+fn locate_node(data: Node, node: &Ast, ctx: &mut Context<'_>) -> Result<Node, JmespathError> {
+    match *node {
+        Ast::Field { ref name, .. } => Ok(data.get_field(name)),
+        Ast::Index { idx, .. } => Ok(data.get_index(idx)),
+        Ast::Identity { .. } => Ok(data),
+        Ast::Root { .. } => Ok(Node::Located(
+            vec![],
+            ctx.root()
+                .cloned()
+                .unwrap_or_else(|| Rcvar::new(Variable::Null)),
+        )),
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            let left = locate(data, lhs, ctx)?;
+            locate(left, rhs, ctx)
+        }
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => match locate(data, lhs, ctx)?.elements() {
+            None => Ok(Node::null()),
+            Some(elements) => {
+                let mut collected = vec![];
+                for element in elements {
+                    let current = locate(element, rhs, ctx)?;
+                    if !current.is_null() {
+                        collected.push(current);
+                    }
+                }
+                Ok(Node::Array(collected))
+            }
+        },
+        Ast::ObjectValues { ref node, .. } => Ok(locate(data, node, ctx)?
+            .values()
+            .map_or_else(Node::null, Node::Array)),
+        Ast::Flatten { ref node, .. } => match locate(data, node, ctx)?.elements() {
+            None => Ok(Node::null()),
+            Some(elements) => {
+                let mut collected = vec![];
+                for element in elements {
+                    match element.elements() {
+                        Some(nested) => collected.extend(nested),
+                        None => collected.push(element),
+                    }
+                }
+                Ok(Node::Array(collected))
+            }
+        },
+        Ast::Slice {
+            start,
+            stop,
+            step,
+            offset,
+        } => {
+            if step == 0 {
+                ctx.offset = offset;
+                let reason = ErrorReason::Runtime(RuntimeError::InvalidSlice);
+                return Err(JmespathError::from_ctx(ctx, reason));
+            }
+            match data.elements() {
+                Some(elements) => Ok(Node::Array(
+                    slice_indices(elements.len(), start, stop, step)
+                        .into_iter()
+                        .map(|i| elements[i].clone())
+                        .collect(),
+                )),
+                None => Ok(Node::null()),
+            }
+        }
+        Ast::Condition {
+            ref predicate,
+            ref then,
+            ..
+        } => {
+            if interpret(&data.to_rcvar(), predicate, ctx)?.is_truthy() {
+                locate(data, then, ctx)
+            } else {
+                Ok(Node::null())
+            }
+        }
+        _ => unreachable!("unsupported nodes are rejected before searching"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compile;
+
+    fn paths(expression: &str, data: &str) -> Vec<(String, String, String)> {
+        let expr = compile(expression).unwrap();
+        let data = Variable::from_json(data).unwrap();
+        expr.search_paths(data)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.jmespath(), m.json_pointer(), m.value.to_string()))
+            .collect()
+    }
+
+    fn path(jmespath: &str, pointer: &str, value: &str) -> (String, String, String) {
+        (jmespath.to_owned(), pointer.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn finds_paths_of_projected_values() {
+        let data = r#"{"reservations": [
+            {"instances": [{"id": "a"}, {"id": "b", "state": "stopped"}]},
+            {"instances": [{"id": "c"}, {"other": 1}, {"id": "d", "state": "stopped"}]}
+        ]}"#;
+        assert_eq!(
+            vec![
+                path(
+                    "reservations[0].instances[0].id",
+                    "/reservations/0/instances/0/id",
+                    "\"a\""
+                ),
+                path(
+                    "reservations[0].instances[1].id",
+                    "/reservations/0/instances/1/id",
+                    "\"b\""
+                ),
+                path(
+                    "reservations[1].instances[0].id",
+                    "/reservations/1/instances/0/id",
+                    "\"c\""
+                ),
+                path(
+                    "reservations[1].instances[2].id",
+                    "/reservations/1/instances/2/id",
+                    "\"d\""
+                ),
+            ],
+            paths("reservations[*].instances[*].id", data)
+        );
+        assert_eq!(
+            vec![
+                path(
+                    "reservations[0].instances[1].id",
+                    "/reservations/0/instances/1/id",
+                    "\"b\""
+                ),
+                path(
+                    "reservations[1].instances[2].id",
+                    "/reservations/1/instances/2/id",
+                    "\"d\""
+                ),
+            ],
+            paths("reservations[].instances[?state == 'stopped'].id", data)
+        );
+        assert_eq!(
+            vec![path(
+                "reservations[1].instances[2]",
+                "/reservations/1/instances/2",
+                r#"{"id":"d","state":"stopped"}"#
+            )],
+            paths("reservations[-1].instances[::-2] | [0]", data)
+        );
+    }
+
+    #[test]
+    fn finds_paths_of_object_values_and_escapes_names() {
+        let data = r#"{"a/b": {"x~y": 1, "z": null}, "c d": [true]}"#;
+        assert_eq!(
+            vec![path(r#""a/b"."x~y""#, "/a~1b/x~0y", "1")],
+            paths(r#""a/b".*"#, data)
+        );
+        assert_eq!(
+            vec![path(r#""c d""#, "/c d", "[true]")],
+            paths(r#"$."c d""#, data)
+        );
+        assert_eq!(vec![path("@", "", r#"{"x":1}"#)], paths("@", r#"{"x": 1}"#));
+        assert!(paths("missing[*]", data).is_empty());
+    }
+
+    #[test]
+    fn rejects_expressions_that_produce_values() {
+        let expr = compile("foo[*].[bar, baz]").unwrap();
+        let err = expr.search_paths(Variable::Null).unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::UnsupportedPathExpression),
+            err.reason
+        );
+        assert_eq!(7, err.offset);
+        // Expressions are rejected even if no value reaches the node.
+        let expr = compile("missing[*].length(@)").unwrap();
+        assert!(expr.search_paths(Variable::Null).is_err());
+        // Filters can use any expression.
+        assert_eq!(1, paths("[?length(@) > `1`]", "[[1, 2], [3]]").len());
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use crate::functions::*;
-use crate::parse;
+use crate::optimizer::optimize;
+use crate::parser::parse_with_max_depth;
 use crate::Expression;
 use crate::JmespathError;
 
@@ -12,12 +13,104 @@ use crate::JmespathError;
 /// utilizing custom functions in your expressions.
 pub struct Runtime {
     functions: HashMap<String, Box<dyn Function>>,
+    optimize: bool,
+    backend: Backend,
+    limits: Limits,
+}
+
+/// Strategy used to evaluate compiled expressions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Recursively walks the AST of the expression.
+    #[default]
+    Interpreter,
+    /// Lowers the AST to a sequence of instructions that are evaluated
+    /// without recursion, which avoids exhausting the stack when searching
+    /// with deeply nested expressions. Expressions passed to functions
+    /// (e.g., `&foo` in `sort_by`) are still interpreted.
+    Bytecode,
+}
+
+/// Limits on the resources used to evaluate expressions.
+///
+/// Expressions are evaluated without limits by default. Evaluation fails
+/// with a `RuntimeError` as soon as any of the limits is exceeded.
+///
+/// ```
+/// use jmespath::{Limits, Runtime};
+///
+/// let mut runtime = Runtime::new();
+/// runtime.register_builtin_functions();
+/// runtime.set_limits(Limits::new().with_max_steps(100).with_max_size(1000));
+/// let expr = runtime.compile("foo[*].bar").unwrap();
+/// assert_eq!(Some(100), expr.limits().max_steps);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of steps taken to evaluate an expression.
+    ///
+    /// Every AST node that is evaluated (or instruction that is executed
+    /// by the bytecode backend) is a step, including nodes of expressions
+    /// that are evaluated by functions, so the number of steps depends on
+    /// the backend.
+    pub max_steps: Option<usize>,
+    /// Maximum depth of nested AST nodes that are evaluated recursively.
+    ///
+    /// The limit only applies to recursive evaluators: the interpreter,
+    /// `search_borrowed`, `search_searchable`, and `search_paths`. The
+    /// bytecode backend evaluates nodes without recursion and only limits
+    /// the depth of expressions evaluated by functions (e.g., the
+    /// expression reference passed to `sort_by`).
+    ///
+    /// Expressions compiled by a `Runtime` with this limit can also not be
+    /// nested (e.g., using parentheses) more deeply than the limit, which
+    /// applies to every backend.
+    pub max_depth: Option<usize>,
+    /// Maximum number of elements of the arrays and objects produced while
+    /// evaluating an expression.
+    pub max_size: Option<usize>,
+    /// Maximum length in bytes of the strings produced by functions.
+    pub max_string_length: Option<usize>,
+}
+
+impl Limits {
+    /// Creates limits that do not limit evaluation.
+    pub fn new() -> Limits {
+        Default::default()
+    }
+
+    /// Sets the maximum number of steps taken to evaluate an expression.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Limits {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets the maximum depth of nested AST nodes.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Limits {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets the maximum number of elements of produced arrays and objects.
+    pub fn with_max_size(mut self, max_size: usize) -> Limits {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets the maximum length in bytes of produced strings.
+    pub fn with_max_string_length(mut self, max_string_length: usize) -> Limits {
+        self.max_string_length = Some(max_string_length);
+        self
+    }
 }
 
 impl Default for Runtime {
     fn default() -> Self {
         Runtime {
-            functions: HashMap::with_capacity(26),
+            functions: HashMap::with_capacity(41),
+            optimize: false,
+            backend: Backend::default(),
+            limits: Limits::default(),
         }
     }
 }
@@ -34,7 +127,72 @@ impl Runtime {
     /// grammar: http://jmespath.org/specification.html
     #[inline]
     pub fn compile<'a>(&'a self, expression: &str) -> Result<Expression<'a>, JmespathError> {
-        parse(expression).map(|ast| Expression::new(expression, ast, self))
+        let mut ast = parse_with_max_depth(expression, self.limits.max_depth)?;
+        if self.optimize {
+            ast = optimize(ast, self);
+        }
+        Ok(Expression::new(expression, ast, self))
+    }
+
+    /// Enables or disables optimizing expressions when they are compiled.
+    ///
+    /// Optimizations are disabled by default. When enabled, constant
+    /// subtrees (e.g., comparisons of literals and calls to pure functions
+    /// with literal arguments) are replaced with their result and redundant
+    /// nodes (e.g., `@.foo`) are removed from the AST returned by
+    /// `Expression::as_ast()`. Optimizations never change the result of
+    /// searching with an expression. Functions are resolved when the
+    /// expression is compiled, so functions must be registered first.
+    ///
+    /// ```
+    /// use jmespath::ast::Ast;
+    /// use jmespath::Runtime;
+    ///
+    /// let mut runtime = Runtime::new();
+    /// runtime.register_builtin_functions();
+    /// runtime.set_optimize(true);
+    /// let expr = runtime.compile("length('abc') > `2`").unwrap();
+    /// assert!(matches!(expr.as_ast(), Ast::Literal { .. }));
+    /// ```
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Sets the backend used to evaluate expressions compiled by the runtime.
+    ///
+    /// Expressions are interpreted by default. Both backends produce the
+    /// same results and errors. The backend of a single expression can be
+    /// changed using `Expression::set_backend()`.
+    ///
+    /// ```
+    /// use jmespath::{Backend, Runtime};
+    ///
+    /// let mut runtime = Runtime::new();
+    /// runtime.register_builtin_functions();
+    /// runtime.set_backend(Backend::Bytecode);
+    /// let expr = runtime.compile("foo[?bar > `1`].baz").unwrap();
+    /// assert_eq!(Backend::Bytecode, expr.backend());
+    /// ```
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Returns the backend used to evaluate expressions compiled by the runtime.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Sets the limits used to evaluate expressions compiled by the runtime.
+    ///
+    /// The limits of a single expression can be changed using
+    /// `Expression::set_limits()`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Returns the limits used to evaluate expressions compiled by the runtime.
+    pub fn limits(&self) -> Limits {
+        self.limits
     }
 
     /// Adds a new function to the runtime.
@@ -43,6 +201,34 @@ impl Runtime {
         self.functions.insert(name.to_owned(), f);
     }
 
+    /// Adds a typed Rust function to the runtime.
+    ///
+    /// The function signature is derived from the argument types, and
+    /// arguments are converted from JMESPath values using serde.
+    ///
+    /// ```
+    /// use jmespath::Runtime;
+    ///
+    /// let mut runtime = Runtime::new();
+    /// runtime.register_typed("slug", |s: String, max: Option<f64>| -> String {
+    ///     let slug = s.to_lowercase().replace(' ', "-");
+    ///     match max {
+    ///         Some(max) => slug.chars().take(max as usize).collect(),
+    ///         None => slug,
+    ///     }
+    /// });
+    ///
+    /// let expr = runtime.compile("slug('Hello World', `7`)").unwrap();
+    /// assert_eq!("hello-w", expr.search(()).unwrap().as_string().unwrap());
+    /// ```
+    pub fn register_typed<F, Args>(&mut self, name: &str, f: F)
+    where
+        F: TypedFunction<Args>,
+        Args: 'static,
+    {
+        self.register_function(name, Box::new(TypedFn::new(f)));
+    }
+
     /// Removes a function from the runtime.
     ///
     /// Returns the function that was removed if it was found.
@@ -58,31 +244,59 @@ impl Runtime {
 
     /// Registers all of the builtin JMESPath functions with the runtime.
     pub fn register_builtin_functions(&mut self) {
-        self.register_function("abs", Box::new(AbsFn::new()));
-        self.register_function("avg", Box::new(AvgFn::new()));
-        self.register_function("ceil", Box::new(CeilFn::new()));
-        self.register_function("contains", Box::new(ContainsFn::new()));
-        self.register_function("ends_with", Box::new(EndsWithFn::new()));
-        self.register_function("floor", Box::new(FloorFn::new()));
-        self.register_function("join", Box::new(JoinFn::new()));
-        self.register_function("keys", Box::new(KeysFn::new()));
-        self.register_function("length", Box::new(LengthFn::new()));
-        self.register_function("map", Box::new(MapFn::new()));
-        self.register_function("min", Box::new(MinFn::new()));
-        self.register_function("max", Box::new(MaxFn::new()));
-        self.register_function("max_by", Box::new(MaxByFn::new()));
-        self.register_function("min_by", Box::new(MinByFn::new()));
-        self.register_function("merge", Box::new(MergeFn::new()));
-        self.register_function("not_null", Box::new(NotNullFn::new()));
-        self.register_function("reverse", Box::new(ReverseFn::new()));
-        self.register_function("sort", Box::new(SortFn::new()));
-        self.register_function("sort_by", Box::new(SortByFn::new()));
-        self.register_function("starts_with", Box::new(StartsWithFn::new()));
-        self.register_function("sum", Box::new(SumFn::new()));
-        self.register_function("to_array", Box::new(ToArrayFn::new()));
-        self.register_function("to_number", Box::new(ToNumberFn::new()));
-        self.register_function("to_string", Box::new(ToStringFn::new()));
-        self.register_function("type", Box::new(TypeFn::new()));
-        self.register_function("values", Box::new(ValuesFn::new()));
+        self.register_builtin("abs", AbsFn::new());
+        self.register_builtin("avg", AvgFn::new());
+        self.register_builtin("ceil", CeilFn::new());
+        self.register_builtin("contains", ContainsFn::new());
+        self.register_builtin("ends_with", EndsWithFn::new());
+        self.register_builtin("find_first", FindFirstFn::new());
+        self.register_builtin("find_last", FindLastFn::new());
+        self.register_builtin("floor", FloorFn::new());
+        self.register_builtin("from_items", FromItemsFn::new());
+        self.register_builtin("group_by", GroupByFn::new());
+        self.register_builtin("items", ItemsFn::new());
+        self.register_builtin("join", JoinFn::new());
+        self.register_builtin("keys", KeysFn::new());
+        self.register_builtin("length", LengthFn::new());
+        self.register_builtin("lower", LowerFn::new());
+        self.register_builtin("map", MapFn::new());
+        #[cfg(feature = "regex")]
+        self.register_builtin("matches", MatchesFn::new());
+        self.register_builtin("min", MinFn::new());
+        self.register_builtin("max", MaxFn::new());
+        self.register_builtin("max_by", MaxByFn::new());
+        self.register_builtin("min_by", MinByFn::new());
+        self.register_builtin("merge", MergeFn::new());
+        self.register_builtin("not_null", NotNullFn::new());
+        self.register_builtin("pad_left", PadLeftFn::new());
+        self.register_builtin("pad_right", PadRightFn::new());
+        #[cfg(feature = "regex")]
+        self.register_builtin("regex_extract", RegexExtractFn::new());
+        #[cfg(feature = "regex")]
+        self.register_builtin("regex_replace", RegexReplaceFn::new());
+        #[cfg(feature = "regex")]
+        self.register_builtin("regex_split", RegexSplitFn::new());
+        self.register_builtin("replace", ReplaceFn::new());
+        self.register_builtin("reverse", ReverseFn::new());
+        self.register_builtin("sort", SortFn::new());
+        self.register_builtin("sort_by", SortByFn::new());
+        self.register_builtin("split", SplitFn::new());
+        self.register_builtin("starts_with", StartsWithFn::new());
+        self.register_builtin("sum", SumFn::new());
+        self.register_builtin("to_array", ToArrayFn::new());
+        self.register_builtin("to_number", ToNumberFn::new());
+        self.register_builtin("to_string", ToStringFn::new());
+        self.register_builtin("trim", TrimFn::new());
+        self.register_builtin("trim_left", TrimLeftFn::new());
+        self.register_builtin("trim_right", TrimRightFn::new());
+        self.register_builtin("type", TypeFn::new());
+        self.register_builtin("upper", UpperFn::new());
+        self.register_builtin("values", ValuesFn::new());
+        self.register_builtin("zip", ZipFn::new());
+    }
+
+    /// Registers a builtin function, which is reported as pure.
+    fn register_builtin<F: Builtin + 'static>(&mut self, name: &str, f: F) {
+        self.register_function(name, Box::new(BuiltinFunction(f)));
     }
 }
@@ -0,0 +1,511 @@
+//! Searches Rust values in place, without serializing them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::BuildHasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde_json::{Number, Value};
+
+use crate::borrowed::JmespathValue;
+use crate::variable::JmespathType;
+use crate::{JmespathError, Rcvar, Variable};
+
+/// A Rust value that can be searched using `Expression::search_searchable()`
+/// without first serializing it into a `Variable`.
+///
+/// Fields, elements, and object values are returned by reference, so only
+/// the values that are part of the result (or are passed to functions,
+/// compared, etc.) are copied into `Variable`s using `to_variable`. The
+/// values exposed by a type should match the way that the type is
+/// serialized, so that searching the value gives the same result as
+/// `Expression::search()`.
+///
+/// `Searchable` is implemented for booleans, numbers, strings, `Option`,
+/// `Vec`, slices, maps with `String` keys, smart pointers, and
+/// `serde_json::Value`. It can be derived for structs with named fields
+/// using `#[derive(Searchable)]` from the `jmespath-macros` crate:
+///
+/// ```ignore
+/// use jmespath_macros::Searchable;
+///
+/// #[derive(Searchable)]
+/// struct Server {
+///     name: String,
+///     #[jmespath(rename = "listenPort")]
+///     port: u16,
+///     #[jmespath(skip)]
+///     connections: std::sync::Mutex<Vec<u32>>,
+/// }
+/// ```
+///
+/// Types that borrow data cannot be searched in place because the values
+/// that they return must be `'static`.
+pub trait Searchable: 'static {
+    /// Returns the JMESPath type of the value.
+    fn get_type(&self) -> JmespathType;
+
+    /// Returns true if the value is truthy. False, null, and empty strings,
+    /// arrays, and objects are falsy.
+    fn is_truthy(&self) -> bool {
+        match self.get_type() {
+            JmespathType::Null => false,
+            JmespathType::Array => self.array_len().is_some_and(|len| len > 0),
+            JmespathType::Object => self.object_values().is_some_and(|v| !v.is_empty()),
+            _ => self.to_variable().is_ok_and(|v| v.is_truthy()),
+        }
+    }
+
+    /// Returns the value of a field if the value is an object with the field.
+    fn get_field(&self, _name: &str) -> Option<&dyn Searchable> {
+        None
+    }
+
+    /// Returns the number of elements if the value is an array.
+    fn array_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns an element if the value is an array with the index.
+    fn get_index(&self, _index: usize) -> Option<&dyn Searchable> {
+        None
+    }
+
+    /// Returns the values of the fields if the value is an object, in the
+    /// same order as the fields of the object returned by `to_variable`.
+    fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+        None
+    }
+
+    /// Copies the value into a `Variable`.
+    fn to_variable(&self) -> Result<Variable, JmespathError>;
+}
+
+impl JmespathValue for dyn Searchable {
+    fn get_type(&self) -> JmespathType {
+        Searchable::get_type(self)
+    }
+
+    fn is_truthy(&self) -> bool {
+        Searchable::is_truthy(self)
+    }
+
+    fn get_field(&self, name: &str) -> Option<&Self> {
+        Searchable::get_field(self, name)
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        Searchable::array_len(self)
+    }
+
+    fn get_index(&self, index: usize) -> Option<&Self> {
+        Searchable::get_index(self, index)
+    }
+
+    fn object_values(&self) -> Option<Vec<&Self>> {
+        Searchable::object_values(self)
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Searchable::to_variable(self)
+    }
+}
+
+impl Searchable for () {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::Null
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Ok(Variable::Null)
+    }
+}
+
+impl Searchable for bool {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::Boolean
+    }
+
+    fn is_truthy(&self) -> bool {
+        *self
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Ok(Variable::Bool(*self))
+    }
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl Searchable for $t {
+            fn get_type(&self) -> JmespathType {
+                JmespathType::Number
+            }
+
+            fn is_truthy(&self) -> bool {
+                true
+            }
+
+            fn to_variable(&self) -> Result<Variable, JmespathError> {
+                Ok(Variable::Number(Number::from(*self)))
+            }
+        }
+    )*};
+}
+
+integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! float {
+    ($($t:ty),*) => {$(
+        // Like serialization, numbers that are not finite become null.
+        impl Searchable for $t {
+            fn get_type(&self) -> JmespathType {
+                if self.is_finite() {
+                    JmespathType::Number
+                } else {
+                    JmespathType::Null
+                }
+            }
+
+            fn is_truthy(&self) -> bool {
+                self.is_finite()
+            }
+
+            fn to_variable(&self) -> Result<Variable, JmespathError> {
+                Ok(Number::from_f64(f64::from(*self)).map_or(Variable::Null, Variable::Number))
+            }
+        }
+    )*};
+}
+
+float!(f32, f64);
+
+impl Searchable for String {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::String
+    }
+
+    fn is_truthy(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Ok(Variable::String(self.clone()))
+    }
+}
+
+impl Searchable for &'static str {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::String
+    }
+
+    fn is_truthy(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Ok(Variable::String((*self).to_owned()))
+    }
+}
+
+impl<T: Searchable> Searchable for Option<T> {
+    fn get_type(&self) -> JmespathType {
+        self.as_ref().map_or(JmespathType::Null, T::get_type)
+    }
+
+    fn is_truthy(&self) -> bool {
+        self.as_ref().is_some_and(T::is_truthy)
+    }
+
+    fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+        self.as_ref().and_then(|v| v.get_field(name))
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        self.as_ref().and_then(T::array_len)
+    }
+
+    fn get_index(&self, index: usize) -> Option<&dyn Searchable> {
+        self.as_ref().and_then(|v| v.get_index(index))
+    }
+
+    fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+        self.as_ref().and_then(T::object_values)
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        self.as_ref().map_or(Ok(Variable::Null), T::to_variable)
+    }
+}
+
+/// Searches a sequence of searchable elements.
+macro_rules! sequence {
+    ($($t:ty),*) => {$(
+        impl<T: Searchable> Searchable for $t {
+            fn get_type(&self) -> JmespathType {
+                JmespathType::Array
+            }
+
+            fn is_truthy(&self) -> bool {
+                !self.is_empty()
+            }
+
+            fn array_len(&self) -> Option<usize> {
+                Some(self.len())
+            }
+
+            fn get_index(&self, index: usize) -> Option<&dyn Searchable> {
+                self.get(index).map(|v| v as &dyn Searchable)
+            }
+
+            fn to_variable(&self) -> Result<Variable, JmespathError> {
+                self.iter()
+                    .map(|v| v.to_variable().map(Rcvar::new))
+                    .collect::<Result<_, _>>()
+                    .map(Variable::Array)
+            }
+        }
+    )*};
+}
+
+sequence!(Vec<T>, [T]);
+
+impl<T: Searchable> Searchable for BTreeMap<String, T> {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::Object
+    }
+
+    fn is_truthy(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+        self.get(name).map(|v| v as &dyn Searchable)
+    }
+
+    fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+        Some(self.values().map(|v| v as &dyn Searchable).collect())
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        self.iter()
+            .map(|(k, v)| Ok((k.clone(), Rcvar::new(v.to_variable()?))))
+            .collect::<Result<_, _>>()
+            .map(Variable::Object)
+    }
+}
+
+impl<T: Searchable, S: BuildHasher + 'static> Searchable for HashMap<String, T, S> {
+    fn get_type(&self) -> JmespathType {
+        JmespathType::Object
+    }
+
+    fn is_truthy(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+        self.get(name).map(|v| v as &dyn Searchable)
+    }
+
+    fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+        Some(
+            sorted_entries(self)
+                .into_iter()
+                .map(|(_, v)| v as &dyn Searchable)
+                .collect(),
+        )
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        sorted_entries(self)
+            .into_iter()
+            .map(|(k, v)| Ok((k.clone(), Rcvar::new(v.to_variable()?))))
+            .collect::<Result<_, _>>()
+            .map(Variable::Object)
+    }
+}
+
+/// Returns the entries of a hash map sorted by key, so that searching the
+/// map does not depend on the iteration order of the map.
+fn sorted_entries<T, S>(map: &HashMap<String, T, S>) -> Vec<(&String, &T)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Searches the value that a pointer points to.
+macro_rules! pointer {
+    ($($t:ty),*) => {$(
+        impl<T: Searchable + ?Sized> Searchable for $t {
+            fn get_type(&self) -> JmespathType {
+                (**self).get_type()
+            }
+
+            fn is_truthy(&self) -> bool {
+                (**self).is_truthy()
+            }
+
+            fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+                (**self).get_field(name)
+            }
+
+            fn array_len(&self) -> Option<usize> {
+                (**self).array_len()
+            }
+
+            fn get_index(&self, index: usize) -> Option<&dyn Searchable> {
+                (**self).get_index(index)
+            }
+
+            fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+                (**self).object_values()
+            }
+
+            fn to_variable(&self) -> Result<Variable, JmespathError> {
+                (**self).to_variable()
+            }
+        }
+    )*};
+}
+
+pointer!(Box<T>, Rc<T>, Arc<T>);
+
+impl Searchable for Value {
+    fn get_type(&self) -> JmespathType {
+        JmespathValue::get_type(self)
+    }
+
+    fn is_truthy(&self) -> bool {
+        JmespathValue::is_truthy(self)
+    }
+
+    fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+        JmespathValue::get_field(self, name).map(|v| v as &dyn Searchable)
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        JmespathValue::array_len(self)
+    }
+
+    fn get_index(&self, index: usize) -> Option<&dyn Searchable> {
+        JmespathValue::get_index(self, index).map(|v| v as &dyn Searchable)
+    }
+
+    fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+        JmespathValue::object_values(self)
+            .map(|values| values.into_iter().map(|v| v as &dyn Searchable).collect())
+    }
+
+    fn to_variable(&self) -> Result<Variable, JmespathError> {
+        Variable::try_from(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compile, Map};
+    use std::cell::Cell;
+
+    /// Object that counts how many times it has been copied.
+    struct Server {
+        name: String,
+        ports: Vec<u16>,
+        tags: HashMap<String, String>,
+        parent: Option<Box<Server>>,
+        copies: Rc<Cell<usize>>,
+    }
+
+    impl Searchable for Server {
+        fn get_type(&self) -> JmespathType {
+            JmespathType::Object
+        }
+
+        fn get_field(&self, name: &str) -> Option<&dyn Searchable> {
+            match name {
+                "name" => Some(&self.name),
+                "ports" => Some(&self.ports),
+                "tags" => Some(&self.tags),
+                "parent" => Some(&self.parent),
+                _ => None,
+            }
+        }
+
+        fn object_values(&self) -> Option<Vec<&dyn Searchable>> {
+            Some(vec![&self.name, &self.parent, &self.ports, &self.tags])
+        }
+
+        fn to_variable(&self) -> Result<Variable, JmespathError> {
+            self.copies.set(self.copies.get() + 1);
+            let mut map = Map::new();
+            map.insert("name".to_owned(), Rcvar::new(self.name.to_variable()?));
+            map.insert("parent".to_owned(), Rcvar::new(self.parent.to_variable()?));
+            map.insert("ports".to_owned(), Rcvar::new(self.ports.to_variable()?));
+            map.insert("tags".to_owned(), Rcvar::new(self.tags.to_variable()?));
+            Ok(Variable::Object(map))
+        }
+    }
+
+    fn servers(copies: &Rc<Cell<usize>>) -> Vec<Server> {
+        let server = |name: &str, port, parent| {
+            let mut tags = HashMap::new();
+            tags.insert("env".to_owned(), format!("{}-env", name));
+            Server {
+                name: name.to_owned(),
+                ports: vec![port, port + 1],
+                tags,
+                parent,
+                copies: copies.clone(),
+            }
+        };
+        let root = server("root", 80, None);
+        vec![
+            server("a", 8000, Some(Box::new(root))),
+            server("b", 9000, None),
+        ]
+    }
+
+    #[test]
+    fn searches_without_copying_objects() {
+        let copies = Rc::new(Cell::new(0));
+        let data = servers(&copies);
+        let cases = vec![
+            ("[?ports[1] > `9000`].name", "[\"b\"]"),
+            ("[*].parent.name", "[\"root\"]"),
+            ("[0].tags.env", "\"a-env\""),
+            ("[].ports[] | sum(@)", "34002"),
+            ("[?parent].tags.*[]", "[\"a-env\"]"),
+            ("length([1].*)", "3"),
+            ("[*].name | sort(@) | join(',', @)", "\"a,b\""),
+        ];
+        for (expression, expected) in cases {
+            let result = compile(expression).unwrap().search_searchable(&data);
+            assert_eq!(
+                Variable::from_json(expected).unwrap(),
+                *result.unwrap(),
+                "{}",
+                expression
+            );
+        }
+        assert_eq!(0, copies.get());
+        let result = compile("[1]").unwrap().search_searchable(&data).unwrap();
+        assert_eq!(Variable::String("b".to_owned()), *result.get_field("name"));
+        assert_eq!(1, copies.get());
+    }
+
+    #[test]
+    fn converts_like_serialization() {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_owned(), vec![Some(1.5), None, Some(f64::NAN)]);
+        let variable = Searchable::to_variable(&map).unwrap();
+        assert_eq!(
+            Variable::from_json(r#"{"a": [1.5, null, null]}"#).unwrap(),
+            variable
+        );
+        assert!(!Searchable::is_truthy(&Some("")));
+        assert!(Searchable::is_truthy(&Box::new(0u8)));
+        assert!(!Searchable::is_truthy(&Vec::<bool>::new()));
+    }
+}
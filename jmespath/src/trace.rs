@@ -0,0 +1,339 @@
+//! Traces the evaluation of expressions.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::ast::Ast;
+use crate::interpreter::SearchResult;
+use crate::Rcvar;
+
+/// Receives an event when the interpreter enters and exits each AST node.
+///
+/// Attach a tracer to a search using `Context::with_tracer()`. Searches
+/// with a tracer are always interpreted, regardless of the backend of the
+/// expression. Expressions passed to functions (e.g., `&foo` in `sort_by`)
+/// are traced each time they are evaluated.
+pub trait Tracer {
+    /// Called before a node is evaluated with the value it is evaluated
+    /// against.
+    fn enter(&mut self, node: &Ast, input: &Rcvar);
+
+    /// Called after a node is evaluated with the value it was evaluated
+    /// against and its result.
+    fn exit(&mut self, node: &Ast, input: &Rcvar, result: &SearchResult);
+}
+
+/// A `Tracer` that renders an indented log of the nodes that are evaluated,
+/// along with their offset in the expression, input, and result.
+///
+/// ```
+/// use jmespath::TraceLog;
+///
+/// let expr = jmespath::compile("foo[?bar > `1`]").unwrap();
+/// let data = jmespath::Variable::from_json(r#"{"foo": [{"bar": 1}]}"#).unwrap();
+/// let mut log = TraceLog::new();
+/// let ctx = expr.context().with_tracer(&mut log);
+/// assert_eq!("[]", expr.search_with_context(data, ctx).unwrap().to_string());
+/// assert!(log.as_str().contains("Condition [?bar > `1`] (offset 14) returned null"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TraceLog {
+    log: String,
+    depth: usize,
+}
+
+impl TraceLog {
+    /// Creates an empty log.
+    pub fn new() -> TraceLog {
+        Default::default()
+    }
+
+    /// Returns the rendered log.
+    pub fn as_str(&self) -> &str {
+        &self.log
+    }
+
+    fn line(&mut self, node: &Ast, message: fmt::Arguments<'_>) {
+        use std::fmt::Write;
+        let indent = "  ".repeat(self.depth);
+        writeln!(
+            self.log,
+            "{}{} {} (offset {}) {}",
+            indent,
+            node_name(node),
+            node,
+            node.offset(),
+            message
+        )
+        .expect("writing to a String does not fail");
+    }
+}
+
+impl Tracer for TraceLog {
+    fn enter(&mut self, node: &Ast, input: &Rcvar) {
+        self.line(node, format_args!("given {}", input));
+        self.depth += 1;
+    }
+
+    fn exit(&mut self, node: &Ast, _input: &Rcvar, result: &SearchResult) {
+        self.depth -= 1;
+        match *result {
+            Ok(ref value) => self.line(node, format_args!("returned {}", value)),
+            Err(ref e) => self.line(node, format_args!("failed: {}", e.reason)),
+        }
+    }
+}
+
+impl fmt::Display for TraceLog {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(&self.log)
+    }
+}
+
+/// A `Tracer` that counts how many times each node of an expression is
+/// evaluated and how long evaluating it takes.
+///
+/// Nodes are identified by their kind and offset in the expression, so
+/// expressions passed to functions are profiled as part of the expression
+/// they appear in. Formatting a profiler renders a report of each node,
+/// ordered by offset, that points to the node in the expression.
+///
+/// ```
+/// use jmespath::Profiler;
+///
+/// let expr = jmespath::compile("foo[*].bar").unwrap();
+/// let data = jmespath::Variable::from_json(r#"{"foo": [{"bar": 1}, {"bar": 2}]}"#).unwrap();
+/// let mut profiler = Profiler::new(expr.as_str());
+/// let ctx = expr.context().with_tracer(&mut profiler);
+/// expr.search_with_context(data, ctx).unwrap();
+/// let bar = profiler.nodes().find(|node| node.kind == "Field" && node.offset == 7);
+/// assert_eq!(2, bar.unwrap().calls);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    expression: String,
+    nodes: BTreeMap<(usize, &'static str), NodeProfile>,
+    /// Start time and time spent in children of each node being evaluated.
+    stack: Vec<(Instant, Duration)>,
+}
+
+/// Statistics about the evaluation of a node, collected by a `Profiler`.
+#[derive(Clone, Debug)]
+pub struct NodeProfile {
+    /// Kind of node (e.g., `Projection`).
+    pub kind: &'static str,
+    /// Offset of the node in the expression.
+    pub offset: usize,
+    /// The node formatted as an expression.
+    pub expression: String,
+    /// Number of times the node was evaluated.
+    pub calls: usize,
+    /// Time spent evaluating the node, including its children.
+    pub total_time: Duration,
+    /// Time spent evaluating the node, excluding its children.
+    pub self_time: Duration,
+}
+
+impl Profiler {
+    /// Creates a profiler for searches using the given expression.
+    pub fn new(expression: &str) -> Profiler {
+        Profiler {
+            expression: expression.to_owned(),
+            nodes: BTreeMap::new(),
+            stack: vec![],
+        }
+    }
+
+    /// Returns the statistics of each evaluated node, ordered by offset.
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeProfile> {
+        self.nodes.values()
+    }
+}
+
+impl Tracer for Profiler {
+    fn enter(&mut self, _node: &Ast, _input: &Rcvar) {
+        self.stack.push((Instant::now(), Duration::ZERO));
+    }
+
+    fn exit(&mut self, node: &Ast, _input: &Rcvar, _result: &SearchResult) {
+        let (start, children) = self.stack.pop().expect("entered node");
+        let elapsed = start.elapsed();
+        if let Some(parent) = self.stack.last_mut() {
+            parent.1 += elapsed;
+        }
+        let profile = self
+            .nodes
+            .entry((node.offset(), node_name(node)))
+            .or_insert_with(|| NodeProfile {
+                kind: node_name(node),
+                offset: node.offset(),
+                expression: node.to_string(),
+                calls: 0,
+                total_time: Duration::ZERO,
+                self_time: Duration::ZERO,
+            });
+        profile.calls += 1;
+        profile.total_time += elapsed;
+        profile.self_time += elapsed.saturating_sub(children);
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in self.nodes() {
+            writeln!(
+                fmt,
+                "{} {} (offset {}): {} calls, {:?} total, {:?} self",
+                node.kind,
+                node.expression,
+                node.offset,
+                node.calls,
+                node.total_time,
+                node.self_time
+            )?;
+            fmt.write_str(&highlight(&self.expression, node.offset))?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders an expression with a caret under the character at an offset,
+/// like the location of a `JmespathError`.
+fn highlight(expression: &str, offset: usize) -> String {
+    let mut buff = String::new();
+    let mut start = 0;
+    for line in expression.split('\n') {
+        let len = line.chars().count();
+        buff.push_str(line);
+        buff.push('\n');
+        if offset >= start && offset <= start + len {
+            buff.push_str(&" ".repeat(offset - start));
+            buff.push_str("^\n");
+        }
+        start += len + 1;
+    }
+    buff
+}
+
+fn node_name(node: &Ast) -> &'static str {
+    match *node {
+        Ast::Comparison { .. } => "Comparison",
+        Ast::Arithmetic { .. } => "Arithmetic",
+        Ast::UnaryArithmetic { .. } => "UnaryArithmetic",
+        Ast::Condition { .. } => "Condition",
+        Ast::Identity { .. } => "Identity",
+        Ast::Root { .. } => "Root",
+        Ast::Expref { .. } => "Expref",
+        Ast::Flatten { .. } => "Flatten",
+        Ast::Function { .. } => "Function",
+        Ast::Field { .. } => "Field",
+        Ast::Index { .. } => "Index",
+        Ast::Literal { .. } => "Literal",
+        Ast::MultiList { .. } => "MultiList",
+        Ast::MultiHash { .. } => "MultiHash",
+        Ast::Not { .. } => "Not",
+        Ast::Projection { .. } => "Projection",
+        Ast::ObjectValues { .. } => "ObjectValues",
+        Ast::And { .. } => "And",
+        Ast::Or { .. } => "Or",
+        Ast::Slice { .. } => "Slice",
+        Ast::Subexpr { .. } => "Subexpr",
+        Ast::Let { .. } => "Let",
+        Ast::VariableRef { .. } => "VariableRef",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compile, Backend, Variable};
+
+    #[test]
+    fn logs_nested_evaluation() {
+        let mut expr = compile("foo[?bar > `1`].baz").unwrap();
+        // Traced searches are interpreted regardless of the backend.
+        expr.set_backend(Backend::Bytecode);
+        let data = Variable::from_json(r#"{"foo": [{"bar": 1}, {"bar": 2, "baz": 3}]}"#).unwrap();
+        let mut log = TraceLog::new();
+        let ctx = expr.context().with_tracer(&mut log);
+        assert_eq!(
+            "[3]",
+            expr.search_with_context(data, ctx).unwrap().to_string()
+        );
+        let expected = r#"Projection foo[?bar > `1`].baz (offset 16) given {"foo":[{"bar":1},{"bar":2,"baz":3}]}
+  Field foo (offset 0) given {"foo":[{"bar":1},{"bar":2,"baz":3}]}
+  Field foo (offset 0) returned [{"bar":1},{"bar":2,"baz":3}]
+  Condition [?bar > `1`].baz (offset 16) given {"bar":1}
+    Comparison bar > `1` (offset 11) given {"bar":1}
+      Field bar (offset 5) given {"bar":1}
+      Field bar (offset 5) returned 1
+      Literal `1` (offset 11) given {"bar":1}
+      Literal `1` (offset 11) returned 1
+    Comparison bar > `1` (offset 11) returned false
+  Condition [?bar > `1`].baz (offset 16) returned null
+  Condition [?bar > `1`].baz (offset 16) given {"bar":2,"baz":3}
+    Comparison bar > `1` (offset 11) given {"bar":2,"baz":3}
+      Field bar (offset 5) given {"bar":2,"baz":3}
+      Field bar (offset 5) returned 2
+      Literal `1` (offset 11) given {"bar":2,"baz":3}
+      Literal `1` (offset 11) returned 1
+    Comparison bar > `1` (offset 11) returned true
+    Field baz (offset 16) given {"bar":2,"baz":3}
+    Field baz (offset 16) returned 3
+  Condition [?bar > `1`].baz (offset 16) returned 3
+Projection foo[?bar > `1`].baz (offset 16) returned [3]
+"#;
+        assert_eq!(expected, log.as_str());
+    }
+
+    #[test]
+    fn logs_errors() {
+        let expr = compile("length(`1`)").unwrap();
+        let mut log = TraceLog::new();
+        let ctx = expr.context().with_tracer(&mut log);
+        assert!(expr.search_with_context(Variable::Null, ctx).is_err());
+        let expected = concat!(
+            "Function length(`1`) (offset 6) given null\n",
+            "  Literal `1` (offset 7) given null\n",
+            "  Literal `1` (offset 7) returned 1\n",
+            "Function length(`1`) (offset 6) failed: Runtime error: ",
+            "Argument 0 expects type array|object|string, given number\n",
+        );
+        assert_eq!(expected, log.as_str());
+    }
+
+    #[test]
+    fn profiles_nodes() {
+        let expr = compile("foo[*].bar").unwrap();
+        let data = Variable::from_json(r#"{"foo": [{"bar": 1}, {"bar": 2}, {}]}"#).unwrap();
+        let mut profiler = Profiler::new(expr.as_str());
+        let ctx = expr.context().with_tracer(&mut profiler);
+        expr.search_with_context(data, ctx).unwrap();
+        let nodes: Vec<_> = profiler
+            .nodes()
+            .map(|node| (node.kind, node.offset, node.calls))
+            .collect();
+        assert_eq!(
+            vec![("Field", 0, 1), ("Field", 7, 3), ("Projection", 7, 1),],
+            nodes
+        );
+        assert!(profiler
+            .nodes()
+            .all(|node| node.self_time <= node.total_time));
+        let report = profiler.to_string();
+        assert!(report.contains("Field bar (offset 7): 3 calls, "));
+        assert!(report.contains("Field foo (offset 0): 1 calls, "));
+        assert!(report.contains("\nfoo[*].bar\n^\n"));
+        assert!(report.contains("\nfoo[*].bar\n       ^\n"));
+    }
+
+    #[test]
+    fn highlights_offsets_on_their_line() {
+        assert_eq!("foo.bar\n    ^\n", highlight("foo.bar", 4));
+        assert_eq!(
+            "foo\n| bar\n  ^\n| baz\n",
+            highlight("foo\n| bar\n| baz", 6)
+        );
+    }
+}
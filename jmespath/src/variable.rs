@@ -5,20 +5,20 @@ use serde::*;
 use serde_json::error::Error;
 use serde_json::value::Value;
 use std::cmp::{max, Ordering};
-use std::collections::BTreeMap;
 use std::fmt;
 use std::iter::Iterator;
 use std::string::ToString;
 use std::vec;
 
-use crate::ast::{Ast, Comparator};
+use crate::ast::{ArithmeticOperator, Ast, Comparator, UnaryOperator};
+use crate::number::Numeric;
 use crate::ToJmespath;
-use crate::{JmespathError, Rcvar};
+use crate::{JmespathError, Map, Rcvar};
 use serde_json::Number;
 use std::convert::TryFrom;
 
 /// JMESPath types.
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum JmespathType {
     Null,
     String,
@@ -55,49 +55,23 @@ pub enum Variable {
     Bool(bool),
     Number(Number),
     Array(Vec<Rcvar>),
-    Object(BTreeMap<String, Rcvar>),
+    Object(Map<String, Rcvar>),
     Expref(Ast),
 }
 
 impl Eq for Variable {}
 
-/// Compares two floats for equality.
-///
-/// Allows for equivalence of floating point numbers like
-/// 0.7100000000000002 and 0.71.
-///
-/// Based on http://stackoverflow.com/a/4915891
-fn float_eq(a: f64, b: f64) -> bool {
-    use std::f64;
-    let abs_a = a.abs();
-    let abs_b = b.abs();
-    let diff = (a - b).abs();
-    if a == b {
-        true
-    } else if !a.is_normal() || !b.is_normal() {
-        // a or b is zero or both are extremely close to it
-        // relative error is less meaningful here.
-        diff < (f64::EPSILON * f64::MIN_POSITIVE)
-    } else {
-        // use relative error.
-        diff / (abs_a + abs_b) < f64::EPSILON
-    }
-}
-
-/// Implement PartialEq for looser floating point comparisons.
+/// Implement PartialEq for exact integer and looser floating point comparisons.
 impl PartialEq for Variable {
     fn eq(&self, other: &Variable) -> bool {
         if self.get_type() != other.get_type() {
             false
         } else {
             match self {
-                Variable::Number(a) => {
-                    if let (Some(a), Some(b)) = (a.as_f64(), other.as_number()) {
-                        float_eq(a, b)
-                    } else {
-                        false
-                    }
-                }
+                Variable::Number(a) => match other {
+                    Variable::Number(b) => Numeric::new(a).equals(&Numeric::new(b)),
+                    _ => false,
+                },
                 Variable::String(ref s) => Some(s) == other.as_string(),
                 Variable::Bool(b) => Some(*b) == other.as_boolean(),
                 Variable::Array(ref a) => Some(a) == other.as_array(),
@@ -149,13 +123,12 @@ impl Ord for Variable {
                         Ordering::Equal
                     }
                 }
-                JmespathType::Number => {
-                    if let (Some(a), Some(b)) = (self.as_number(), other.as_number()) {
-                        a.partial_cmp(&b).unwrap_or(Ordering::Less)
-                    } else {
-                        Ordering::Equal
+                JmespathType::Number => match (self, other) {
+                    (Variable::Number(a), Variable::Number(b)) => {
+                        Numeric::new(a).compare(&Numeric::new(b))
                     }
-                }
+                    _ => Ordering::Equal,
+                },
                 _ => Ordering::Equal,
             }
         }
@@ -180,7 +153,7 @@ fn convert_map<'a, T>(value: T) -> Result<Variable, JmespathError>
 where
     T: Iterator<Item = (&'a String, &'a Value)>,
 {
-    let mut map: BTreeMap<String, Rcvar> = BTreeMap::new();
+    let mut map: Map<String, Rcvar> = Map::new();
     for kvp in value {
         map.insert(kvp.0.to_owned(), kvp.1.to_jmespath()?);
     }
@@ -264,9 +237,9 @@ impl Variable {
         self.as_object().is_some()
     }
 
-    /// If the value is an Object, returns the associated BTreeMap.
+    /// If the value is an Object, returns the associated Map.
     /// Returns None otherwise.
-    pub fn as_object(&self) -> Option<&BTreeMap<String, Rcvar>> {
+    pub fn as_object(&self) -> Option<&Map<String, Rcvar>> {
         match self {
             Variable::Object(map) => Some(map),
             _ => None,
@@ -289,10 +262,7 @@ impl Variable {
 
     /// Returns true if the value is a Number. Returns false otherwise.
     pub fn is_number(&self) -> bool {
-        match self {
-            Variable::Number(_) => true,
-            _ => false,
-        }
+        matches!(self, Variable::Number(_))
     }
 
     /// If the value is a number, return or cast it to a f64.
@@ -429,6 +399,36 @@ impl Variable {
         }
     }
 
+    /// Applies an arithmetic operator to two Variable values.
+    ///
+    /// Returns None if either value is not a number or if the result cannot
+    /// be represented as a JSON number (e.g., when dividing by zero). Integer
+    /// operands produce an integer result unless the operator is `Divide` or
+    /// the result overflows.
+    pub fn arithmetic(&self, operator: &ArithmeticOperator, value: &Variable) -> Option<Variable> {
+        match (self, value) {
+            (Variable::Number(a), Variable::Number(b)) => Numeric::new(a)
+                .arithmetic(operator, Numeric::new(b))?
+                .into_number()
+                .map(Variable::Number),
+            _ => None,
+        }
+    }
+
+    /// Applies a unary arithmetic operator to the Variable.
+    ///
+    /// Returns None if the value is not a number or if the result cannot be
+    /// represented as a JSON number.
+    pub fn unary_arithmetic(&self, operator: &UnaryOperator) -> Option<Variable> {
+        match (self, operator) {
+            (Variable::Number(_), UnaryOperator::Plus) => Some(self.clone()),
+            (Variable::Number(n), UnaryOperator::Minus) => {
+                Numeric::new(n).negate().into_number().map(Variable::Number)
+            }
+            _ => None,
+        }
+    }
+
     /// Returns a slice of the variable if the variable is an array.
     pub fn slice(&self, start: Option<i32>, stop: Option<i32>, step: i32) -> Option<Vec<Rcvar>> {
         self.as_array().map(|a| slice(a, start, stop, step))
@@ -454,8 +454,22 @@ impl Variable {
 // ------------------------------------------
 
 fn slice(array: &[Rcvar], start: Option<i32>, stop: Option<i32>, step: i32) -> Vec<Rcvar> {
+    slice_indices(array.len(), start, stop, step)
+        .into_iter()
+        .map(|i| array[i].clone())
+        .collect()
+}
+
+/// Returns the indexes of the elements selected by slicing an array of the
+/// given length.
+pub(crate) fn slice_indices(
+    len: usize,
+    start: Option<i32>,
+    stop: Option<i32>,
+    step: i32,
+) -> Vec<usize> {
     let mut result = vec![];
-    let len = array.len() as i32;
+    let len = len as i32;
     if len == 0 {
         return result;
     }
@@ -472,12 +486,12 @@ fn slice(array: &[Rcvar], start: Option<i32>, stop: Option<i32>, step: i32) -> V
     let mut i = a;
     if step > 0 {
         while i < b {
-            result.push(array[i as usize].clone());
+            result.push(i as usize);
             i += step;
         }
     } else {
         while i > b {
-            result.push(array[i as usize].clone());
+            result.push(i as usize);
             i += step;
         }
     }
@@ -513,6 +527,20 @@ fn adjust_slice_endpoint(len: i32, mut endpoint: i32, step: i32) -> i32 {
 // `(*self).clone` that I don't like, but I'm not sure how to work
 // around it.
 
+/// Converts the map that serde_json uses to serialize and deserialize
+/// numbers when the `arbitrary_precision` feature is enabled into a number.
+#[cfg(feature = "arbitrary_precision")]
+fn arbitrary_precision_number(map: &Map<String, Rcvar>) -> Option<Result<Variable, Error>> {
+    const TOKEN: &str = "$serde_json::private::Number";
+    match map.get(TOKEN) {
+        Some(value) if map.len() == 1 => {
+            let value = value.as_string()?;
+            Some(value.parse().map(Variable::Number))
+        }
+        _ => None,
+    }
+}
+
 /// Shortcut function to encode a `T` into a JMESPath `Variable`
 fn to_variable<T>(value: T) -> Result<Variable, Error>
 where
@@ -607,12 +635,19 @@ impl<'de> de::Deserialize<'de> for Variable {
             where
                 V: de::MapAccess<'de>,
             {
-                let mut values = BTreeMap::new();
+                let mut values = Map::new();
 
                 while let Some((key, value)) = visitor.next_entry()? {
                     values.insert(key, value);
                 }
 
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    if let Some(number) = arbitrary_precision_number(&values) {
+                        return number.map_err(de::Error::custom);
+                    }
+                }
+
                 Ok(Variable::Object(values))
             }
         }
@@ -871,7 +906,7 @@ impl<'de> de::SeqAccess<'de> for SeqDeserializer {
 }
 
 struct MapDeserializer {
-    iter: <BTreeMap<String, Rcvar> as IntoIterator>::IntoIter,
+    iter: <Map<String, Rcvar> as IntoIterator>::IntoIter,
     value: Option<Variable>,
 }
 
@@ -962,12 +997,12 @@ pub struct TupleVariantState {
 #[doc(hidden)]
 pub struct StructVariantState {
     name: String,
-    map: BTreeMap<String, Rcvar>,
+    map: Map<String, Rcvar>,
 }
 
 #[doc(hidden)]
 pub struct MapState {
-    map: BTreeMap<String, Rcvar>,
+    map: Map<String, Rcvar>,
     next_key: Option<String>,
 }
 
@@ -1078,18 +1113,14 @@ impl ser::Serializer for Serializer {
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<Variable, Error>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Variable, Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -1097,10 +1128,10 @@ impl ser::Serializer for Serializer {
         value: &T,
     ) -> Result<Variable, Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        let mut values = BTreeMap::new();
-        values.insert(String::from(variant), Rcvar::new(to_variable(&value)?));
+        let mut values = Map::new();
+        values.insert(String::from(variant), Rcvar::new(to_variable(value)?));
         Ok(Variable::Object(values))
     }
 
@@ -1110,9 +1141,9 @@ impl ser::Serializer for Serializer {
     }
 
     #[inline]
-    fn serialize_some<V: ?Sized>(self, value: &V) -> Result<Variable, Error>
+    fn serialize_some<V>(self, value: &V) -> Result<Variable, Error>
     where
-        V: ser::Serialize,
+        V: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
@@ -1144,7 +1175,7 @@ impl ser::Serializer for Serializer {
 
     fn serialize_map(self, _len: Option<usize>) -> Result<MapState, Error> {
         Ok(MapState {
-            map: BTreeMap::new(),
+            map: Map::new(),
             next_key: None,
         })
     }
@@ -1162,7 +1193,7 @@ impl ser::Serializer for Serializer {
     ) -> Result<StructVariantState, Error> {
         Ok(StructVariantState {
             name: String::from(variant),
-            map: BTreeMap::new(),
+            map: Map::new(),
         })
     }
 }
@@ -1171,9 +1202,9 @@ impl ser::SerializeSeq for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         self.0.push(Rcvar::new(to_variable(value)?));
         Ok(())
@@ -1188,9 +1219,9 @@ impl ser::SerializeTuple for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -1204,9 +1235,9 @@ impl ser::SerializeTupleStruct for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -1220,16 +1251,16 @@ impl ser::SerializeTupleVariant for TupleVariantState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        self.vec.push(Rcvar::new(to_variable(&value)?));
+        self.vec.push(Rcvar::new(to_variable(value)?));
         Ok(())
     }
 
     fn end(self) -> Result<Variable, Error> {
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert(self.name, Rcvar::new(Variable::Array(self.vec)));
         Ok(Variable::Object(object))
     }
@@ -1239,9 +1270,9 @@ impl ser::SerializeMap for MapState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         match to_variable(key)? {
             Variable::String(s) => self.next_key = Some(s),
@@ -1250,9 +1281,9 @@ impl ser::SerializeMap for MapState {
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         let key = self
             .next_key
@@ -1271,15 +1302,21 @@ impl ser::SerializeStruct for MapState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeMap::serialize_key(self, key)?;
         ser::SerializeMap::serialize_value(self, value)
     }
 
     fn end(self) -> Result<Variable, Error> {
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            if let Some(number) = arbitrary_precision_number(&self.map) {
+                return number;
+            }
+        }
         ser::SerializeMap::end(self)
     }
 }
@@ -1288,9 +1325,9 @@ impl ser::SerializeStructVariant for StructVariantState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         self.map
             .insert(String::from(key), Rcvar::new(to_variable(value)?));
@@ -1298,7 +1335,7 @@ impl ser::SerializeStructVariant for StructVariantState {
     }
 
     fn end(self) -> Result<Variable, Error> {
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert(self.name, Rcvar::new(Variable::Object(self.map)));
         Ok(Variable::Object(object))
     }
@@ -1310,7 +1347,6 @@ mod tests {
     use crate::ast::{Ast, Comparator};
     use crate::Rcvar;
     use serde_json::{self, Number, Value};
-    use std::collections::BTreeMap;
 
     #[test]
     fn creates_variable_from_str() {
@@ -1325,13 +1361,13 @@ mod tests {
     fn test_determines_types() {
         assert_eq!(
             JmespathType::Object,
-            Variable::from_json(&"{\"foo\": \"bar\"}")
+            Variable::from_json("{\"foo\": \"bar\"}")
                 .unwrap()
                 .get_type()
         );
         assert_eq!(
             JmespathType::Array,
-            Variable::from_json(&"[\"foo\"]").unwrap().get_type()
+            Variable::from_json("[\"foo\"]").unwrap().get_type()
         );
         assert_eq!(JmespathType::Null, Variable::Null.get_type());
         assert_eq!(JmespathType::Boolean, Variable::Bool(true).get_type());
@@ -1347,28 +1383,19 @@ mod tests {
 
     #[test]
     fn test_is_truthy() {
-        assert_eq!(
-            true,
-            Variable::from_json(&"{\"foo\": \"bar\"}")
-                .unwrap()
-                .is_truthy()
-        );
-        assert_eq!(false, Variable::from_json(&"{}").unwrap().is_truthy());
-        assert_eq!(true, Variable::from_json(&"[\"foo\"]").unwrap().is_truthy());
-        assert_eq!(false, Variable::from_json(&"[]").unwrap().is_truthy());
-        assert_eq!(false, Variable::Null.is_truthy());
-        assert_eq!(true, Variable::Bool(true).is_truthy());
-        assert_eq!(false, Variable::Bool(false).is_truthy());
-        assert_eq!(true, Variable::String("foo".to_string()).is_truthy());
-        assert_eq!(false, Variable::String("".to_string()).is_truthy());
-        assert_eq!(
-            true,
-            Variable::Number(Number::from_f64(10.0).unwrap()).is_truthy()
-        );
-        assert_eq!(
-            true,
-            Variable::Number(Number::from_f64(0.0).unwrap()).is_truthy()
-        );
+        assert!(Variable::from_json("{\"foo\": \"bar\"}")
+            .unwrap()
+            .is_truthy());
+        assert!(!Variable::from_json("{}").unwrap().is_truthy());
+        assert!(Variable::from_json("[\"foo\"]").unwrap().is_truthy());
+        assert!(!Variable::from_json("[]").unwrap().is_truthy());
+        assert!(!Variable::Null.is_truthy());
+        assert!(Variable::Bool(true).is_truthy());
+        assert!(!Variable::Bool(false).is_truthy());
+        assert!(Variable::String("foo".to_string()).is_truthy());
+        assert!(!Variable::String("".to_string()).is_truthy());
+        assert!(Variable::Number(Number::from_f64(10.0).unwrap()).is_truthy());
+        assert!(Variable::Number(Number::from_f64(0.0).unwrap()).is_truthy());
     }
 
     #[test]
@@ -1395,6 +1422,53 @@ mod tests {
         assert_eq!(Some(false), r.compare(&Comparator::LessThanEqual, &l));
     }
 
+    #[test]
+    fn test_arithmetic() {
+        let int = |n: i64| Variable::Number(Number::from(n));
+        let float = |n: f64| Variable::Number(Number::from_f64(n).unwrap());
+        let add = ArithmeticOperator::Add;
+        assert_eq!(Some(int(3)), int(1).arithmetic(&add, &int(2)));
+        assert_eq!("3", int(1).arithmetic(&add, &int(2)).unwrap().to_string());
+        assert_eq!(Some(float(3.5)), int(1).arithmetic(&add, &float(2.5)));
+        assert_eq!(
+            Some(int(-2)),
+            int(1).arithmetic(&ArithmeticOperator::Subtract, &int(3))
+        );
+        assert_eq!(
+            Some(int(6)),
+            int(2).arithmetic(&ArithmeticOperator::Multiply, &int(3))
+        );
+        assert_eq!(
+            Some(float(1.5)),
+            int(3).arithmetic(&ArithmeticOperator::Divide, &int(2))
+        );
+        assert_eq!(
+            Some(int(-2)),
+            int(-3).arithmetic(&ArithmeticOperator::IntegerDivide, &int(2))
+        );
+        assert_eq!(
+            Some(int(1)),
+            int(-3).arithmetic(&ArithmeticOperator::Modulo, &int(2))
+        );
+        assert_eq!(
+            Some(float(0.5)),
+            float(-1.5).arithmetic(&ArithmeticOperator::Modulo, &int(2))
+        );
+        assert_eq!(
+            None,
+            int(1).arithmetic(&ArithmeticOperator::Divide, &int(0))
+        );
+        assert_eq!(
+            None,
+            int(1).arithmetic(&ArithmeticOperator::IntegerDivide, &int(0))
+        );
+        assert_eq!(
+            None,
+            int(1).arithmetic(&add, &Variable::String("1".to_string()))
+        );
+        assert_eq!(None, Variable::Null.arithmetic(&add, &int(1)));
+    }
+
     #[test]
     fn gets_value_from_object() {
         let var = Variable::from_json("{\"foo\":1}").unwrap();
@@ -1414,8 +1488,8 @@ mod tests {
 
     #[test]
     fn determines_if_null() {
-        assert_eq!(false, Variable::Bool(true).is_null());
-        assert_eq!(true, Variable::Null.is_null());
+        assert!(!Variable::Bool(true).is_null());
+        assert!(Variable::Null.is_null());
     }
 
     #[test]
@@ -1426,8 +1500,8 @@ mod tests {
 
     #[test]
     fn determines_if_boolean() {
-        assert_eq!(true, Variable::Bool(true).is_boolean());
-        assert_eq!(false, Variable::Null.is_boolean());
+        assert!(Variable::Bool(true).is_boolean());
+        assert!(!Variable::Null.is_boolean());
     }
 
     #[test]
@@ -1438,8 +1512,8 @@ mod tests {
 
     #[test]
     fn determines_if_string() {
-        assert_eq!(false, Variable::Bool(true).is_string());
-        assert_eq!(true, Variable::String("foo".to_string()).is_string());
+        assert!(!Variable::Bool(true).is_string());
+        assert!(Variable::String("foo".to_string()).is_string());
     }
 
     #[test]
@@ -1491,10 +1565,7 @@ mod tests {
 
     #[test]
     fn test_is_expref() {
-        assert_eq!(
-            true,
-            Variable::Expref(Ast::Identity { offset: 0 }).is_expref()
-        );
+        assert!(Variable::Expref(Ast::Identity { offset: 0 }).is_expref());
         assert_eq!(
             &Ast::Identity { offset: 0 },
             Variable::Expref(Ast::Identity { offset: 0 })
@@ -1536,8 +1607,8 @@ mod tests {
     #[test]
     fn test_parses_json_object() {
         let var = Variable::from_json("{\"a\": 1, \"b\": {\"c\": true}}").unwrap();
-        let mut expected = BTreeMap::new();
-        let mut sub_obj = BTreeMap::new();
+        let mut expected = Map::new();
+        let mut sub_obj = Map::new();
         expected.insert(
             "a".to_string(),
             Rcvar::new(Variable::Number(Number::from_f64(1.0).unwrap())),
@@ -1575,6 +1646,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(not(feature = "arbitrary_precision"))]
     fn test_compares_float_equality() {
         assert_eq!(
             Variable::Number(Number::from_f64(1.0).unwrap()),
@@ -1615,7 +1687,7 @@ mod tests {
         #[derive(serde_derive::Serialize)]
         struct Map {
             num: usize,
-        };
+        }
 
         let map = Map { num: 231 };
 
@@ -1630,7 +1702,7 @@ mod tests {
         #[derive(serde_derive::Serialize)]
         struct Map {
             num: isize,
-        };
+        }
 
         let map = Map { num: -2141 };
 
@@ -1645,7 +1717,7 @@ mod tests {
         #[derive(serde_derive::Serialize)]
         struct Map {
             num: f64,
-        };
+        }
 
         let map = Map { num: 41.0 };
 
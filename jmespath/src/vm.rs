@@ -0,0 +1,719 @@
+//! Compiles ASTs to bytecode that is evaluated without recursion.
+//!
+//! Each node is lowered to a sequence of instructions that replaces the
+//! value on the top of the stack (the current node) with the result of the
+//! node. Projections iterate using frames that are kept on a separate stack,
+//! so neither compiling nor evaluating an expression recurses on the depth
+//! of the AST.
+
+use crate::ast::{ArithmeticOperator, Ast, Comparator, UnaryOperator};
+use crate::interpreter::{arithmetic, unary_arithmetic, SearchResult};
+use crate::variable::Variable;
+use crate::{Context, ErrorReason, JmespathError, Map, Rcvar, RuntimeError};
+
+/// A single bytecode instruction.
+///
+/// Jump targets are indexes into the instructions of the program.
+#[derive(Clone, Debug)]
+enum Instruction {
+    /// Replaces the current node with one of its fields.
+    Field(String),
+    /// Replaces the current node with one of its elements.
+    Index(i32),
+    /// Replaces the current node with a literal value.
+    Literal(Rcvar),
+    /// Replaces the current node with the root node.
+    Root,
+    /// Replaces the current node with the value of a variable.
+    Variable { name: String, offset: usize },
+    /// Pushes a copy of the current node.
+    Dup,
+    /// Swaps the top two values of the stack.
+    Swap,
+    /// Pops the current node.
+    Pop,
+    /// Replaces the current node with the negation of its truthiness.
+    Not,
+    /// Pops the right and left hand sides and pushes their comparison.
+    Compare(Comparator),
+    /// Pops the right and left hand sides and pushes their result.
+    Arithmetic {
+        operator: ArithmeticOperator,
+        offset: usize,
+    },
+    /// Replaces the current node with the result of a unary operator.
+    UnaryArithmetic {
+        operator: UnaryOperator,
+        offset: usize,
+    },
+    /// Pops a value, and if it is truthy, replaces the current node with it
+    /// and jumps to the target.
+    JumpIfTruthy(usize),
+    /// Pops a value, and if it is falsy, replaces the current node with it
+    /// and jumps to the target.
+    JumpIfFalsy(usize),
+    /// Pops a predicate result, and if it is falsy, replaces the current
+    /// node with null and jumps to the target.
+    Filter(usize),
+    /// Jumps to the target if the current node is null.
+    JumpIfNull(usize),
+    /// Pops an array and starts projecting its elements, or replaces the
+    /// current node with null and jumps to the target if it is not an array.
+    Iterate(usize),
+    /// Pushes the next element of the projection, or jumps to the target if
+    /// every element was projected.
+    Next(usize),
+    /// Pops the result of projecting an element, collecting it if it is not
+    /// null, and jumps to the target to project the next element.
+    Collect(usize),
+    /// Finishes the projection and pushes the collected results.
+    EndIterate,
+    /// Replaces the current node with its flattened elements.
+    Flatten,
+    /// Replaces the current node with its values.
+    Values,
+    /// Replaces the current node with a slice of its elements.
+    Slice {
+        start: Option<i32>,
+        stop: Option<i32>,
+        step: i32,
+        offset: usize,
+    },
+    /// Pops the given number of values and pushes them as an array.
+    MakeArray(usize),
+    /// Pops a value for each key and pushes them as an object.
+    MakeObject(Vec<String>),
+    /// Pops the given number of arguments and pushes the result of calling
+    /// a function with them.
+    Call {
+        name: String,
+        args: usize,
+        offset: usize,
+    },
+    /// Pops the current node and a value for each name, binds the values to
+    /// the names in a new scope, and pushes the current node.
+    PushScope(Vec<String>),
+    /// Pops the innermost scope.
+    PopScope,
+}
+
+impl Instruction {
+    /// Returns true if the instruction can push a new array, object, or
+    /// string rather than part of its input.
+    fn produces_value(&self) -> bool {
+        matches!(
+            *self,
+            Instruction::EndIterate
+                | Instruction::Flatten
+                | Instruction::Values
+                | Instruction::Slice { .. }
+                | Instruction::MakeArray(_)
+                | Instruction::MakeObject(_)
+                | Instruction::Call { .. }
+        )
+    }
+}
+
+/// Work remaining while compiling an AST.
+enum Task<'a> {
+    Node(&'a Ast),
+    Emit(Instruction),
+    Label(usize),
+}
+
+/// An AST compiled to bytecode.
+#[derive(Clone, Debug)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+}
+
+impl Program {
+    /// Compiles an AST to bytecode.
+    pub fn compile(ast: &Ast) -> Program {
+        let mut compiler = Compiler::default();
+        let mut tasks = vec![Task::Node(ast)];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Node(node) => {
+                    let expanded = compiler.expand(node);
+                    tasks.extend(expanded.into_iter().rev());
+                }
+                Task::Emit(instruction) => compiler.instructions.push(instruction),
+                Task::Label(label) => compiler.labels[label] = compiler.instructions.len(),
+            }
+        }
+        compiler.finish()
+    }
+
+    /// Evaluates the program against the given data.
+    pub fn execute(&self, data: &Rcvar, ctx: &mut Context<'_>) -> SearchResult {
+        let mut scopes = 0;
+        let result = self.run(data, ctx, &mut scopes);
+        // Scopes are only left on the context when evaluation fails.
+        for _ in 0..scopes {
+            ctx.pop_scope();
+        }
+        result
+    }
+
+    fn run(&self, data: &Rcvar, ctx: &mut Context<'_>, scopes: &mut usize) -> SearchResult {
+        let mut stack = vec![data.clone()];
+        let mut frames: Vec<Frame> = vec![];
+        let mut pc = 0;
+        while let Some(instruction) = self.instructions.get(pc) {
+            pc += 1;
+            ctx.step(ctx.offset)?;
+            match *instruction {
+                Instruction::Field(ref name) => {
+                    let current = top(&mut stack);
+                    *current = current.get_field(name);
+                }
+                Instruction::Index(idx) => {
+                    let current = top(&mut stack);
+                    *current = if idx >= 0 {
+                        current.get_index(idx as usize)
+                    } else {
+                        current.get_negative_index((-idx) as usize)
+                    };
+                }
+                Instruction::Literal(ref value) => *top(&mut stack) = value.clone(),
+                Instruction::Root => {
+                    *top(&mut stack) = ctx
+                        .root()
+                        .cloned()
+                        .unwrap_or_else(|| Rcvar::new(Variable::Null));
+                }
+                Instruction::Variable { ref name, offset } => match ctx.get_variable(name) {
+                    Some(value) => *top(&mut stack) = value,
+                    None => {
+                        ctx.offset = offset;
+                        let reason =
+                            ErrorReason::Runtime(RuntimeError::UndefinedVariable(name.to_owned()));
+                        return Err(JmespathError::from_ctx(ctx, reason));
+                    }
+                },
+                Instruction::Dup => {
+                    let current = top(&mut stack).clone();
+                    stack.push(current);
+                }
+                Instruction::Swap => {
+                    let len = stack.len();
+                    stack.swap(len - 1, len - 2);
+                }
+                Instruction::Pop => {
+                    pop(&mut stack);
+                }
+                Instruction::Not => {
+                    let current = top(&mut stack);
+                    *current = Rcvar::new(Variable::Bool(!current.is_truthy()));
+                }
+                Instruction::Compare(ref comparator) => {
+                    let right = pop(&mut stack);
+                    let current = top(&mut stack);
+                    *current = current
+                        .compare(comparator, &right)
+                        .map_or(Rcvar::new(Variable::Null), |result| {
+                            Rcvar::new(Variable::Bool(result))
+                        });
+                }
+                Instruction::Arithmetic {
+                    ref operator,
+                    offset,
+                } => {
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    stack.push(arithmetic(&left, operator, &right, offset, ctx)?);
+                }
+                Instruction::UnaryArithmetic {
+                    ref operator,
+                    offset,
+                } => {
+                    let operand = pop(&mut stack);
+                    stack.push(unary_arithmetic(operator, &operand, offset, ctx)?);
+                }
+                Instruction::JumpIfTruthy(target) => {
+                    let value = pop(&mut stack);
+                    if value.is_truthy() {
+                        *top(&mut stack) = value;
+                        pc = target;
+                    }
+                }
+                Instruction::JumpIfFalsy(target) => {
+                    let value = pop(&mut stack);
+                    if !value.is_truthy() {
+                        *top(&mut stack) = value;
+                        pc = target;
+                    }
+                }
+                Instruction::Filter(target) => {
+                    if !pop(&mut stack).is_truthy() {
+                        *top(&mut stack) = Rcvar::new(Variable::Null);
+                        pc = target;
+                    }
+                }
+                Instruction::JumpIfNull(target) => {
+                    if top(&mut stack).is_null() {
+                        pc = target;
+                    }
+                }
+                Instruction::Iterate(target) => {
+                    if top(&mut stack).is_array() {
+                        frames.push(Frame {
+                            elements: pop(&mut stack),
+                            index: 0,
+                            collected: vec![],
+                        });
+                    } else {
+                        *top(&mut stack) = Rcvar::new(Variable::Null);
+                        pc = target;
+                    }
+                }
+                Instruction::Next(target) => {
+                    let frame = frames.last_mut().expect("projection frame");
+                    match frame.elements.as_array().and_then(|a| a.get(frame.index)) {
+                        Some(element) => {
+                            stack.push(element.clone());
+                            frame.index += 1;
+                        }
+                        None => pc = target,
+                    }
+                }
+                Instruction::Collect(target) => {
+                    let value = pop(&mut stack);
+                    if !value.is_null() {
+                        let frame = frames.last_mut().expect("projection frame");
+                        frame.collected.push(value);
+                    }
+                    pc = target;
+                }
+                Instruction::EndIterate => {
+                    let frame = frames.pop().expect("projection frame");
+                    stack.push(Rcvar::new(Variable::Array(frame.collected)));
+                }
+                Instruction::Flatten => {
+                    let current = top(&mut stack);
+                    *current = match current.as_array() {
+                        None => Rcvar::new(Variable::Null),
+                        Some(a) => {
+                            let mut collected: Vec<Rcvar> = vec![];
+                            for element in a {
+                                match element.as_array() {
+                                    Some(array) => collected.extend(array.iter().cloned()),
+                                    _ => collected.push(element.clone()),
+                                }
+                            }
+                            Rcvar::new(Variable::Array(collected))
+                        }
+                    };
+                }
+                Instruction::Values => {
+                    let current = top(&mut stack);
+                    *current = match **current {
+                        Variable::Object(ref v) => {
+                            Rcvar::new(Variable::Array(v.values().cloned().collect()))
+                        }
+                        _ => Rcvar::new(Variable::Null),
+                    };
+                }
+                Instruction::Slice {
+                    start,
+                    stop,
+                    step,
+                    offset,
+                } => {
+                    if step == 0 {
+                        ctx.offset = offset;
+                        let reason = ErrorReason::Runtime(RuntimeError::InvalidSlice);
+                        return Err(JmespathError::from_ctx(ctx, reason));
+                    }
+                    let current = top(&mut stack);
+                    *current = match current.slice(start, stop, step) {
+                        Some(array) => Rcvar::new(Variable::Array(array)),
+                        None => Rcvar::new(Variable::Null),
+                    };
+                }
+                Instruction::MakeArray(len) => {
+                    let elements = stack.split_off(stack.len() - len);
+                    stack.push(Rcvar::new(Variable::Array(elements)));
+                }
+                Instruction::MakeObject(ref keys) => {
+                    let values = stack.split_off(stack.len() - keys.len());
+                    let object: Map<String, Rcvar> = keys.iter().cloned().zip(values).collect();
+                    stack.push(Rcvar::new(Variable::Object(object)));
+                }
+                Instruction::Call {
+                    ref name,
+                    args,
+                    offset,
+                } => {
+                    let args = stack.split_off(stack.len() - args);
+                    // Reset the offset so that it points to the function being evaluated.
+                    ctx.offset = offset;
+                    let result = match ctx.runtime.get_function(name) {
+                        Some(f) => f.evaluate(&args, ctx)?,
+                        None => {
+                            let reason = ErrorReason::Runtime(RuntimeError::UnknownFunction(
+                                name.to_owned(),
+                            ));
+                            return Err(JmespathError::from_ctx(ctx, reason));
+                        }
+                    };
+                    stack.push(result);
+                }
+                Instruction::PushScope(ref names) => {
+                    let current = pop(&mut stack);
+                    let values = stack.split_off(stack.len() - names.len());
+                    ctx.push_scope(names.iter().cloned().zip(values).collect());
+                    *scopes += 1;
+                    stack.push(current);
+                }
+                Instruction::PopScope => {
+                    ctx.pop_scope();
+                    *scopes -= 1;
+                }
+            }
+            if instruction.produces_value() {
+                ctx.check_value(top(&mut stack), ctx.offset)?;
+            }
+        }
+        Ok(pop(&mut stack))
+    }
+}
+
+/// State of a projection that is being evaluated.
+struct Frame {
+    /// Array that is being projected.
+    elements: Rcvar,
+    /// Index of the next element to project.
+    index: usize,
+    /// Non-null results of projecting each element.
+    collected: Vec<Rcvar>,
+}
+
+fn top(stack: &mut [Rcvar]) -> &mut Rcvar {
+    stack.last_mut().expect("stack is not empty")
+}
+
+fn pop(stack: &mut Vec<Rcvar>) -> Rcvar {
+    stack.pop().expect("stack is not empty")
+}
+
+/// Lowers AST nodes to instructions.
+///
+/// Jump instructions initially refer to labels, which are resolved to
+/// instruction indexes once every instruction has been emitted.
+#[derive(Default)]
+struct Compiler {
+    instructions: Vec<Instruction>,
+    labels: Vec<usize>,
+}
+
+impl Compiler {
+    fn label(&mut self) -> usize {
+        self.labels.push(0);
+        self.labels.len() - 1
+    }
+
+    /// Returns the tasks that evaluate a node, in order.
+    fn expand<'a>(&mut self, node: &'a Ast) -> Vec<Task<'a>> {
+        use self::Instruction::*;
+        use self::Task::*;
+        match *node {
+            Ast::Identity { .. } => vec![],
+            Ast::Field { ref name, .. } => vec![Emit(Field(name.clone()))],
+            Ast::Index { idx, .. } => vec![Emit(Index(idx))],
+            Ast::Literal { ref value, .. } => vec![Emit(Literal(value.clone()))],
+            Ast::Root { .. } => vec![Emit(Root)],
+            Ast::VariableRef { ref name, offset } => vec![Emit(Variable {
+                name: name.clone(),
+                offset,
+            })],
+            Ast::Expref { ref ast, .. } => {
+                let value = Rcvar::new(crate::Variable::Expref(*ast.clone()));
+                vec![Emit(Literal(value))]
+            }
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => vec![Node(lhs), Node(rhs)],
+            Ast::Comparison {
+                ref comparator,
+                ref lhs,
+                ref rhs,
+                ..
+            } => vec![
+                Emit(Dup),
+                Node(lhs),
+                Emit(Swap),
+                Node(rhs),
+                Emit(Compare(comparator.clone())),
+            ],
+            Ast::Arithmetic {
+                ref operator,
+                ref lhs,
+                ref rhs,
+                offset,
+            } => vec![
+                Emit(Dup),
+                Node(lhs),
+                Emit(Swap),
+                Node(rhs),
+                Emit(Arithmetic {
+                    operator: operator.clone(),
+                    offset,
+                }),
+            ],
+            Ast::Or {
+                ref lhs, ref rhs, ..
+            } => {
+                let end = self.label();
+                vec![
+                    Emit(Dup),
+                    Node(lhs),
+                    Emit(JumpIfTruthy(end)),
+                    Node(rhs),
+                    Label(end),
+                ]
+            }
+            Ast::And {
+                ref lhs, ref rhs, ..
+            } => {
+                let end = self.label();
+                vec![
+                    Emit(Dup),
+                    Node(lhs),
+                    Emit(JumpIfFalsy(end)),
+                    Node(rhs),
+                    Label(end),
+                ]
+            }
+            Ast::UnaryArithmetic {
+                ref operator,
+                ref node,
+                offset,
+            } => vec![
+                Node(node),
+                Emit(UnaryArithmetic {
+                    operator: operator.clone(),
+                    offset,
+                }),
+            ],
+            Ast::Not { ref node, .. } => vec![Node(node), Emit(Not)],
+            Ast::Condition {
+                ref predicate,
+                ref then,
+                ..
+            } => {
+                let end = self.label();
+                vec![
+                    Emit(Dup),
+                    Node(predicate),
+                    Emit(Filter(end)),
+                    Node(then),
+                    Label(end),
+                ]
+            }
+            Ast::Projection {
+                ref lhs, ref rhs, ..
+            } => {
+                let (next, done, end) = (self.label(), self.label(), self.label());
+                vec![
+                    Node(lhs),
+                    Emit(Iterate(end)),
+                    Label(next),
+                    Emit(Next(done)),
+                    Node(rhs),
+                    Emit(Collect(next)),
+                    Label(done),
+                    Emit(EndIterate),
+                    Label(end),
+                ]
+            }
+            Ast::Flatten { ref node, .. } => vec![Node(node), Emit(Flatten)],
+            Ast::ObjectValues { ref node, .. } => vec![Node(node), Emit(Values)],
+            Ast::Slice {
+                start,
+                stop,
+                step,
+                offset,
+            } => vec![Emit(Slice {
+                start,
+                stop,
+                step,
+                offset,
+            })],
+            Ast::MultiList { ref elements, .. } => {
+                let end = self.label();
+                let mut tasks = vec![Emit(JumpIfNull(end))];
+                tasks.extend(collect(elements.iter()));
+                tasks.extend(vec![Emit(Pop), Emit(MakeArray(elements.len())), Label(end)]);
+                tasks
+            }
+            Ast::MultiHash { ref elements, .. } => {
+                let end = self.label();
+                let keys = elements.iter().map(|kvp| kvp.key.clone()).collect();
+                let mut tasks = vec![Emit(JumpIfNull(end))];
+                tasks.extend(collect(elements.iter().map(|kvp| &kvp.value)));
+                tasks.extend(vec![Emit(Pop), Emit(MakeObject(keys)), Label(end)]);
+                tasks
+            }
+            Ast::Function {
+                ref name,
+                ref args,
+                offset,
+            } => {
+                let mut tasks = collect(args.iter());
+                tasks.push(Emit(Pop));
+                tasks.push(Emit(Call {
+                    name: name.clone(),
+                    args: args.len(),
+                    offset,
+                }));
+                tasks
+            }
+            Ast::Let {
+                ref bindings,
+                ref expr,
+                ..
+            } => {
+                let names = bindings.iter().map(|b| b.name.clone()).collect();
+                let mut tasks = collect(bindings.iter().map(|b| &b.value));
+                tasks.extend(vec![Emit(PushScope(names)), Node(expr), Emit(PopScope)]);
+                tasks
+            }
+        }
+    }
+
+    /// Resolves labels to instruction indexes.
+    fn finish(self) -> Program {
+        let labels = self.labels;
+        let resolve = |label: usize| labels[label];
+        let instructions = self
+            .instructions
+            .into_iter()
+            .map(|instruction| match instruction {
+                Instruction::JumpIfTruthy(l) => Instruction::JumpIfTruthy(resolve(l)),
+                Instruction::JumpIfFalsy(l) => Instruction::JumpIfFalsy(resolve(l)),
+                Instruction::Filter(l) => Instruction::Filter(resolve(l)),
+                Instruction::JumpIfNull(l) => Instruction::JumpIfNull(resolve(l)),
+                Instruction::Iterate(l) => Instruction::Iterate(resolve(l)),
+                Instruction::Next(l) => Instruction::Next(resolve(l)),
+                Instruction::Collect(l) => Instruction::Collect(resolve(l)),
+                instruction => instruction,
+            })
+            .collect();
+        Program { instructions }
+    }
+}
+
+/// Returns tasks that evaluate each node against the current node, leaving
+/// the results below the current node on the stack.
+fn collect<'a, I: Iterator<Item = &'a Ast>>(nodes: I) -> Vec<Task<'a>> {
+    let mut tasks = vec![];
+    for node in nodes {
+        tasks.push(Task::Emit(Instruction::Dup));
+        tasks.push(Task::Node(node));
+        tasks.push(Task::Emit(Instruction::Swap));
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compile, parse, Runtime};
+
+    fn execute(expression: &str, data: &str) -> SearchResult {
+        let ast = parse(expression).unwrap();
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        let data = Rcvar::new(Variable::from_json(data).unwrap());
+        let mut ctx = Context::new(expression, &runtime).with_root(data.clone());
+        Program::compile(&ast).execute(&data, &mut ctx)
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        let data = r#"{"a": [{"b": 1, "c": [1, 2]}, {"b": 2, "c": [3]}, {"c": 4}]}"#;
+        let cases = vec![
+            ("a[*].b", "[1,2]"),
+            ("a[?b > `1`].c[0]", "[3]"),
+            ("a[*].c[]", "[1,2,3,4]"),
+            ("a[0].c || a[1].c", "[1,2]"),
+            ("a[2].b && a[0].b", "null"),
+            ("a[*].[b, c[0]]", "[[1,1],[2,3],[null,null]]"),
+            ("a[0].{x: b, y: c[-1]}", r#"{"x":1,"y":2}"#),
+            ("max_by(a[:2], &b).b", "2"),
+            ("let $x = a[0].b in a[?b == $x].c | [0]", "[1,2]"),
+            ("a[0].c[::-1].[@, $.a[1].b]", "[[2,2],[1,2]]"),
+        ];
+        for (expression, expected) in cases {
+            let expected = Rcvar::new(Variable::from_json(expected).unwrap());
+            assert_eq!(
+                expected,
+                execute(expression, data).unwrap(),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn returns_errors_with_offsets() {
+        let err = execute("foo || a[::0]", "{}").unwrap_err();
+        assert_eq!(ErrorReason::Runtime(RuntimeError::InvalidSlice), err.reason);
+        assert_eq!(12, err.offset);
+        let err = execute("[foo, bar * `2`]", "{}").unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::InvalidType {
+                expected: "number".to_owned(),
+                actual: "null".to_owned(),
+                position: 0,
+            }),
+            err.reason
+        );
+        assert_eq!(10, err.offset);
+        let err = execute("[$x]", "{}").unwrap_err();
+        assert_eq!(
+            ErrorReason::Runtime(RuntimeError::UndefinedVariable("x".to_owned())),
+            err.reason
+        );
+    }
+
+    #[test]
+    fn pops_scopes_when_evaluation_fails() {
+        let runtime = Runtime::new();
+        let data = Rcvar::new(Variable::Null);
+        let mut ctx = Context::new("", &runtime);
+        let program = Program::compile(&parse("let $x = `1` in unknown($x)").unwrap());
+        assert!(program.execute(&data, &mut ctx).is_err());
+        assert_eq!(None, ctx.get_variable("x"));
+    }
+
+    #[test]
+    fn evaluates_deeply_nested_expressions() {
+        let depth = 10_000;
+        let mut ast = Ast::Identity { offset: 0 };
+        for _ in 0..depth {
+            ast = Ast::Projection {
+                offset: 0,
+                lhs: Box::new(Ast::Identity { offset: 0 }),
+                rhs: Box::new(ast),
+            };
+        }
+        let runtime = Runtime::new();
+        let data = Rcvar::new(Variable::from_json("[]").unwrap());
+        let mut ctx = Context::new("", &runtime);
+        let result = Program::compile(&ast).execute(&data, &mut ctx).unwrap();
+        assert_eq!(data, result);
+    }
+
+    #[test]
+    fn selects_backend_per_expression() {
+        let mut expr = compile("a.b").unwrap();
+        assert_eq!(crate::Backend::Interpreter, expr.backend());
+        expr.set_backend(crate::Backend::Bytecode);
+        assert_eq!(crate::Backend::Bytecode, expr.backend());
+        let data = Variable::from_json(r#"{"a": {"b": true}}"#).unwrap();
+        assert_eq!(Rcvar::new(Variable::Bool(true)), expr.search(data).unwrap());
+    }
+}
@@ -6,7 +6,19 @@
 use serde_json::Value;
 use std::fmt;
 
-use jmespath::{compile, Expression, Rcvar, RuntimeError, Variable};
+use jmespath::analysis::Shape;
+use jmespath::{compile, Backend, Expression, Rcvar, Runtime, RuntimeError, Variable};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Runtime that optimizes compiled expressions.
+    static ref OPTIMIZING_RUNTIME: Runtime = {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        runtime.set_optimize(true);
+        runtime
+    };
+}
 
 /// Avaliable benchmark types.
 pub enum BenchType {
@@ -29,17 +41,17 @@ impl BenchType {
                 "parse" => Ok(BenchType::Parse),
                 "interpret" => Ok(BenchType::Interpret),
                 "full" => Ok(BenchType::Full),
-                s @ _ => Err(TestCaseError::UnknownBenchType(s.to_string())),
+                s => Err(TestCaseError::UnknownBenchType(s.to_string())),
             })
     }
 }
 
 impl fmt::Display for BenchType {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        match self {
-            &BenchType::Parse => write!(fmt, "parse"),
-            &BenchType::Interpret => write!(fmt, "interpret"),
-            &BenchType::Full => write!(fmt, "full"),
+        match *self {
+            BenchType::Parse => write!(fmt, "parse"),
+            BenchType::Interpret => write!(fmt, "interpret"),
+            BenchType::Full => write!(fmt, "full"),
         }
     }
 }
@@ -54,6 +66,8 @@ pub enum ErrorType {
     InvalidSlice,
     /// Ensures that the expression fails due to an unknown-function error.
     UnknownFunction,
+    /// Ensures that the expression fails due to an undefined-variable error.
+    UndefinedVariable,
     /// Ensures that an expression cannot be parsed due to a syntax error.
     SyntaxError,
 }
@@ -70,7 +84,8 @@ impl ErrorType {
                 "invalid-value" => Ok(ErrorType::InvalidSlice),
                 "invalid-arity" => Ok(ErrorType::InvalidArity),
                 "unknown-function" => Ok(ErrorType::UnknownFunction),
-                e @ _ => Err(TestCaseError::UnknownErrorType(e.to_string())),
+                "undefined-variable" => Ok(ErrorType::UndefinedVariable),
+                e => Err(TestCaseError::UnknownErrorType(e.to_string())),
             })
     }
 }
@@ -78,12 +93,13 @@ impl ErrorType {
 impl fmt::Display for ErrorType {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         use self::ErrorType::*;
-        match self {
-            &InvalidArity => write!(fmt, "invalid-arity"),
-            &InvalidType => write!(fmt, "invalid-type"),
-            &InvalidSlice => write!(fmt, "invalid-value"),
-            &UnknownFunction => write!(fmt, "unknown-function"),
-            &SyntaxError => write!(fmt, "syntax"),
+        match *self {
+            InvalidArity => write!(fmt, "invalid-arity"),
+            InvalidType => write!(fmt, "invalid-type"),
+            InvalidSlice => write!(fmt, "invalid-value"),
+            UnknownFunction => write!(fmt, "unknown-function"),
+            UndefinedVariable => write!(fmt, "undefined-variable"),
+            SyntaxError => write!(fmt, "syntax"),
         }
     }
 }
@@ -101,54 +117,73 @@ pub enum Assertion {
 impl Assertion {
     /// Runs the assertion of a test case
     pub fn assert(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        self.assert_result(suite, case, given.clone())?;
+        self.assert_optimized(suite, case, given.clone())?;
+        self.assert_bytecode(suite, case, given.clone())?;
+        self.assert_analyzed(suite, case, given.clone())?;
+        self.assert_borrowed(suite, case, given.clone())?;
+        self.assert_searchable(suite, case, given.clone())?;
+        self.assert_paths(suite, case, given)
+    }
+
+    /// Ensures that the expression returns the expected result or error.
+    fn assert_result(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
         match self {
             &Assertion::Bench(_) => Ok(()),
-            &Assertion::ValidResult(ref expected_result) => {
+            Assertion::ValidResult(expected_result) => {
                 let expr = self.try_parse(suite, case)?;
-                match expr.search(given) {
+                match expr.search(given.clone()) {
                     Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
                     Ok(r) => {
                         if *r == **expected_result {
-                            Ok(())
+                            self.assert_round_trip(suite, case, &expr, given, &r)
                         } else {
                             Err(self.err_message(
                                 suite,
                                 case,
-                                format!("{:?}, {}", r, expr.as_ast()),
+                                format!("{:?}, {:#?}", r, expr.as_ast()),
                             ))
                         }
                     }
                 }
             }
-            &Assertion::Error(ref error_type) => {
+            Assertion::Error(error_type) => {
                 use jmespath::ErrorReason::*;
                 let result = self.try_parse(suite, case);
-                match error_type {
-                    &ErrorType::InvalidArity => match result?.search(given).map_err(|e| e.reason) {
+                match *error_type {
+                    ErrorType::InvalidArity => match result?.search(given).map_err(|e| e.reason) {
                         Err(Runtime(RuntimeError::NotEnoughArguments { .. })) => Ok(()),
                         Err(Runtime(RuntimeError::TooManyArguments { .. })) => Ok(()),
                         Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
                         Ok(r) => Err(self.err_message(suite, case, r.to_string())),
                     },
-                    &ErrorType::InvalidType => match result?.search(given).map_err(|e| e.reason) {
+                    ErrorType::InvalidType => match result?.search(given).map_err(|e| e.reason) {
                         Err(Runtime(RuntimeError::InvalidType { .. })) => Ok(()),
                         Err(Runtime(RuntimeError::InvalidReturnType { .. })) => Ok(()),
                         Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
                         Ok(r) => Err(self.err_message(suite, case, r.to_string())),
                     },
-                    &ErrorType::InvalidSlice => match result?.search(given).map_err(|e| e.reason) {
+                    ErrorType::InvalidSlice => match result?.search(given).map_err(|e| e.reason) {
                         Err(Runtime(RuntimeError::InvalidSlice)) => Ok(()),
+                        Err(Runtime(RuntimeError::InvalidValue { .. })) => Ok(()),
                         Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
                         Ok(r) => Err(self.err_message(suite, case, r.to_string())),
                     },
-                    &ErrorType::UnknownFunction => {
+                    ErrorType::UnknownFunction => {
                         match result?.search(given).map_err(|e| e.reason) {
                             Err(Runtime(RuntimeError::UnknownFunction(_))) => Ok(()),
                             Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
                             Ok(r) => Err(self.err_message(suite, case, r.to_string())),
                         }
                     }
-                    &ErrorType::SyntaxError => match result {
+                    ErrorType::UndefinedVariable => {
+                        match result?.search(given).map_err(|e| e.reason) {
+                            Err(Runtime(RuntimeError::UndefinedVariable(_))) => Ok(()),
+                            Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                            Ok(r) => Err(self.err_message(suite, case, r.to_string())),
+                        }
+                    }
+                    ErrorType::SyntaxError => match result {
                         Err(_) => Ok(()),
                         Ok(expr) => {
                             Err(self.err_message(suite, case, format!("Parsed {:?}", expr)))
@@ -159,6 +194,196 @@ impl Assertion {
         }
     }
 
+    /// Ensures that the formatted AST of an expression can be parsed back
+    /// into an expression that returns the same result.
+    fn assert_round_trip(
+        &self,
+        suite: &str,
+        case: &TestCase,
+        expr: &Expression<'_>,
+        given: Rcvar,
+        expected: &Variable,
+    ) -> Result<(), String> {
+        let formatted = expr.as_ast().to_string();
+        let reparsed = compile(&formatted).map_err(|e| {
+            self.err_message(suite, case, format!("{} does not parse: {}", formatted, e))
+        })?;
+        let reformatted = reparsed.as_ast().to_string();
+        if reformatted != formatted {
+            return Err(self.err_message(
+                suite,
+                case,
+                format!("{} was formatted as {}", formatted, reformatted),
+            ));
+        }
+        match reparsed.search(given) {
+            Ok(ref r) if **r == *expected => Ok(()),
+            Ok(r) => Err(self.err_message(suite, case, format!("{} returned {:?}", formatted, r))),
+            Err(e) => Err(self.err_message(suite, case, format!("{} failed: {}", formatted, e))),
+        }
+    }
+
+    /// Ensures that optimizing an expression does not change its result or
+    /// the error that it raises.
+    fn assert_optimized(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        let (expr, optimized) = match (
+            compile(&case.expression),
+            OPTIMIZING_RUNTIME.compile(&case.expression),
+        ) {
+            (Ok(expr), Ok(optimized)) => (expr, optimized),
+            _ => return Ok(()),
+        };
+        match (expr.search(given.clone()), optimized.search(given)) {
+            (Ok(ref a), Ok(ref b)) if a == b => Ok(()),
+            (Err(ref a), Err(ref b)) if a.reason == b.reason => Ok(()),
+            (a, b) => Err(self.err_message(
+                suite,
+                case,
+                format!(
+                    "{:?} was optimized to {:#?}, which returned {:?}",
+                    a,
+                    optimized.as_ast(),
+                    b
+                ),
+            )),
+        }
+    }
+
+    /// Ensures that evaluating an expression as bytecode returns the same
+    /// result or error as interpreting it.
+    fn assert_bytecode(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        let expr = match compile(&case.expression) {
+            Ok(expr) => expr,
+            Err(_) => return Ok(()),
+        };
+        let mut bytecode = expr.clone();
+        bytecode.set_backend(Backend::Bytecode);
+        match (expr.search(given.clone()), bytecode.search(given)) {
+            (Ok(ref a), Ok(ref b)) if a == b => Ok(()),
+            (Err(ref a), Err(ref b)) if a.to_string() == b.to_string() => Ok(()),
+            (a, b) => Err(self.err_message(
+                suite,
+                case,
+                format!("{:?} was evaluated as bytecode to {:?}", a, b),
+            )),
+        }
+    }
+
+    /// Ensures that the result of an expression matches the shape inferred
+    /// by analyzing the expression against the shape of the given data.
+    fn assert_analyzed(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        let expr = match compile(&case.expression) {
+            Ok(expr) => expr,
+            Err(_) => return Ok(()),
+        };
+        let analysis = expr.analyze(&Shape::of(&given));
+        match expr.search(given) {
+            Ok(ref result) if !matches_shape(analysis.result(), result) => Err(self.err_message(
+                suite,
+                case,
+                format!("{:?} does not match {}", result, analysis.result()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ensures that searching borrowed JSON returns the same result or error
+    /// as searching the converted variable.
+    fn assert_borrowed(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        let expr = match compile(&case.expression) {
+            Ok(expr) => expr,
+            Err(_) => return Ok(()),
+        };
+        let json = serde_json::to_value(&*given).unwrap();
+        match (expr.search(given), expr.search_borrowed(&json)) {
+            (Ok(ref a), Ok(ref b)) if a == b => Ok(()),
+            (Err(ref a), Err(ref b)) if a.to_string() == b.to_string() => Ok(()),
+            (a, b) => Err(self.err_message(
+                suite,
+                case,
+                format!("{:?} was searched in place as {:?}", a, b),
+            )),
+        }
+    }
+
+    /// Ensures that searching JSON in place as a `Searchable` returns the
+    /// same result or error as searching the converted variable.
+    fn assert_searchable(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        let expr = match compile(&case.expression) {
+            Ok(expr) => expr,
+            Err(_) => return Ok(()),
+        };
+        let json = serde_json::to_value(&*given).unwrap();
+        match (expr.search(given), expr.search_searchable(&json)) {
+            (Ok(ref a), Ok(ref b)) if a == b => Ok(()),
+            (Err(ref a), Err(ref b)) if a.to_string() == b.to_string() => Ok(()),
+            (a, b) => Err(self.err_message(
+                suite,
+                case,
+                format!("{:?} was searched as a Searchable as {:?}", a, b),
+            )),
+        }
+    }
+
+    /// Ensures that each value located by `search_paths` is the value found
+    /// at its path, that the located values agree with the result of the
+    /// search, and that locating values fails with the same error as
+    /// searching unless paths are not supported for the expression.
+    fn assert_paths(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        use jmespath::ErrorReason::Runtime;
+        let expr = match compile(&case.expression) {
+            Ok(expr) => expr,
+            Err(_) => return Ok(()),
+        };
+        let (result, matches) = match (expr.search(given.clone()), expr.search_paths(given.clone()))
+        {
+            (_, Err(ref e)) if e.reason == Runtime(RuntimeError::UnsupportedPathExpression) => {
+                return Ok(())
+            }
+            (Ok(result), Ok(matches)) => (result, matches),
+            (Err(ref a), Err(ref b)) if a.to_string() == b.to_string() => return Ok(()),
+            (a, b) => {
+                return Err(self.err_message(
+                    suite,
+                    case,
+                    format!("{:?} was located as {:?}", a, b),
+                ))
+            }
+        };
+        let values = matches.iter().map(|m| m.value.clone()).collect::<Vec<_>>();
+        let consistent = if result.is_null() {
+            values.is_empty()
+        } else if !result.is_array() {
+            values == vec![result.clone()]
+        } else {
+            true
+        };
+        if !consistent {
+            return Err(self.err_message(
+                suite,
+                case,
+                format!("{:?} was located as {:?}", result, values),
+            ));
+        }
+        for m in matches {
+            let path = m.jmespath();
+            match compile(&path).and_then(|p| p.search(given.clone())) {
+                Ok(ref value) if *value == m.value => {}
+                other => {
+                    return Err(self.err_message(
+                        suite,
+                        case,
+                        format!(
+                            "{:?} was located at {}, which is {:?}",
+                            m.value, path, other
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Attempts to parse an expression for a case, returning the expression or an error string.
     fn try_parse(&self, suite: &str, case: &TestCase) -> Result<Expression<'_>, String> {
         match compile(&case.expression) {
@@ -180,9 +405,9 @@ impl Assertion {
 impl fmt::Display for Assertion {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
-            &Assertion::Error(ref e) => write!(fmt, "expects error({})", e),
-            &Assertion::Bench(ref b) => write!(fmt, "expects bench({})", b),
-            &Assertion::ValidResult(ref r) => write!(fmt, "expects result({:?})", r),
+            Assertion::Error(e) => write!(fmt, "expects error({})", e),
+            Assertion::Bench(b) => write!(fmt, "expects bench({})", b),
+            Assertion::ValidResult(r) => write!(fmt, "expects result({:?})", r),
         }
     }
 }
@@ -223,9 +448,9 @@ impl<'a> TestSuite<'a> {
             .clone();
         let given = serde_json::from_value::<Variable>(value).map_err(|e| format!("{}", e))?;
         Ok(TestSuite {
-            filename: filename,
+            filename,
             given: Rcvar::new(given),
-            cases: cases,
+            cases,
         })
     }
 }
@@ -248,16 +473,16 @@ impl fmt::Display for TestCaseError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         use self::TestCaseError::*;
         match self {
-            &InvalidJSON(ref msg) => write!(fmt, "invalid test case JSON: {}", msg),
+            InvalidJSON(msg) => write!(fmt, "invalid test case JSON: {}", msg),
             &NoCaseType => write!(fmt, "case has no result, error, or bench"),
             &NoResult => write!(fmt, "test case has no result key"),
             &ResultCannotToString => write!(fmt, "result could not be cast to string"),
             &NoExpression => write!(fmt, "test case has no expression key"),
             &ExpressionIsNotString => write!(fmt, "test case expression is not a string"),
             &ErrorIsNotString => write!(fmt, "test case error value is not a string"),
-            &UnknownErrorType(ref t) => write!(fmt, "unknown error type: {}", t),
+            UnknownErrorType(t) => write!(fmt, "unknown error type: {}", t),
             &BenchIsNotString => write!(fmt, "bench value is not a string"),
-            &UnknownBenchType(ref bench) => write!(
+            UnknownBenchType(bench) => write!(
                 fmt,
                 "unknown bench value: {}, expected one of of parse|full",
                 bench
@@ -282,6 +507,7 @@ pub struct TestCase {
 
 impl TestCase {
     /// Creates a test case from a JSON encoded string.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(case: &str) -> Result<TestCase, TestCaseError> {
         serde_json::from_str::<Value>(case)
             .map_err(|e| TestCaseError::InvalidJSON(e.to_string()))
@@ -326,3 +552,33 @@ impl TestCase {
 }
 
 include!(concat!(env!("OUT_DIR"), "/compliance_tests.rs"));
+
+/// Returns true if a value matches a shape.
+fn matches_shape(shape: &Shape, value: &Variable) -> bool {
+    match (shape, value) {
+        (Shape::Any, _)
+        | (Shape::Null, Variable::Null)
+        | (Shape::Bool, Variable::Bool(_))
+        | (Shape::Number, Variable::Number(_))
+        | (Shape::String, Variable::String(_))
+        | (Shape::Expref, Variable::Expref(_)) => true,
+        (Shape::Array(element), Variable::Array(values)) => {
+            values.iter().all(|value| matches_shape(element, value))
+        }
+        (
+            Shape::Object {
+                properties,
+                additional,
+            },
+            Variable::Object(values),
+        ) => values
+            .iter()
+            .all(|(key, value)| match (properties.get(key), additional) {
+                (Some(shape), _) => matches_shape(shape, value),
+                (None, Some(shape)) => matches_shape(shape, value),
+                (None, None) => false,
+            }),
+        (Shape::Union(shapes), _) => shapes.iter().any(|shape| matches_shape(shape, value)),
+        _ => false,
+    }
+}